@@ -0,0 +1,18 @@
+defmodule {module}Web.Health do
+  @moduledoc """
+  Answers `GET /health` for a service without a REST API.
+  """
+
+  use Plug.Router
+
+  plug :match
+  plug :dispatch
+
+  get "/health" do
+    send_resp(conn, 200, "ok")
+  end
+
+  match _ do
+    send_resp(conn, 404, "")
+  end
+end
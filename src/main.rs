@@ -1,4 +1,4 @@
-use crate::photosphere::{setup, validations::validate_project_name};
+use crate::photosphere::{deps, setup, validations::validate_project_name};
 use anyhow::Result;
 use clap::{ArgEnum, Args, Parser, Subcommand};
 
@@ -17,6 +17,8 @@ struct Cli {
 enum Commands {
     #[clap(arg_required_else_help = true)]
     Service(Service),
+    #[clap(arg_required_else_help = true)]
+    Deps(Deps),
 }
 
 #[derive(Args)]
@@ -39,6 +41,15 @@ pub struct ServiceArgs {
     path: String,
     #[clap(long)]
     ssh: bool,
+    /// Template to generate from: a directory, a .tar.gz or a git URL or mirror
+    #[clap(long)]
+    template: Option<String>,
+    /// Branch, tag or commit of a git template to check out instead of the one its manifest names
+    #[clap(long = "ref")]
+    git_ref: Option<String>,
+    /// Point solfacil git deps to the same scheme (ssh or https) the template was cloned with
+    #[clap(long)]
+    rewrite_git_deps: bool,
     #[clap(long)]
     no_auth: bool,
     #[clap(long)]
@@ -53,8 +64,139 @@ pub struct ServiceArgs {
     no_messaging: bool,
     #[clap(long)]
     no_monitoring: bool,
+    /// Features to take out, by name or flag, as listed in the template feature catalog
+    #[clap(long, use_value_delimiter = true)]
+    without: Vec<String>,
     #[clap(long, arg_enum, default_value_t = Protocol::Rest)]
     protocol: Protocol,
+    /// Keep an HTTP health endpoint on a gRPC-only service
+    #[clap(long)]
+    health: bool,
+    /// Print why each feature ended up enabled or disabled
+    #[clap(long)]
+    explain: bool,
+    /// Keep the half-generated service when generation fails, to debug it
+    #[clap(long)]
+    keep_failed: bool,
+    /// Print what would be done to the template instead of generating the service
+    #[clap(long)]
+    dry_run: bool,
+    /// Format of the `--dry-run` plan
+    #[clap(long, arg_enum, default_value_t = PlanFormat::Text)]
+    format: PlanFormat,
+    /// Print a unified diff of the template and the service, by the step that made each change
+    #[clap(long)]
+    diff: bool,
+}
+
+#[derive(Args)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Deps {
+    #[clap(subcommand)]
+    cmd: DepsCommand,
+}
+
+#[derive(Subcommand)]
+#[clap(arg_required_else_help = true)]
+enum DepsCommand {
+    /// Print the dependency tree, of the whole project or of one dep
+    Tree(DepsTreeArgs),
+    /// Print every chain of deps that brings a dep in
+    Why(DepsWhyArgs),
+    /// Resolve the deps against a local Hex registry snapshot
+    Check(DepsCheckArgs),
+    /// List deps behind the newest release in a local Hex registry snapshot
+    Outdated(DepsOutdatedArgs),
+    /// Add a dep to mix.exs
+    Add(DepsAddArgs),
+    /// Remove a dep from mix.exs
+    Remove(DepsRemoveArgs),
+}
+
+#[derive(Args)]
+pub struct DepsTreeArgs {
+    name: Option<String>,
+    #[clap(long, default_value = ".")]
+    path: String,
+    #[clap(long, arg_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+#[derive(Args)]
+pub struct DepsWhyArgs {
+    name: String,
+    #[clap(long, default_value = ".")]
+    path: String,
+    #[clap(long, arg_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+#[derive(Args)]
+pub struct DepsCheckArgs {
+    #[clap(long, default_value = ".")]
+    path: String,
+    /// Directory of `<package>.json` files, defaults to ~/.photosphere/registry
+    #[clap(long)]
+    registry: Option<String>,
+}
+
+#[derive(Args)]
+pub struct DepsOutdatedArgs {
+    #[clap(long, default_value = ".")]
+    path: String,
+    /// Directory of `<package>.json` files, defaults to ~/.photosphere/registry
+    #[clap(long)]
+    registry: Option<String>,
+    /// Rewrite the requirements that don't allow the newest release
+    #[clap(long)]
+    update: bool,
+}
+
+#[derive(Args)]
+pub struct DepsAddArgs {
+    name: String,
+    #[clap(long, default_value = ".")]
+    path: String,
+    /// Requirement, `~> 1.0`, any release when left out of a hex dep
+    #[clap(long)]
+    version: Option<String>,
+    #[clap(long, use_value_delimiter = true)]
+    only: Vec<String>,
+    #[clap(long, parse(try_from_str))]
+    runtime: Option<bool>,
+    #[clap(long)]
+    git: Option<String>,
+    #[clap(long)]
+    github: Option<String>,
+    #[clap(long)]
+    tag: Option<String>,
+    #[clap(long)]
+    branch: Option<String>,
+    #[clap(long = "ref")]
+    git_ref: Option<String>,
+    #[clap(long = "override")]
+    conflict: bool,
+}
+
+#[derive(Args)]
+pub struct DepsRemoveArgs {
+    name: String,
+    #[clap(long, default_value = ".")]
+    path: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Format {
+    Text,
+    Dot,
+    Json,
+}
+
+// a plan has no graph to draw
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ArgEnum)]
+pub enum PlanFormat {
+    Text,
+    Json,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, ArgEnum)]
@@ -84,6 +226,14 @@ fn main() -> Result<()> {
                 setup::create_service(&mut service, args)?
             }
         },
+        Commands::Deps(deps) => match &deps.cmd {
+            DepsCommand::Tree(args) => deps::print_tree(args)?,
+            DepsCommand::Why(args) => deps::print_why(args)?,
+            DepsCommand::Check(args) => deps::check(args)?,
+            DepsCommand::Outdated(args) => deps::print_outdated(args)?,
+            DepsCommand::Add(args) => deps::add(args)?,
+            DepsCommand::Remove(args) => deps::remove(args)?,
+        },
     }
 
     Ok(())
@@ -1 +1,1650 @@
+use self::ast::{Call, Clause, Def, Expr, If, Module, Sigil};
+use self::cst::{Builder, Checkpoint, SyntaxKind, SyntaxTree};
+use self::lexer::{
+    closing_delim,
+    token::{Fragment, Token},
+    Lexer,
+};
+use anyhow::{anyhow, bail, Result};
+
+pub mod ast;
+pub mod cst;
 pub mod lexer;
+
+// (left, right) binding powers, the lower
+// the looser; see Elixir's operator table
+fn binding_power(op: &str) -> Option<(u8, u8)> {
+    let power = match op {
+        "<-" | "\\\\" => (2, 3),
+        "when" => (4, 4),
+        "::" => (6, 6),
+        "|" => (8, 8),
+        "=" => (14, 14),
+        "||" | "|||" | "or" => (16, 17),
+        "&&" | "&&&" | "and" => (18, 19),
+        "==" | "!=" | "=~" | "===" | "!==" => (20, 21),
+        "<" | ">" | "<=" | ">=" => (22, 23),
+        "|>" | "<<<" | ">>>" | "<<~" | "~>>" | "<~" | "~>" | "<~>" | "<|>" => (24, 25),
+        "in" | "not in" => (26, 27),
+        "^^^" => (28, 29),
+        "++" | "--" | "+++" | "---" | ".." | "<>" | "//" => (32, 32),
+        "+" | "-" => (34, 35),
+        "*" | "/" => (36, 37),
+        "**" => (38, 39),
+        _ => return None,
+    };
+
+    Some(power)
+}
+
+const CAPTURE_POWER: u8 = 12;
+const UNARY_POWER: u8 = 40;
+// just above `|`, so map keys stop at `|` and `=>`
+const MAP_KEY_POWER: u8 = 9;
+
+// the base of `%{map | key: value}` and its pairs
+type MapBody = (Option<Expr>, Vec<(Expr, Expr)>);
+
+const UNARY_OPERATORS: [&str; 6] = ["!", "^", "-", "+", "~~~", "&"];
+const BLOCK_TERMINATORS: [&str; 5] = ["end", "else", "after", "rescue", "catch"];
+const RESERVED: [&str; 10] = [
+    "do", "end", "else", "after", "rescue", "catch", "when", "and", "or", "in",
+];
+
+pub fn parse(source: &str) -> Result<Expr> {
+    Parser::new(source)?.parse()
+}
+
+#[derive(Clone, Debug)]
+pub struct Parser {
+    builder: Builder,
+    cursor: usize,
+    // one past the last consumed token that
+    // isn't whitespace, comment nor newline
+    last_end: usize,
+    // `do` blocks bind to the outermost call
+    // without parens, so its args can't take them
+    no_do: bool,
+    tokens: Vec<Token>,
+}
+
+impl Parser {
+    pub fn new(source: &str) -> Result<Self> {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.by_ref().collect::<Vec<Token>>();
+
+        if !lexer.diagnostics().is_empty() {
+            let diagnostics = lexer
+                .diagnostics()
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<String>>();
+
+            bail!(diagnostics.join("\n"));
+        }
+
+        Ok(Parser::from_tokens(tokens))
+    }
+
+    fn from_tokens(tokens: Vec<Token>) -> Self {
+        Parser {
+            builder: Builder::default(),
+            cursor: usize::MIN,
+            last_end: usize::MIN,
+            no_do: false,
+            tokens,
+        }
+    }
+
+    // same as `parse`, but keeping every
+    // token around; see `cst::SyntaxTree`
+    pub fn parse_tree(mut self) -> Result<SyntaxTree> {
+        self.builder.start(0);
+        self.parse()?;
+
+        let root = self
+            .builder
+            .finish(SyntaxKind::Root, self.tokens.len())
+            .ok_or_else(|| anyhow!("unbalanced syntax tree"))?;
+
+        Ok(SyntaxTree::new(root, self.tokens))
+    }
+
+    // parses the whole source, multiple
+    // top level expressions become a block
+    pub fn parse(&mut self) -> Result<Expr> {
+        let expr = self.parse_body(&[])?;
+
+        if self.peek().is_some() {
+            return Err(self.unexpected());
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_body(&mut self, terminators: &[&str]) -> Result<Expr> {
+        self.skip_newlines();
+
+        if self.at_stab() {
+            return Ok(Expr::Stab(self.parse_clauses(terminators)?));
+        }
+
+        let mut exprs = Vec::<Expr>::new();
+
+        loop {
+            self.skip_newlines();
+
+            if self.at_terminator(terminators) {
+                break;
+            }
+
+            exprs.push(self.parse_expr(0)?);
+
+            if !self.at_end_of_expr(terminators) {
+                return Err(self.unexpected());
+            }
+
+            self.eat(";");
+        }
+
+        Ok(block(exprs))
+    }
+
+    fn parse_clauses(&mut self, terminators: &[&str]) -> Result<Vec<Clause>> {
+        let mut clauses = Vec::<Clause>::new();
+
+        loop {
+            self.skip_newlines();
+
+            if self.at_terminator(terminators) {
+                break;
+            }
+
+            self.start_node();
+            clauses.push(self.parse_clause(terminators)?);
+            self.finish_node(SyntaxKind::Clause);
+        }
+
+        Ok(clauses)
+    }
+
+    fn parse_clause(&mut self, terminators: &[&str]) -> Result<Clause> {
+        let mut args = Vec::<Expr>::new();
+
+        while !self.at("->") {
+            args.push(self.parse_expr(0)?);
+
+            if !self.eat(",") {
+                break;
+            }
+
+            self.skip_newlines();
+        }
+
+        let guard = split_guard(&mut args);
+
+        self.expect("->")?;
+
+        let mut exprs = Vec::<Expr>::new();
+
+        loop {
+            self.skip_newlines();
+
+            // next clause starts on this line
+            if self.at_terminator(terminators) || self.at_stab() {
+                break;
+            }
+
+            exprs.push(self.parse_expr(0)?);
+
+            if !self.at_end_of_expr(terminators) {
+                return Err(self.unexpected());
+            }
+
+            self.eat(";");
+        }
+
+        Ok(Clause {
+            args,
+            guard,
+            body: Box::new(block(exprs)),
+        })
+    }
+
+    fn parse_expr(&mut self, min_power: u8) -> Result<Expr> {
+        let checkpoint = self.checkpoint();
+        let mut lhs = self.parse_unary()?;
+
+        while let Some((op, len)) = self.peek_binary_op() {
+            let (left, right) = binding_power(&op).unwrap_or_default();
+
+            if left < min_power {
+                break;
+            }
+
+            self.builder.start_at(checkpoint);
+
+            self.skip_newlines();
+            for _ in 0..len {
+                self.bump();
+            }
+            self.skip_newlines();
+
+            let rhs = self.parse_expr(right)?;
+
+            lhs = match op.as_str() {
+                "|>" => Expr::Pipe(Box::new(lhs), Box::new(rhs)),
+                _ => Expr::BinaryOp(op, Box::new(lhs), Box::new(rhs)),
+            };
+
+            self.finish_node(SyntaxKind::from(&lhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if let Some(op) = self.peek_unary_op() {
+            self.start_node();
+            self.bump();
+
+            // `&1` is an argument of the capture, not one
+            let is_arg = self
+                .tokens
+                .get(self.cursor)
+                .is_some_and(|t| t.kind().is_number());
+            let power = match op.as_str() {
+                "&" if !is_arg => CAPTURE_POWER,
+                _ => UNARY_POWER,
+            };
+            let operand = self.parse_expr(power)?;
+
+            self.finish_node(SyntaxKind::UnaryOp);
+
+            return Ok(Expr::UnaryOp(op, Box::new(operand)));
+        }
+
+        let checkpoint = self.checkpoint();
+        let expr = self.parse_primary()?;
+
+        self.parse_access(checkpoint, expr)
+    }
+
+    // `foo[:bar]`, only when `[` is right next to it
+    fn parse_access(&mut self, checkpoint: Checkpoint, mut expr: Expr) -> Result<Expr> {
+        while self.raw_at(self.cursor, "[") {
+            self.builder.start_at(checkpoint);
+            self.bump();
+
+            let key = self.nested(|p| {
+                p.skip_newlines();
+                let key = p.parse_expr(0)?;
+                p.skip_newlines();
+                p.expect("]")?;
+
+                Ok(key)
+            })?;
+
+            expr = Expr::Access(Box::new(expr), Box::new(key));
+
+            self.finish_node(SyntaxKind::Access);
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        self.start_node();
+        let expr = self.parse_term()?;
+        self.finish_node(SyntaxKind::from(&expr));
+
+        Ok(expr)
+    }
+
+    fn parse_term(&mut self) -> Result<Expr> {
+        let token = self.peek().cloned().ok_or_else(|| self.unexpected())?;
+        let lexeme = token.lexeme();
+        let kind = token.kind();
+
+        self.bump();
+
+        match kind {
+            k if k.is_atom() && is_atom_call(&lexeme) => self.parse_atom_call(&lexeme),
+            k if k.is_atom() => Ok(Expr::Atom(parse_atom(&lexeme))),
+            k if k.is_boolean() => Ok(match lexeme.as_str() {
+                "true" => Expr::Boolean(true),
+                "false" => Expr::Boolean(false),
+                _ => Expr::Nil,
+            }),
+            k if k.is_char() => Ok(Expr::Char(lexeme)),
+            k if k.is_string() || k.is_charlist() => parse_quoted(&token),
+            k if k.is_sigil() => parse_sigil(&token),
+            k if k.is_number() => Ok(Expr::Number(lexeme)),
+            k if k.is_identifier() => self.parse_identifier(&lexeme),
+            k if k.is_delimiter() => match lexeme.as_str() {
+                "(" => self.nested(Parser::parse_parens),
+                "[" => self.nested(Parser::parse_list),
+                "{" => self.nested(Parser::parse_tuple),
+                "%" => self.nested(Parser::parse_map),
+                _ => Err(self.unexpected_token(&token)),
+            },
+            k if k.is_operator() && lexeme.eq("<<") => self.nested(Parser::parse_bitstring),
+            _ => Err(self.unexpected_token(&token)),
+        }
+    }
+
+    fn parse_parens(&mut self) -> Result<Expr> {
+        let expr = self.parse_body(&[")"])?;
+        self.expect(")")?;
+
+        Ok(expr)
+    }
+
+    fn parse_list(&mut self) -> Result<Expr> {
+        let (mut items, keyword) = self.parse_elements("]")?;
+        // `[a, b: 1]` is sugar for `[a, {:b, 1}]`
+        items.extend(keyword);
+
+        Ok(Expr::List(items))
+    }
+
+    fn parse_tuple(&mut self) -> Result<Expr> {
+        let (items, keyword) = self.parse_elements("}")?;
+
+        Ok(Expr::Tuple(with_keyword(items, keyword)))
+    }
+
+    fn parse_bitstring(&mut self) -> Result<Expr> {
+        let (items, keyword) = self.parse_elements(">>")?;
+
+        Ok(Expr::Bitstring(with_keyword(items, keyword)))
+    }
+
+    fn parse_map(&mut self) -> Result<Expr> {
+        if self.raw_at(self.cursor, "{") {
+            self.bump();
+
+            return match self.parse_map_pairs()? {
+                (Some(base), pairs) => Ok(Expr::MapUpdate(Box::new(base), pairs)),
+                (None, pairs) => Ok(Expr::Map(pairs)),
+            };
+        }
+
+        // %Struct{} or %__MODULE__{}
+        let token = self.bump().ok_or_else(|| self.unexpected())?;
+        let name = match token.lexeme() {
+            n if token.kind().is_identifier() && starts_uppercase(&n) => Expr::Alias(n),
+            n if token.kind().is_identifier() => Expr::Var(n),
+            _ => return Err(self.unexpected_token(&token)),
+        };
+
+        if !self.raw_at(self.cursor, "{") {
+            return Err(self.unexpected());
+        }
+        self.bump();
+
+        match self.parse_map_pairs()? {
+            (None, pairs) => Ok(Expr::Struct(Box::new(name), pairs)),
+            (Some(_), _) => bail!("struct update syntax is not supported"),
+        }
+    }
+
+    fn parse_map_pairs(&mut self) -> Result<MapBody> {
+        let mut base = None;
+        let mut pairs = Vec::<(Expr, Expr)>::new();
+
+        loop {
+            self.skip_newlines();
+
+            if self.eat("}") {
+                break;
+            }
+
+            if self.at_keyword_key() {
+                self.start_node();
+                let key = self.parse_keyword_key()?;
+                self.skip_newlines();
+                pairs.push((Expr::Atom(key), self.parse_expr(0)?));
+                self.finish_node(SyntaxKind::Pair);
+            } else {
+                let checkpoint = self.checkpoint();
+                let key = self.parse_expr(MAP_KEY_POWER)?;
+
+                if base.is_none() && pairs.is_empty() && self.eat("|") {
+                    base = Some(key);
+                    continue;
+                }
+
+                self.builder.start_at(checkpoint);
+                self.skip_newlines();
+                self.expect("=>")?;
+                self.skip_newlines();
+                pairs.push((key, self.parse_expr(0)?));
+                self.finish_node(SyntaxKind::Pair);
+            }
+
+            self.skip_newlines();
+
+            if !self.eat(",") {
+                self.expect("}")?;
+                break;
+            }
+        }
+
+        Ok((base, pairs))
+    }
+
+    // comma separated elements up to `close`,
+    // trailing keyword pairs are returned apart
+    fn parse_elements(&mut self, close: &str) -> Result<(Vec<Expr>, Vec<Expr>)> {
+        let mut items = Vec::<Expr>::new();
+        let mut keyword = Vec::<Expr>::new();
+
+        loop {
+            self.skip_newlines();
+
+            if self.eat(close) {
+                break;
+            }
+
+            if self.at_keyword_key() {
+                keyword.push(self.parse_keyword_pair()?);
+            } else if keyword.is_empty() {
+                items.push(self.parse_expr(0)?);
+            } else {
+                bail!(
+                    "keyword elements must come last, found {}",
+                    self.describe_next()
+                );
+            }
+
+            self.skip_newlines();
+
+            if !self.eat(",") {
+                self.expect(close)?;
+                break;
+            }
+        }
+
+        Ok((items, keyword))
+    }
+
+    fn parse_keyword_pair(&mut self) -> Result<Expr> {
+        self.start_node();
+        let key = self.parse_keyword_key()?;
+        self.skip_newlines();
+        let value = self.parse_expr(0)?;
+        self.finish_node(SyntaxKind::Pair);
+
+        Ok(Expr::Tuple(vec![Expr::Atom(key), value]))
+    }
+
+    fn parse_keyword_key(&mut self) -> Result<String> {
+        let key = match self.peek() {
+            Some(t) if t.kind().is_string() || t.kind().is_charlist() => {
+                let token = self.bump().unwrap();
+
+                match parse_quoted(&token)? {
+                    Expr::String(key) | Expr::Charlist(key) => key,
+                    _ => bail!("interpolated keys are not supported"),
+                }
+            }
+            Some(t) if t.kind().is_identifier() => self.bump().map(|t| t.lexeme()).unwrap(),
+            _ => return Err(self.unexpected()),
+        };
+
+        // the colon is always glued to the key
+        self.bump();
+
+        Ok(key)
+    }
+
+    fn parse_identifier(&mut self, lexeme: &str) -> Result<Expr> {
+        if let Some(name) = lexeme.strip_prefix('@') {
+            return self.parse_attribute(name);
+        }
+
+        if RESERVED.contains(&lexeme) {
+            bail!("unexpected keyword `{}`", lexeme);
+        }
+
+        if lexeme.eq("fn") {
+            return self.parse_fn();
+        }
+
+        // `Foo.{Bar, Baz}` and `fun.(arg)`
+        if let Some(prefix) = lexeme.strip_suffix('.') {
+            return self.parse_dot_call(prefix);
+        }
+
+        let segments = lexeme.split('.').collect::<Vec<&str>>();
+
+        if segments.iter().all(|s| starts_uppercase(s)) {
+            return Ok(Expr::Alias(lexeme.to_string()));
+        }
+
+        let (name, path) = segments.split_last().unwrap();
+        let target = match path {
+            [] => None,
+            path => Some(Box::new(build_target(path))),
+        };
+
+        self.parse_call(target, name)
+    }
+
+    // `:telemetry.attach(...)` lexes as a single atom
+    fn parse_atom_call(&mut self, lexeme: &str) -> Result<Expr> {
+        let (module, name) = lexeme
+            .trim_start_matches(':')
+            .split_once('.')
+            .ok_or_else(|| anyhow!("unexpected atom `{}`", lexeme))?;
+
+        self.parse_call(Some(Box::new(Expr::Atom(module.to_string()))), name)
+    }
+
+    fn parse_call(&mut self, target: Option<Box<Expr>>, name: &str) -> Result<Expr> {
+        let mut args = if self.raw_at(self.cursor, "(") {
+            self.bump();
+            let (items, keyword) = self.nested(|p| p.parse_elements(")"))?;
+
+            with_keyword(items, keyword)
+        } else if self.at_no_parens_arg() {
+            self.parse_no_parens_args()?
+        } else if !self.no_do && self.at_do_block() {
+            vec![]
+        } else if target.is_some() {
+            // `Mix.compilers` or `conn.assigns`
+            return Ok(Expr::Call(Call {
+                target,
+                name: name.to_string(),
+                args: vec![],
+            }));
+        } else {
+            return Ok(Expr::Var(name.to_string()));
+        };
+
+        if !self.no_do && self.at_do_block() {
+            args.push(self.parse_do_block()?);
+        }
+
+        Ok(desugar(Call {
+            target,
+            name: name.to_string(),
+            args,
+        }))
+    }
+
+    fn parse_dot_call(&mut self, prefix: &str) -> Result<Expr> {
+        let target = Box::new(build_target(&prefix.split('.').collect::<Vec<&str>>()));
+
+        let (name, close) = match self.bump() {
+            Some(t) if t.lexeme().eq("{") => ("{}", "}"),
+            Some(t) if t.lexeme().eq("(") => ("", ")"),
+            _ => return Err(self.unexpected()),
+        };
+
+        let (items, keyword) = self.nested(|p| p.parse_elements(close))?;
+
+        Ok(Expr::Call(Call {
+            target: Some(target),
+            name: name.to_string(),
+            args: with_keyword(items, keyword),
+        }))
+    }
+
+    fn parse_attribute(&mut self, name: &str) -> Result<Expr> {
+        if !self.at_no_parens_arg() {
+            return Ok(Expr::Attribute(name.to_string(), None));
+        }
+
+        let value = self.with_no_do(true, |p| p.parse_expr(0))?;
+
+        Ok(Expr::Attribute(name.to_string(), Some(Box::new(value))))
+    }
+
+    fn parse_fn(&mut self) -> Result<Expr> {
+        let clauses = self.nested(|p| p.parse_clauses(&["end"]))?;
+        self.expect("end")?;
+
+        Ok(Expr::Fn(clauses))
+    }
+
+    fn parse_no_parens_args(&mut self) -> Result<Vec<Expr>> {
+        self.with_no_do(true, |p| {
+            let mut items = Vec::<Expr>::new();
+            let mut keyword = Vec::<Expr>::new();
+
+            loop {
+                if p.at_keyword_key() {
+                    keyword.push(p.parse_keyword_pair()?);
+                } else if keyword.is_empty() {
+                    items.push(p.parse_expr(0)?);
+                } else {
+                    bail!(
+                        "keyword arguments must come last, found {}",
+                        p.describe_next()
+                    );
+                }
+
+                // a trailing comma continues on next line
+                if !p.eat(",") {
+                    break;
+                }
+
+                p.skip_newlines();
+            }
+
+            Ok(with_keyword(items, keyword))
+        })
+    }
+
+    // `do ... else ... end` becomes
+    // `[do: ..., else: ...]`
+    fn parse_do_block(&mut self) -> Result<Expr> {
+        self.start_node();
+        self.bump();
+
+        let mut sections = Vec::<Expr>::new();
+        let mut section = "do".to_string();
+
+        loop {
+            self.skip_newlines();
+            self.start_node();
+            let body = self.nested(|p| p.parse_body(&BLOCK_TERMINATORS))?;
+            self.finish_node(SyntaxKind::Block);
+
+            sections.push(Expr::Tuple(vec![Expr::Atom(section), body]));
+
+            match self.bump() {
+                Some(t) if t.lexeme().eq("end") => break,
+                Some(t) => section = t.lexeme(),
+                None => bail!("missing `end` for `do` block"),
+            }
+        }
+
+        self.finish_node(SyntaxKind::DoBlock);
+
+        Ok(Expr::List(sections))
+    }
+
+    fn at_keyword_key(&self) -> bool {
+        let index = self.peek_index();
+
+        let end = match self.tokens.get(index) {
+            Some(t) if t.kind().is_identifier() => index + 1,
+            Some(t) if t.kind().is_string() || t.kind().is_charlist() => index + 1,
+            _ => return false,
+        };
+
+        self.tokens
+            .get(end)
+            .is_some_and(|t| t.kind().is_operator() && t.lexeme().eq(":"))
+    }
+
+    fn at_do_block(&self) -> bool {
+        let index = self.peek_index();
+
+        self.raw_at(index, "do")
+            && !self
+                .tokens
+                .get(index + 1)
+                .is_some_and(|t| t.lexeme().eq(":"))
+    }
+
+    // calls without parens need a space
+    // before an expression on the same line
+    fn at_no_parens_arg(&self) -> bool {
+        match self.tokens.get(self.cursor) {
+            Some(t) if t.kind().is_whitespace() => {}
+            _ => return false,
+        }
+
+        let index = self.cursor + 1;
+        let token = match self.tokens.get(index) {
+            Some(t) => t,
+            None => return false,
+        };
+        let lexeme = token.lexeme();
+        let kind = token.kind();
+
+        match kind {
+            k if k.is_atom() || k.is_boolean() || k.is_char() || k.is_number() => true,
+            k if k.is_string() || k.is_charlist() || k.is_sigil() => true,
+            k if k.is_identifier() => {
+                let is_key = self.raw_at(index + 1, ":");
+                let is_not_in =
+                    lexeme.eq("not") && self.raw_at(self.next_significant(index + 1), "in");
+
+                is_key || (!RESERVED.contains(&lexeme.as_str()) && !is_not_in)
+            }
+            k if k.is_delimiter() => ["(", "[", "{", "%"].contains(&lexeme.as_str()),
+            k if k.is_operator() => {
+                let glued = self
+                    .tokens
+                    .get(index + 1)
+                    .is_some_and(|t| !t.kind().is_whitespace() && !t.kind().is_newline());
+
+                lexeme.eq("<<") || (UNARY_OPERATORS.contains(&lexeme.as_str()) && glued)
+            }
+            _ => false,
+        }
+    }
+
+    // whether a `->` shows up before the
+    // end of the line or the next `;`
+    fn at_stab(&self) -> bool {
+        let mut index = self.peek_index();
+        let mut depth = 0;
+
+        while let Some(token) = self.tokens.get(index) {
+            let lexeme = token.lexeme();
+            let kind = token.kind();
+
+            if (kind.is_newline() || lexeme.eq(";")) && depth == 0 {
+                return false;
+            }
+
+            if kind.is_delimiter() || kind.is_identifier() {
+                match lexeme.as_str() {
+                    "(" | "[" | "{" | "fn" => depth += 1,
+                    "do" if !self.raw_at(index + 1, ":") => depth += 1,
+                    ")" | "]" | "}" | "end" => depth -= 1,
+                    _ => {}
+                }
+            }
+
+            if depth < 0 {
+                return false;
+            }
+
+            if kind.is_operator() && lexeme.eq("->") && depth == 0 {
+                return true;
+            }
+
+            index += 1;
+        }
+
+        false
+    }
+
+    fn at_terminator(&self, terminators: &[&str]) -> bool {
+        match self.peek() {
+            None => true,
+            Some(t) => {
+                let kind = t.kind();
+
+                (kind.is_identifier() || kind.is_delimiter())
+                    && terminators.contains(&t.lexeme().as_str())
+            }
+        }
+    }
+
+    // expressions are separated by newlines or `;`
+    fn at_end_of_expr(&self, terminators: &[&str]) -> bool {
+        self.peek().is_none_or(|t| t.kind().is_newline())
+            || self.at(";")
+            || self.at_terminator(terminators)
+    }
+
+    fn peek_unary_op(&self) -> Option<String> {
+        let index = self.peek_index();
+        let token = self.tokens.get(index)?;
+        let lexeme = token.lexeme();
+
+        let is_unary = token.kind().is_operator() && UNARY_OPERATORS.contains(&lexeme.as_str());
+        let is_not =
+            token.kind().is_identifier() && lexeme.eq("not") && !self.raw_at(index + 1, ":");
+
+        if is_unary || is_not {
+            return Some(lexeme);
+        }
+
+        None
+    }
+
+    // operator and how many tokens it spans
+    fn peek_binary_op(&self) -> Option<(String, usize)> {
+        let index = self.peek_index();
+        let token = self.tokens.get(index)?;
+
+        if !token.kind().is_newline() {
+            return self.binary_op_at(index);
+        }
+
+        // a newline only continues an expression
+        // before an operator that can't be unary
+        let mut next = index;
+        while self.tokens.get(next).is_some_and(|t| t.kind().is_newline()) {
+            next = self.next_significant(next + 1);
+        }
+
+        self.binary_op_at(next)
+            .filter(|(op, _)| !UNARY_OPERATORS.contains(&op.as_str()))
+    }
+
+    fn binary_op_at(&self, index: usize) -> Option<(String, usize)> {
+        let token = self.tokens.get(index)?;
+        let lexeme = token.lexeme();
+
+        if token.kind().is_operator() {
+            return binding_power(&lexeme).map(|_| (lexeme, 1));
+        }
+
+        if !token.kind().is_identifier() || self.raw_at(index + 1, ":") {
+            return None;
+        }
+
+        match lexeme.as_str() {
+            "when" | "and" | "or" | "in" => Some((lexeme, 1)),
+            "not" if self.raw_at(self.next_significant(index + 1), "in") => {
+                Some(("not in".to_string(), 2))
+            }
+            _ => None,
+        }
+    }
+
+    fn with_no_do<T, F>(&mut self, no_do: bool, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        let previous = self.no_do;
+        self.no_do = no_do;
+        let result = f(self);
+        self.no_do = previous;
+
+        result
+    }
+
+    // inside delimiters `do` blocks are allowed again
+    fn nested<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        self.with_no_do(false, f)
+    }
+
+    fn next_significant(&self, mut index: usize) -> usize {
+        while self.tokens.get(index).is_some_and(is_trivia) {
+            index += 1;
+        }
+
+        index
+    }
+
+    fn peek_index(&self) -> usize {
+        self.next_significant(self.cursor)
+    }
+
+    // next token that is not whitespace
+    // nor comment, newlines included
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.peek_index())
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let index = self.peek_index();
+        let token = self.tokens.get(index).cloned()?;
+
+        self.cursor = index + 1;
+
+        if !token.kind().is_newline() {
+            self.last_end = self.cursor;
+        }
+
+        Some(token)
+    }
+
+    fn start_node(&mut self) {
+        self.builder.start(self.peek_index());
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        self.builder.checkpoint(self.peek_index())
+    }
+
+    fn finish_node(&mut self, kind: SyntaxKind) {
+        self.builder.finish(kind, self.last_end);
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek().is_some_and(|t| t.kind().is_newline()) {
+            self.bump();
+        }
+    }
+
+    fn raw_at(&self, index: usize, lexeme: &str) -> bool {
+        self.tokens
+            .get(index)
+            .is_some_and(|t| t.lexeme().eq(lexeme))
+    }
+
+    fn at(&self, lexeme: &str) -> bool {
+        self.raw_at(self.peek_index(), lexeme)
+    }
+
+    fn eat(&mut self, lexeme: &str) -> bool {
+        if self.at(lexeme) {
+            self.bump();
+
+            return true;
+        }
+
+        false
+    }
+
+    fn expect(&mut self, lexeme: &str) -> Result<Token> {
+        if self.at(lexeme) {
+            return Ok(self.bump().unwrap());
+        }
+
+        bail!("expected `{}`, found {}", lexeme, self.describe_next())
+    }
+
+    fn describe_next(&self) -> String {
+        match self.peek() {
+            Some(t) => describe(t),
+            None => "end of input".to_string(),
+        }
+    }
+
+    fn unexpected(&self) -> anyhow::Error {
+        anyhow!("unexpected {}", self.describe_next())
+    }
+
+    fn unexpected_token(&self, token: &Token) -> anyhow::Error {
+        anyhow!("unexpected {}", describe(token))
+    }
+}
+
+fn is_trivia(token: &Token) -> bool {
+    let kind = token.kind();
+
+    kind.is_whitespace() || kind.is_comment()
+}
+
+fn describe(token: &Token) -> String {
+    if token.kind().is_newline() {
+        return "newline".to_string();
+    }
+
+    format!("`{}` at {}", token.lexeme(), token.span().start())
+}
+
+fn block(mut exprs: Vec<Expr>) -> Expr {
+    if exprs.len() == 1 {
+        return exprs.remove(0);
+    }
+
+    Expr::Block(exprs)
+}
+
+fn with_keyword(mut items: Vec<Expr>, keyword: Vec<Expr>) -> Vec<Expr> {
+    if !keyword.is_empty() {
+        items.push(Expr::List(keyword));
+    }
+
+    items
+}
+
+// `a, b when c` guards all of the args
+fn split_guard(args: &mut Vec<Expr>) -> Option<Box<Expr>> {
+    match args.pop() {
+        Some(Expr::BinaryOp(op, lhs, rhs)) if op.eq("when") => {
+            args.push(*lhs);
+            Some(rhs)
+        }
+        Some(last) => {
+            args.push(last);
+            None
+        }
+        None => None,
+    }
+}
+
+fn starts_uppercase(str: &str) -> bool {
+    str.chars().next().is_some_and(char::is_uppercase)
+}
+
+fn parse_atom(lexeme: &str) -> String {
+    let atom = lexeme.trim_start_matches(':');
+
+    atom.strip_prefix('"')
+        .and_then(|a| a.strip_suffix('"'))
+        .unwrap_or(atom)
+        .to_string()
+}
+
+// `:mod.fun`, but not `:"mod.fun"`
+fn is_atom_call(lexeme: &str) -> bool {
+    lexeme
+        .strip_prefix(':')
+        .is_some_and(|a| !a.starts_with('"') && a.contains('.'))
+}
+
+// `Foo.Bar.baz.qux` has `Foo.Bar` as an alias
+// and `baz` as a call without args
+fn build_target(path: &[&str]) -> Expr {
+    let aliases = path.iter().take_while(|s| starts_uppercase(s)).count();
+
+    let mut target = match aliases {
+        0 => Expr::Var(path[0].to_string()),
+        n => Expr::Alias(path[..n].join(".")),
+    };
+
+    for segment in path.iter().skip(aliases.max(1)) {
+        target = Expr::Call(Call {
+            target: Some(Box::new(target)),
+            name: segment.to_string(),
+            args: vec![],
+        });
+    }
+
+    target
+}
+
+fn desugar(call: Call) -> Expr {
+    if call.target.is_some() {
+        return Expr::Call(call);
+    }
+
+    let desugared = match call.name.as_str() {
+        "defmodule" => desugar_module(&call),
+        "def" | "defp" => desugar_def(&call),
+        "if" => desugar_if(&call),
+        _ => None,
+    };
+
+    desugared.unwrap_or(Expr::Call(call))
+}
+
+fn desugar_module(call: &Call) -> Option<Expr> {
+    match call.args.as_slice() {
+        [Expr::Alias(name), block] => Some(Expr::Module(Module {
+            name: name.to_string(),
+            body: Box::new(block.keyword_get("do")?.clone()),
+        })),
+        _ => None,
+    }
+}
+
+fn desugar_def(call: &Call) -> Option<Expr> {
+    let (head, body) = match call.args.as_slice() {
+        [head] => (head, None),
+        [head, block] => (head, Some(Box::new(block.keyword_get("do")?.clone()))),
+        _ => return None,
+    };
+
+    let (head, guard) = match head {
+        Expr::BinaryOp(op, head, guard) if op.eq("when") => (head.as_ref(), Some(guard.clone())),
+        head => (head, None),
+    };
+
+    let (name, args) = match head {
+        Expr::Var(name) => (name.to_string(), vec![]),
+        Expr::Call(Call {
+            target: None,
+            name,
+            args,
+        }) => (name.to_string(), args.clone()),
+        _ => return None,
+    };
+
+    Some(Expr::Def(Def {
+        private: call.name.eq("defp"),
+        name,
+        args,
+        guard,
+        body,
+    }))
+}
+
+fn desugar_if(call: &Call) -> Option<Expr> {
+    match call.args.as_slice() {
+        [condition, block] => Some(Expr::If(If {
+            condition: Box::new(condition.clone()),
+            then: Box::new(block.keyword_get("do")?.clone()),
+            otherwise: block.keyword_get("else").cloned().map(Box::new),
+        })),
+        _ => None,
+    }
+}
+
+// literals and `#{}` parts, charlists with
+// parts are `List.to_charlist/1` as in Elixir
+fn parse_quoted(token: &Token) -> Result<Expr> {
+    let (parts, literal) = parse_fragments(token)?;
+
+    Ok(match (token.kind().is_charlist(), literal) {
+        (false, Some(string)) => Expr::String(string),
+        (true, Some(charlist)) => Expr::Charlist(charlist),
+        (false, None) => Expr::Interpolated(parts),
+        (true, None) => Expr::Call(Call {
+            target: Some(Box::new(Expr::Alias("List".to_string()))),
+            name: "to_charlist".to_string(),
+            args: vec![Expr::List(parts)],
+        }),
+    })
+}
+
+fn parse_sigil(token: &Token) -> Result<Expr> {
+    let lexeme = token.lexeme();
+    let name = lexeme[1..]
+        .chars()
+        .take_while(char::is_ascii_alphanumeric)
+        .collect::<String>();
+    let modifiers = lexeme
+        .chars()
+        .rev()
+        .take_while(char::is_ascii_alphanumeric)
+        .collect::<Vec<char>>()
+        .into_iter()
+        .rev()
+        .collect::<String>();
+
+    // `"""` and `'''` for heredocs
+    let rest = &lexeme[1 + name.len()..];
+    let open = match rest.get(..3) {
+        Some(heredoc @ ("\"\"\"" | "'''")) => heredoc.to_string(),
+        _ => rest.chars().next().map(String::from).unwrap_or_default(),
+    };
+    let close = match open.chars().next().and_then(|ch| closing_delim(&ch)) {
+        Some(close) if open.len() == 1 => close.to_string(),
+        _ => open.clone(),
+    };
+
+    let content = match parse_fragments(token)? {
+        (_, Some(string)) => Expr::String(string),
+        (parts, None) => Expr::Interpolated(parts),
+    };
+
+    Ok(Expr::Sigil(Sigil {
+        name,
+        open,
+        content: Box::new(content),
+        close,
+        modifiers,
+    }))
+}
+
+// the parts and, when nothing is
+// interpolated, the whole literal
+fn parse_fragments(token: &Token) -> Result<(Vec<Expr>, Option<String>)> {
+    let mut parts = Vec::<Expr>::new();
+
+    for fragment in token.fragments() {
+        match fragment {
+            Fragment::Literal(literal) => parts.push(Expr::String(literal.to_string())),
+            Fragment::Interpolation(tokens) => {
+                let mut parser = Parser::from_tokens(tokens.to_vec());
+                parts.push(parser.parse()?);
+            }
+        }
+    }
+
+    let literal = parts
+        .iter()
+        .map(Expr::as_string)
+        .collect::<Option<String>>();
+
+    Ok((parts, literal))
+}
+
+#[cfg(test)]
+mod literals {
+    use super::*;
+
+    #[test]
+    fn atom() {
+        assert_eq!(parse(":ok").unwrap(), Expr::Atom("ok".to_string()));
+        assert_eq!(parse(r#":"ok?""#).unwrap(), Expr::Atom("ok?".to_string()));
+    }
+
+    #[test]
+    fn string() {
+        let expr = parse(r#""git@github.com:solfacil/database.git""#).unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::String("git@github.com:solfacil/database.git".to_string())
+        );
+    }
+
+    #[test]
+    fn escaped_string() {
+        let expr = parse(r#""say \"hi\"""#).unwrap();
+
+        assert_eq!(expr, Expr::String(r#"say "hi""#.to_string()));
+    }
+
+    #[test]
+    fn heredoc() {
+        let source = "\"\"\"\n  line one\n  line two\n  \"\"\"";
+
+        assert_eq!(
+            parse(source).unwrap(),
+            Expr::String("line one\nline two\n".to_string())
+        );
+    }
+
+    #[test]
+    fn interpolated_string() {
+        let expr = parse(r#""v#{@version} #{"(#{env})"}""#).unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::Interpolated(vec![
+                Expr::String("v".to_string()),
+                Expr::Attribute("version".to_string(), None),
+                Expr::String(" ".to_string()),
+                Expr::Interpolated(vec![
+                    Expr::String("(".to_string()),
+                    Expr::Var("env".to_string()),
+                    Expr::String(")".to_string()),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn charlist() {
+        assert_eq!(
+            parse("'~> 1.6'").unwrap(),
+            Expr::Charlist("~> 1.6".to_string())
+        );
+
+        let expr = parse("'#{name}.git'").unwrap();
+        let call = match expr {
+            Expr::Call(call) => call,
+            expr => panic!("expected a call, found {:?}", expr),
+        };
+        assert_eq!(call.name, "to_charlist");
+        assert_eq!(
+            call.args,
+            vec![Expr::List(vec![
+                Expr::Var("name".to_string()),
+                Expr::String(".git".to_string()),
+            ])]
+        );
+    }
+
+    #[test]
+    fn sigil() {
+        let expr = parse(r#"~r/guides\/local\/.*/i"#).unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::Sigil(Sigil {
+                name: "r".to_string(),
+                open: "/".to_string(),
+                content: Box::new(Expr::String("guides/local/.*".to_string())),
+                close: "/".to_string(),
+                modifiers: "i".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn sigil_in_keyword() {
+        let expr = parse(r#"["Ambiente de desenvolvimento": ~r/guides\/local\/.*/]"#).unwrap();
+
+        assert!(matches!(
+            expr.keyword_get("Ambiente de desenvolvimento"),
+            Some(Expr::Sigil(sigil)) if sigil.name.eq("r") && sigil.modifiers.is_empty()
+        ));
+    }
+
+    #[test]
+    fn quoted_keyword_key() {
+        let expr = parse(r#"["ecto.setup": ["ecto.create"]]"#).unwrap();
+
+        assert!(expr.keyword_get("ecto.setup").is_some());
+    }
+
+    #[test]
+    fn number_bool_nil() {
+        assert_eq!(parse("15_000").unwrap(), Expr::Number("15_000".to_string()));
+        assert_eq!(parse("true").unwrap(), Expr::Boolean(true));
+        assert_eq!(parse("nil").unwrap(), Expr::Nil);
+    }
+}
+
+#[cfg(test)]
+mod containers {
+    use super::*;
+
+    #[test]
+    fn list() {
+        let expr = parse("[:dev, :test]").unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::List(vec![
+                Expr::Atom("dev".to_string()),
+                Expr::Atom("test".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn keyword_list() {
+        let expr = parse("[tool: ExCoveralls, \"ecto.setup\": []]").unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::keyword(vec![
+                ("tool", Expr::Alias("ExCoveralls".to_string())),
+                ("ecto.setup", Expr::List(vec![])),
+            ])
+        );
+    }
+
+    #[test]
+    fn tuple_with_keyword() {
+        let expr = parse(r#"{:credo, "~> 1.6", only: [:dev, :test], runtime: false}"#).unwrap();
+        let tuple = expr.as_tuple().unwrap();
+
+        assert_eq!(tuple.len(), 3);
+        assert_eq!(tuple[0].as_atom(), Some("credo"));
+        assert_eq!(tuple[1].as_string(), Some("~> 1.6"));
+        assert_eq!(tuple[2].keyword_get("runtime"), Some(&Expr::Boolean(false)));
+    }
+
+    #[test]
+    fn multiline_list() {
+        let source = "[\n  # start deps\n  {:phoenix, \"~> 1.6.6\"},\n  {:jason, \"~> 1.2\"}\n]";
+        let expr = parse(source).unwrap();
+
+        assert_eq!(expr.as_list().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn map() {
+        let expr = parse(r#"%{"a" => 1, b: 2}"#).unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::Map(vec![
+                (Expr::String("a".to_string()), Expr::Number("1".to_string())),
+                (Expr::Atom("b".to_string()), Expr::Number("2".to_string())),
+            ])
+        );
+    }
+
+    #[test]
+    fn map_update_and_struct() {
+        assert!(matches!(
+            parse("%{map | a: 1}").unwrap(),
+            Expr::MapUpdate(_, _)
+        ));
+        assert!(matches!(parse("%Foo{a: 1}").unwrap(), Expr::Struct(_, _)));
+    }
+}
+
+#[cfg(test)]
+mod calls {
+    use super::*;
+
+    #[test]
+    fn remote_call() {
+        let expr = parse("Mix.env()").unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::Call(Call {
+                target: Some(Box::new(Expr::Alias("Mix".to_string()))),
+                name: "env".to_string(),
+                args: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn erlang_call() {
+        let expr = parse(":telemetry.attach(\"id\", [:a], &Mod.handle/4, nil)").unwrap();
+
+        match expr {
+            Expr::Call(call) => {
+                assert_eq!(
+                    call.target,
+                    Some(Box::new(Expr::Atom("telemetry".to_string())))
+                );
+                assert_eq!(call.name, "attach");
+                assert_eq!(call.args.len(), 4);
+            }
+            expr => panic!("not a call: {:?}", expr),
+        }
+
+        assert_eq!(parse(r#":"a.b""#).unwrap(), Expr::Atom("a.b".to_string()));
+    }
+
+    #[test]
+    fn no_parens_call() {
+        let expr = parse("config :logger, level: :info").unwrap();
+
+        match expr {
+            Expr::Call(call) => {
+                assert_eq!(call.name, "config");
+                assert_eq!(call.args.len(), 2);
+                assert_eq!(
+                    call.args[1].keyword_get("level"),
+                    Some(&Expr::Atom("info".to_string()))
+                );
+            }
+            expr => panic!("not a call: {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn pipe() {
+        let expr = parse("\"a\"\n|> String.upcase()\n|> IO.puts()").unwrap();
+
+        match expr {
+            Expr::Pipe(lhs, _) => assert!(matches!(*lhs, Expr::Pipe(_, _))),
+            expr => panic!("not a pipe: {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        let expr = parse("Mix.env() == :prod and 1 + 2 * 3").unwrap();
+
+        match expr {
+            Expr::BinaryOp(op, lhs, rhs) => {
+                assert_eq!(op, "and");
+                assert!(matches!(*lhs, Expr::BinaryOp(ref op, _, _) if op == "=="));
+                assert!(matches!(*rhs, Expr::BinaryOp(ref op, _, _) if op == "+"));
+            }
+            expr => panic!("not an operator: {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn if_do_else() {
+        let expr = parse("if config_env() == :prod do\n  :a\nelse\n  :b\nend").unwrap();
+
+        match expr {
+            Expr::If(If {
+                then, otherwise, ..
+            }) => {
+                assert_eq!(*then, Expr::Atom("a".to_string()));
+                assert_eq!(otherwise, Some(Box::new(Expr::Atom("b".to_string()))));
+            }
+            expr => panic!("not an if: {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn if_keyword() {
+        let expr = parse("if System.get_env(\"ECTO_IPV6\"), do: [:inet6], else: []").unwrap();
+
+        assert!(matches!(
+            expr,
+            Expr::If(If {
+                otherwise: Some(_),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn case_clauses() {
+        let source = "case x do\n  {:ok, v} when is_binary(v) -> v\n  _ ->\n    nil\nend";
+
+        match parse(source).unwrap() {
+            Expr::Call(call) => match call.args[1].keyword_get("do") {
+                Some(Expr::Stab(clauses)) => {
+                    assert_eq!(clauses.len(), 2);
+                    assert!(clauses[0].guard.is_some());
+                    assert_eq!(*clauses[1].body, Expr::Nil);
+                }
+                other => panic!("not clauses: {:?}", other),
+            },
+            expr => panic!("not a call: {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn anonymous_fn() {
+        let expr = parse("fn x -> x end").unwrap();
+
+        assert!(matches!(expr, Expr::Fn(clauses) if clauses.len() == 1));
+    }
+
+    #[test]
+    fn semicolons() {
+        let expr = parse("a = 1; b = a\nc;").unwrap();
+
+        assert!(matches!(expr, Expr::Block(exprs) if exprs.len() == 3));
+        assert!(matches!(
+            parse("fn :a -> 1; :b -> 2 end").unwrap(),
+            Expr::Fn(clauses) if clauses.len() == 2
+        ));
+    }
+
+    #[test]
+    fn bitstring() {
+        assert_eq!(parse("<<>>").unwrap(), Expr::Bitstring(vec![]));
+        assert_eq!(
+            parse("<<1, rest::binary>>").unwrap(),
+            Expr::Bitstring(vec![
+                Expr::Number("1".to_string()),
+                Expr::BinaryOp(
+                    "::".to_string(),
+                    Box::new(Expr::Var("rest".to_string())),
+                    Box::new(Expr::Var("binary".to_string()))
+                )
+            ])
+        );
+    }
+
+    #[test]
+    fn unterminated() {
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("def foo do\n :ok\n").is_err());
+    }
+
+    #[test]
+    fn error_position() {
+        let error = parse("[\n  1,\n  2 3\n]").unwrap_err();
+
+        assert_eq!(error.to_string(), "expected `]`, found `3` at 3:5");
+    }
+
+    #[test]
+    fn lexer_errors() {
+        let error = parse("[:ok`\n \"open]").unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "unexpected ``` at 1:5, expected one of atom, char, comment, delimiter, \
+             identifier, number, operator, sigil, string\n\
+             unexpected `\"open]` at 2:2, expected closing `\"`"
+        );
+    }
+}
+
+#[cfg(test)]
+mod definitions {
+    use super::*;
+
+    const MIX_EXS: &str = r#"
+defmodule ServiceTemplate.MixProject do
+  use Mix.Project
+
+  def project do
+    [
+      app: :service_template,
+      version: "0.1.0",
+      deps: deps()
+    ]
+  end
+
+  # Specifies which paths to compile per environment.
+  defp elixirc_paths(:test), do: ["lib", "test/support"]
+  defp elixirc_paths(_), do: ["lib"]
+
+  defp deps do
+    [
+      {:phoenix, "~> 1.6.6"},
+      {:credo, "~> 1.6", only: [:dev, :test], runtime: false}
+    ]
+  end
+end
+"#;
+
+    #[test]
+    fn module() {
+        let expr = parse(MIX_EXS).unwrap();
+        let module = expr.as_module().unwrap();
+
+        assert_eq!(module.name, "ServiceTemplate.MixProject");
+        assert_eq!(module.defs().len(), 4);
+    }
+
+    #[test]
+    fn def() {
+        let expr = parse(MIX_EXS).unwrap();
+        let project = expr.as_module().unwrap().def("project", 0).unwrap();
+
+        assert!(!project.private);
+
+        let body = project.body.as_ref().unwrap();
+        assert_eq!(
+            body.keyword_get("app"),
+            Some(&Expr::Atom("service_template".to_string()))
+        );
+    }
+
+    #[test]
+    fn defp() {
+        let expr = parse(MIX_EXS).unwrap();
+        let module = expr.as_module().unwrap();
+        let deps = module.def("deps", 0).unwrap();
+
+        assert!(deps.private);
+        assert_eq!(deps.body.as_ref().unwrap().as_list().unwrap().len(), 2);
+
+        let paths = module.def("elixirc_paths", 1).unwrap();
+        assert_eq!(paths.args, vec![Expr::Atom("test".to_string())]);
+    }
+
+    #[test]
+    fn guard() {
+        let expr = parse("def foo(a) when is_binary(a), do: a").unwrap();
+
+        assert!(matches!(expr, Expr::Def(Def { guard: Some(_), .. })));
+    }
+
+    #[test]
+    fn attribute() {
+        let expr = parse("@moduledoc false").unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::Attribute(
+                "moduledoc".to_string(),
+                Some(Box::new(Expr::Boolean(false)))
+            )
+        );
+    }
+}
@@ -0,0 +1,532 @@
+use std::fmt;
+
+// The AST follows Elixir's own quoted form
+// semantics where it matters for us: keyword
+// lists are lists of `{atom, value}` tuples and
+// `do` blocks are a trailing keyword argument
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Access(Box<Expr>, Box<Expr>), // foo[:bar]
+    Alias(String),                // ServiceTemplate.MixProject
+    Atom(String),                 // without the leading colon
+    Attribute(String, Option<Box<Expr>>),
+    BinaryOp(String, Box<Expr>, Box<Expr>),
+    Bitstring(Vec<Expr>), // <<size::16, rest::binary>>
+    Block(Vec<Expr>),
+    Boolean(bool),
+    Call(Call),
+    Char(String),
+    Charlist(String),
+    Def(Def),
+    Fn(Vec<Clause>),
+    If(If),
+    Interpolated(Vec<Expr>), // "v#{vsn}" parts, literals as `String`
+    List(Vec<Expr>),
+    Map(Vec<(Expr, Expr)>),
+    MapUpdate(Box<Expr>, Vec<(Expr, Expr)>), // %{map | key: value}
+    Module(Module),
+    Nil,
+    Number(String),
+    Pipe(Box<Expr>, Box<Expr>),
+    Sigil(Sigil),
+    Stab(Vec<Clause>), // `->` clauses of case, cond, receive...
+    String(String),
+    Struct(Box<Expr>, Vec<(Expr, Expr)>),
+    Tuple(Vec<Expr>),
+    UnaryOp(String, Box<Expr>),
+    Var(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Call {
+    pub target: Option<Box<Expr>>, // `Mix` in `Mix.env()`
+    pub name: String,
+    pub args: Vec<Expr>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Clause {
+    pub args: Vec<Expr>,
+    pub guard: Option<Box<Expr>>,
+    pub body: Box<Expr>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Def {
+    pub private: bool,
+    pub name: String,
+    pub args: Vec<Expr>,
+    pub guard: Option<Box<Expr>>,
+    pub body: Option<Box<Expr>>, // bodiless heads have none
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct If {
+    pub condition: Box<Expr>,
+    pub then: Box<Expr>,
+    pub otherwise: Option<Box<Expr>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sigil {
+    pub name: String,       // `r` in `~r/.*/i`
+    pub open: String,       // `{` in `~r{.*}`, `"""` for heredocs
+    pub content: Box<Expr>, // `String` or `Interpolated`
+    pub close: String,      // `}` in `~r{.*}`
+    pub modifiers: String,  // `i` in `~r/.*/i`
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Module {
+    pub name: String,
+    pub body: Box<Expr>,
+}
+
+impl Expr {
+    pub fn as_atom(&self) -> Option<&str> {
+        match self {
+            Expr::Atom(atom) => Some(atom),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&str> {
+        match self {
+            Expr::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Expr::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<Expr>> {
+        match self {
+            Expr::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn as_tuple(&self) -> Option<&Vec<Expr>> {
+        match self {
+            Expr::Tuple(tuple) => Some(tuple),
+            _ => None,
+        }
+    }
+
+    pub fn as_module(&self) -> Option<&Module> {
+        match self {
+            Expr::Module(module) => Some(module),
+            _ => None,
+        }
+    }
+
+    // a keyword list is a list made only
+    // of `{atom, value}` tuples
+    pub fn as_keyword(&self) -> Option<Vec<(&str, &Expr)>> {
+        self.as_list()?
+            .iter()
+            .map(|pair| match pair.as_tuple()?.as_slice() {
+                [key, value] => Some((key.as_atom()?, value)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn keyword_get(&self, key: &str) -> Option<&Expr> {
+        self.as_keyword()?
+            .into_iter()
+            .find(|(k, _)| k.eq(&key))
+            .map(|(_, value)| value)
+    }
+
+    // top level expressions of a block,
+    // or the expression itself otherwise
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Block(exprs) => exprs.iter().collect(),
+            expr => vec![expr],
+        }
+    }
+
+    pub fn keyword(pairs: Vec<(&str, Expr)>) -> Self {
+        Expr::List(
+            pairs
+                .into_iter()
+                .map(|(key, value)| Expr::Tuple(vec![Expr::Atom(key.to_string()), value]))
+                .collect(),
+        )
+    }
+}
+
+impl Module {
+    pub fn defs(&self) -> Vec<&Def> {
+        self.body
+            .exprs()
+            .into_iter()
+            .filter_map(|expr| match expr {
+                Expr::Def(def) => Some(def),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn def(&self, name: &str, arity: usize) -> Option<&Def> {
+        self.defs()
+            .into_iter()
+            .find(|def| def.name.eq(name) && def.args.len() == arity)
+    }
+}
+
+// back to Elixir source, on a single line but
+// for `do` blocks, with trailing keyword lists
+// of calls and tuples written without brackets
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Access(expr, key) => write!(f, "{}[{}]", expr, key),
+            Expr::Alias(alias) => write!(f, "{}", alias),
+            Expr::Atom(atom) if is_plain_atom(atom) => write!(f, ":{}", atom),
+            Expr::Atom(atom) => write!(f, ":{}", quote(atom, '"')),
+            Expr::Attribute(name, None) => write!(f, "@{}", name),
+            Expr::Attribute(name, Some(value)) => write!(f, "@{} {}", name, value),
+            Expr::BinaryOp(op, left, right) => {
+                write!(f, "{} {} {}", operand(op, left), op, operand(op, right))
+            }
+            Expr::Bitstring(items) => write!(f, "<<{}>>", join(items, ", ")),
+            Expr::Block(exprs) => write!(f, "{}", join(exprs, "\n")),
+            Expr::Boolean(b) => write!(f, "{}", b),
+            Expr::Call(call) => write!(f, "{}", call),
+            Expr::Char(ch) => write!(f, "{}", ch),
+            Expr::Charlist(charlist) => write!(f, "{}", quote(charlist, '\'')),
+            Expr::Def(def) => write!(f, "{}", def),
+            Expr::Fn(clauses) => write!(f, "fn {} end", join(clauses, "; ")),
+            Expr::If(expr) => write!(f, "{}", expr),
+            Expr::Interpolated(parts) => {
+                let parts = parts
+                    .iter()
+                    .map(|part| match part {
+                        Expr::String(string) => escape(string, '"'),
+                        expr => format!("#{{{}}}", expr),
+                    })
+                    .collect::<String>();
+
+                write!(f, "\"{}\"", parts)
+            }
+            Expr::List(list) => match Expr::List(list.clone()).as_keyword() {
+                Some(keyword) if !keyword.is_empty() => write!(f, "[{}]", keyword_str(&keyword)),
+                _ => write!(f, "[{}]", join(list, ", ")),
+            },
+            Expr::Map(pairs) => write!(f, "%{{{}}}", pairs_str(pairs)),
+            Expr::MapUpdate(map, pairs) => write!(f, "%{{{} | {}}}", map, pairs_str(pairs)),
+            Expr::Module(module) => write!(f, "{}", module),
+            Expr::Nil => write!(f, "nil"),
+            Expr::Number(number) => write!(f, "{}", number),
+            Expr::Pipe(left, right) => write!(f, "{} |> {}", left, right),
+            Expr::Sigil(sigil) => write!(f, "{}", sigil),
+            Expr::Stab(clauses) => write!(f, "{}", join(clauses, "\n")),
+            Expr::String(string) => write!(f, "{}", quote(string, '"')),
+            Expr::Struct(name, pairs) => write!(f, "%{}{{{}}}", name, pairs_str(pairs)),
+            Expr::Tuple(items) => write!(f, "{{{}}}", args_str(items)),
+            Expr::UnaryOp(op, expr) if op.chars().all(char::is_alphabetic) => {
+                write!(f, "{} {}", op, expr)
+            }
+            Expr::UnaryOp(op, expr) if op.eq("&") => match capture(expr) {
+                Some(fun) => write!(f, "&{}", fun),
+                None => write!(f, "&{}", operand(op, expr)),
+            },
+            Expr::UnaryOp(op, expr) => write!(f, "{}{}", op, expr),
+            Expr::Var(var) => write!(f, "{}", var),
+        }
+    }
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(target) = &self.target {
+            write!(f, "{}.", target)?;
+        }
+
+        write!(f, "{}({})", self.name, args_str(&self.args))
+    }
+}
+
+impl fmt::Display for Clause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", join(&self.args, ", "))?;
+
+        if let Some(guard) = &self.guard {
+            write!(f, " when {}", guard)?;
+        }
+
+        write!(f, " -> {}", self.body)
+    }
+}
+
+impl fmt::Display for Def {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keyword = if self.private { "defp" } else { "def" };
+        write!(f, "{} {}", keyword, self.name)?;
+
+        if !self.args.is_empty() {
+            write!(f, "({})", join(&self.args, ", "))?;
+        }
+
+        if let Some(guard) = &self.guard {
+            write!(f, " when {}", guard)?;
+        }
+
+        match &self.body {
+            Some(body) => write!(f, " do\n{}end", indent(body)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for If {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "if {} do\n{}", self.condition, indent(&self.then))?;
+
+        if let Some(otherwise) = &self.otherwise {
+            write!(f, "else\n{}", indent(otherwise))?;
+        }
+
+        write!(f, "end")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "defmodule {} do\n{}end", self.name, indent(&self.body))
+    }
+}
+
+// with the delimiters it was written with, the
+// closing one escaped inside unless it's a heredoc
+impl fmt::Display for Sigil {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let content = match self.content.as_ref() {
+            Expr::String(string) => string.to_string(),
+            expr => expr.to_string().trim_matches('"').to_string(),
+        };
+        let content = match self.close.len() {
+            1 => content.replace(&self.close, &format!("\\{}", self.close)),
+            _ => content,
+        };
+
+        write!(
+            f,
+            "~{}{}{}{}{}",
+            self.name, self.open, content, self.close, self.modifiers
+        )
+    }
+}
+
+fn join<T: fmt::Display>(items: &[T], separator: &str) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+// a trailing keyword list goes without brackets
+fn args_str(args: &[Expr]) -> String {
+    match args.split_last() {
+        Some((last, rest)) => match last.as_keyword() {
+            Some(keyword) if !keyword.is_empty() => {
+                let mut items = rest.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+                items.push(keyword_str(&keyword));
+
+                items.join(", ")
+            }
+            _ => join(args, ", "),
+        },
+        None => String::new(),
+    }
+}
+
+fn keyword_str(keyword: &[(&str, &Expr)]) -> String {
+    keyword
+        .iter()
+        .map(|(key, value)| format!("{} {}", keyword_key(key), value))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn pairs_str(pairs: &[(Expr, Expr)]) -> String {
+    pairs
+        .iter()
+        .map(|(key, value)| match key {
+            Expr::Atom(atom) => format!("{} {}", keyword_key(atom), value),
+            key => format!("{} => {}", key, value),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+pub fn keyword_key(key: &str) -> String {
+    if is_plain_atom(key) {
+        return format!("{}:", key);
+    }
+
+    format!("{}:", quote(key, '"'))
+}
+
+// nested operators get parens unless
+// they are the same, or a `&1` argument
+fn operand(op: &str, expr: &Expr) -> String {
+    match expr {
+        Expr::UnaryOp(inner, arg) if inner.eq("&") && matches!(**arg, Expr::Number(_)) => {
+            expr.to_string()
+        }
+        Expr::BinaryOp(inner, _, _) | Expr::UnaryOp(inner, _) if inner.ne(op) => {
+            format!("({})", expr)
+        }
+        Expr::Pipe(_, _) => format!("({})", expr),
+        expr => expr.to_string(),
+    }
+}
+
+// `Foo.bar/2` of `&Foo.bar/2`, the
+// captured function by name and arity
+fn capture(expr: &Expr) -> Option<String> {
+    let (fun, arity) = match expr {
+        Expr::BinaryOp(op, fun, arity) if op.eq("/") => (fun, arity),
+        _ => return None,
+    };
+
+    let name = match fun.as_ref() {
+        Expr::Call(call) if call.args.is_empty() => match &call.target {
+            Some(target) => format!("{}.{}", target, call.name),
+            None => call.name.to_string(),
+        },
+        Expr::Var(name) => name.to_string(),
+        _ => return None,
+    };
+
+    match arity.as_ref() {
+        Expr::Number(_) | Expr::Var(_) => Some(format!("{}/{}", name, arity)),
+        _ => None,
+    }
+}
+
+fn indent(body: &Expr) -> String {
+    body.to_string()
+        .lines()
+        .map(|line| match line.is_empty() {
+            true => "\n".to_string(),
+            false => format!("  {}\n", line),
+        })
+        .collect()
+}
+
+fn is_plain_atom(atom: &str) -> bool {
+    let body = atom.strip_suffix(['?', '!']).unwrap_or(atom);
+
+    body.starts_with(|ch: char| ch.is_alphabetic() || ch.eq(&'_'))
+        && body.chars().all(|ch| ch.is_alphanumeric() || ch.eq(&'_'))
+}
+
+pub fn quote(string: &str, quote: char) -> String {
+    format!("{}{}{}", quote, escape(string, quote), quote)
+}
+
+fn escape(string: &str, quote: char) -> String {
+    let mut escaped = String::new();
+    let mut chars = string.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '#' if chars.peek().eq(&Some(&'{')) => escaped.push_str("\\#"),
+            ch if ch.eq(&quote) => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            ch => escaped.push(ch),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse;
+
+    fn round_trip(source: &str) {
+        let expr = parse(source).unwrap();
+
+        assert_eq!(expr.to_string(), source);
+        assert_eq!(parse(&expr.to_string()).unwrap(), expr);
+    }
+
+    #[test]
+    fn literals() {
+        round_trip(":ok");
+        round_trip(r#":"ecto.setup""#);
+        round_trip(r#""say \"hi\"\n""#);
+        round_trip("'~> 1.0'");
+        round_trip("15_000");
+        round_trip("nil");
+        round_trip("?a");
+        round_trip(r#""v#{@version}""#);
+        round_trip("~r/guides\\/local\\/.*/i");
+        round_trip("~r{guides/local/.*}i");
+        round_trip("~r<a\\>b>");
+        round_trip("~w(a b)a");
+        round_trip("~S|a/b|");
+    }
+
+    #[test]
+    fn containers() {
+        round_trip("[1, [2, 3]]");
+        round_trip(r#"[app: :foo, "ecto.setup": ["ecto.create"]]"#);
+        round_trip("{:ok, 1}");
+        round_trip(r#"%{"a" => 1, b: 2}"#);
+        round_trip("%Foo{bar: 1}");
+        round_trip("<<>>");
+        round_trip(r#"<<size :: 16, "v" :: binary>>"#);
+    }
+
+    #[test]
+    fn dep_tuple() {
+        round_trip(r#"{:credo, "~> 1.6", only: [:dev, :test], runtime: false}"#);
+        round_trip(r#"{:grpc, github: "elixir-grpc/grpc"}"#);
+    }
+
+    #[test]
+    fn calls_and_operators() {
+        round_trip("Mix.env() == :prod");
+        round_trip("[:gettext] ++ Mix.compilers()");
+        round_trip("elixirc_paths(Mix.env())");
+        round_trip("(a or b) and c");
+        round_trip("foo |> bar(1)");
+        round_trip("not valid?");
+    }
+
+    #[test]
+    fn captures() {
+        round_trip("&Foo.bar/2");
+        round_trip("&bar/arity");
+        round_trip("&:erlang.now/0");
+        round_trip("&Foo.bar(&1, 2)");
+        round_trip("&(&1 + 1)");
+        round_trip("fn a -> a; b -> b end");
+    }
+
+    #[test]
+    fn definitions() {
+        round_trip("def project do\n  [app: :foo]\nend");
+        round_trip("defp elixirc_paths(:test) do\n  [\"lib\"]\nend");
+        round_trip("defmodule Foo do\n  use(Mix.Project)\nend");
+    }
+}
@@ -0,0 +1,649 @@
+use super::{
+    ast::Expr,
+    lexer::token::{Span, Token},
+    Parser,
+};
+use anyhow::{anyhow, Result};
+use std::{fmt, ops::Range};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyntaxKind {
+    Access,
+    Alias,
+    Atom,
+    Attribute,
+    BinaryOp,
+    Bitstring,
+    Block, // body of a `do` block section
+    Boolean,
+    Call,
+    Char,
+    Charlist,
+    Clause,
+    Def,
+    DoBlock, // from `do` to `end`
+    Fn,
+    If,
+    Interpolated,
+    List,
+    Map,
+    MapUpdate,
+    Module,
+    Nil,
+    Number,
+    Pair, // `key: value` and `key => value`
+    Pipe,
+    Root,
+    Sigil,
+    Stab,
+    String,
+    Struct,
+    Tuple,
+    UnaryOp,
+    Var,
+}
+
+// A node only knows which tokens it spans,
+// whitespace, newlines and comments between
+// its children included
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxNode {
+    kind: SyntaxKind,
+    range: Range<usize>,
+    children: Vec<SyntaxNode>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SyntaxTree {
+    root: SyntaxNode,
+    tokens: Vec<Token>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Checkpoint {
+    children: usize,
+    start: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(super) struct Builder {
+    frames: Vec<Frame>,
+}
+
+#[derive(Clone, Debug)]
+struct Frame {
+    start: usize,
+    children: Vec<SyntaxNode>,
+}
+
+impl From<&Expr> for SyntaxKind {
+    fn from(expr: &Expr) -> Self {
+        match expr {
+            Expr::Access(_, _) => SyntaxKind::Access,
+            Expr::Alias(_) => SyntaxKind::Alias,
+            Expr::Atom(_) => SyntaxKind::Atom,
+            Expr::Attribute(_, _) => SyntaxKind::Attribute,
+            Expr::BinaryOp(_, _, _) => SyntaxKind::BinaryOp,
+            Expr::Bitstring(_) => SyntaxKind::Bitstring,
+            Expr::Block(_) => SyntaxKind::Block,
+            Expr::Boolean(_) => SyntaxKind::Boolean,
+            Expr::Call(_) => SyntaxKind::Call,
+            Expr::Char(_) => SyntaxKind::Char,
+            Expr::Charlist(_) => SyntaxKind::Charlist,
+            Expr::Def(_) => SyntaxKind::Def,
+            Expr::Fn(_) => SyntaxKind::Fn,
+            Expr::If(_) => SyntaxKind::If,
+            Expr::Interpolated(_) => SyntaxKind::Interpolated,
+            Expr::List(_) => SyntaxKind::List,
+            Expr::Map(_) => SyntaxKind::Map,
+            Expr::MapUpdate(_, _) => SyntaxKind::MapUpdate,
+            Expr::Module(_) => SyntaxKind::Module,
+            Expr::Nil => SyntaxKind::Nil,
+            Expr::Number(_) => SyntaxKind::Number,
+            Expr::Pipe(_, _) => SyntaxKind::Pipe,
+            Expr::Sigil(_) => SyntaxKind::Sigil,
+            Expr::Stab(_) => SyntaxKind::Stab,
+            Expr::String(_) => SyntaxKind::String,
+            Expr::Struct(_, _) => SyntaxKind::Struct,
+            Expr::Tuple(_) => SyntaxKind::Tuple,
+            Expr::UnaryOp(_, _) => SyntaxKind::UnaryOp,
+            Expr::Var(_) => SyntaxKind::Var,
+        }
+    }
+}
+
+impl SyntaxNode {
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    // token indexes, not bytes
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    pub fn children(&self) -> &[SyntaxNode] {
+        &self.children
+    }
+
+    // this node and all nodes below it, in source order
+    pub fn descendants(&self) -> Vec<&SyntaxNode> {
+        let mut nodes = vec![self];
+
+        for child in self.children.iter() {
+            nodes.extend(child.descendants());
+        }
+
+        nodes
+    }
+
+    // body of `do ... end` or `do:`, a
+    // single expression block is unwrapped
+    pub fn do_body(&self) -> Option<&SyntaxNode> {
+        let last = self.children.last()?;
+
+        let body = match last.kind {
+            SyntaxKind::DoBlock => last.children.first()?,
+            SyntaxKind::List => last.children.first()?.children.first()?,
+            _ => return None,
+        };
+
+        match body.kind {
+            SyntaxKind::Block if body.children.len() == 1 => body.children.first(),
+            _ => Some(body),
+        }
+    }
+}
+
+impl SyntaxTree {
+    pub fn parse(source: &str) -> Result<Self> {
+        Parser::new(source)?.parse_tree()
+    }
+
+    pub(super) fn new(root: SyntaxNode, tokens: Vec<Token>) -> Self {
+        SyntaxTree { root, tokens }
+    }
+
+    pub fn root(&self) -> &SyntaxNode {
+        &self.root
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    // source location of the tokens in `range`
+    pub fn span(&self, range: Range<usize>) -> Span {
+        let start = self
+            .tokens
+            .get(range.start)
+            .map(|t| t.span().start())
+            .unwrap_or_default();
+        let end = match range.is_empty() {
+            true => start,
+            false => self.tokens[range.end - 1].span().end(),
+        };
+
+        Span::new(start, end)
+    }
+
+    pub fn text(&self, range: Range<usize>) -> String {
+        self.tokens[range].iter().map(|t| t.lexeme()).collect()
+    }
+
+    // parses the node on its own, keyword
+    // pairs come back as `{key, value}` tuples
+    pub fn expr(&self, node: &SyntaxNode) -> Result<Expr> {
+        let text = self.text(node.range());
+
+        if node.kind == SyntaxKind::Pair {
+            let list = super::parse(&format!("[{}]", text))?;
+
+            return list
+                .as_list()
+                .and_then(|l| l.first())
+                .cloned()
+                .ok_or_else(|| anyhow!("`{}` is not a keyword pair", text));
+        }
+
+        super::parse(&text)
+    }
+
+    // swaps the tokens in `range` for `text`, anything
+    // else is kept byte for byte; nodes are rebuilt
+    pub fn replace(&mut self, range: Range<usize>, text: &str) -> Result<()> {
+        let source = format!(
+            "{}{}{}",
+            self.text(0..range.start),
+            text,
+            self.text(range.end..self.tokens.len())
+        );
+
+        *self = SyntaxTree::parse(&source)?;
+
+        Ok(())
+    }
+
+    // removes a node with its comma and, when
+    // it was alone on its lines, the lines too
+    pub fn remove(&mut self, range: Range<usize>) -> Result<()> {
+        let range = self.removal_range(range);
+
+        self.replace(range, "")
+    }
+
+    // adds `text` as the `index`th element of a list,
+    // tuple or map, laid out like the ones around it
+    pub fn insert(&mut self, container: &SyntaxNode, index: usize, text: &str) -> Result<()> {
+        let children = container.children();
+
+        if let Some(next) = children.get(index) {
+            let at = next.range().start;
+            let separator = self.separator(at);
+
+            return self.replace(at..at, &format!("{},{}", text, separator));
+        }
+
+        match children.last() {
+            Some(last) => {
+                let at = last.range().end;
+                let separator = self.separator(last.range().start);
+
+                self.replace(at..at, &format!(",{}{}", separator, text))
+            }
+            None => {
+                // right before the closing delimiter
+                let at = container.range().end - 1;
+
+                self.replace(at..at, text)
+            }
+        }
+    }
+
+    // a line break with the same indentation when
+    // the element at `index` starts its line
+    fn separator(&self, index: usize) -> String {
+        let line_start = self.skip_backward(index, |t| t.kind().is_whitespace());
+
+        if self.is_line_start(line_start) && line_start > 0 {
+            return format!("\n{}", self.text(line_start..index));
+        }
+
+        " ".to_string()
+    }
+
+    fn removal_range(&self, range: Range<usize>) -> Range<usize> {
+        let (mut start, mut end) = (range.start, range.end);
+
+        let next = self.skip_forward(end, |t| t.kind().is_whitespace());
+        let previous = self.skip_backward(start, is_trivia_or_newline);
+
+        if self.kind_at(next, |t| t.kind().is_comma()) {
+            end = next + 1;
+        } else if previous > 0 && self.kind_at(previous - 1, |t| t.kind().is_comma()) {
+            // the last element takes the comma
+            // before it, its line break stays
+            let line_end = self.skip_forward(end, is_trivia);
+
+            if self.is_line_end(line_end) {
+                end = line_end;
+            }
+
+            return previous - 1..end;
+        }
+
+        let line_start = self.skip_backward(start, |t| t.kind().is_whitespace());
+        let line_end = self.skip_forward(end, is_trivia);
+
+        if self.is_line_start(line_start) && self.is_line_end(line_end) {
+            start = line_start;
+            end = (line_end + 1).min(self.tokens.len());
+
+            // don't leave two blank lines behind, nor
+            // one at the top of a block
+            if (self.is_blank_line_before(start) || self.is_block_start(start))
+                && self.is_blank_line_at(end)
+            {
+                end = self.skip_forward(end, |t| t.kind().is_whitespace()) + 1;
+            }
+
+            return start..end;
+        }
+
+        start..self.skip_forward(end, |t| t.kind().is_whitespace())
+    }
+
+    fn kind_at<P>(&self, index: usize, pred: P) -> bool
+    where
+        P: Fn(&Token) -> bool,
+    {
+        self.tokens.get(index).is_some_and(pred)
+    }
+
+    fn skip_forward<P>(&self, mut index: usize, pred: P) -> usize
+    where
+        P: Fn(&Token) -> bool,
+    {
+        while self.kind_at(index, &pred) {
+            index += 1;
+        }
+
+        index
+    }
+
+    fn skip_backward<P>(&self, mut index: usize, pred: P) -> usize
+    where
+        P: Fn(&Token) -> bool,
+    {
+        while index > 0 && self.kind_at(index - 1, &pred) {
+            index -= 1;
+        }
+
+        index
+    }
+
+    fn is_line_start(&self, index: usize) -> bool {
+        index == 0 || self.kind_at(index - 1, |t| t.kind().is_newline())
+    }
+
+    fn is_line_end(&self, index: usize) -> bool {
+        index >= self.tokens.len() || self.kind_at(index, |t| t.kind().is_newline())
+    }
+
+    fn is_blank_line_at(&self, index: usize) -> bool {
+        let end = self.skip_forward(index, |t| t.kind().is_whitespace());
+
+        self.kind_at(end, |t| t.kind().is_newline())
+    }
+
+    // the line before ends with `do`, `->` or an open delimiter
+    fn is_block_start(&self, index: usize) -> bool {
+        if index < 2 {
+            return false;
+        }
+
+        let end = self.skip_backward(index - 1, |t| t.kind().is_whitespace());
+
+        end > 0 && ["do", "->", "(", "[", "{"].contains(&self.tokens[end - 1].lexeme().as_str())
+    }
+
+    fn is_blank_line_before(&self, index: usize) -> bool {
+        if index < 2 {
+            return false;
+        }
+
+        let start = self.skip_backward(index - 1, |t| t.kind().is_whitespace());
+
+        start == 0 || self.kind_at(start - 1, |t| t.kind().is_newline())
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token in self.tokens.iter() {
+            write!(f, "{}", token.lexeme())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Builder {
+    pub(super) fn start(&mut self, start: usize) {
+        self.frames.push(Frame {
+            start,
+            children: vec![],
+        });
+    }
+
+    pub(super) fn checkpoint(&self, start: usize) -> Checkpoint {
+        let children = self.frames.last().map_or(0, |f| f.children.len());
+
+        Checkpoint { children, start }
+    }
+
+    // starts a node that takes the nodes
+    // finished since `checkpoint` as children
+    pub(super) fn start_at(&mut self, checkpoint: Checkpoint) {
+        let children = self
+            .frames
+            .last_mut()
+            .map(|f| f.children.split_off(checkpoint.children))
+            .unwrap_or_default();
+
+        self.frames.push(Frame {
+            start: checkpoint.start,
+            children,
+        });
+    }
+
+    pub(super) fn finish(&mut self, kind: SyntaxKind, end: usize) -> Option<SyntaxNode> {
+        let frame = self.frames.pop()?;
+        let node = SyntaxNode {
+            kind,
+            range: frame.start..end.max(frame.start),
+            children: frame.children,
+        };
+
+        match self.frames.last_mut() {
+            Some(parent) => {
+                parent.children.push(node);
+                None
+            }
+            None => Some(node),
+        }
+    }
+}
+
+fn is_trivia(token: &Token) -> bool {
+    token.kind().is_whitespace() || token.kind().is_comment()
+}
+
+fn is_trivia_or_newline(token: &Token) -> bool {
+    is_trivia(token) || token.kind().is_newline()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEPS: &str = r#"defmodule Foo.MixProject do
+  use Mix.Project
+
+  # Run "mix help deps" to learn about dependencies.
+  defp deps do
+    [
+      # start deps
+      {:phoenix, "~> 1.6.6"},   # web
+      {:jason, "~> 1.2"},
+      {:credo, "~> 1.6", only: [:dev, :test], runtime: false}
+      # end deps
+    ]
+  end
+end
+"#;
+
+    fn deps_list(tree: &SyntaxTree) -> &SyntaxNode {
+        tree.root()
+            .descendants()
+            .into_iter()
+            .find(|n| n.kind() == SyntaxKind::Def)
+            .and_then(|n| n.do_body())
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let tree = SyntaxTree::parse(DEPS).unwrap();
+
+        assert_eq!(tree.to_string(), DEPS);
+    }
+
+    #[test]
+    fn node_text() {
+        let tree = SyntaxTree::parse(DEPS).unwrap();
+        let list = deps_list(&tree);
+
+        assert_eq!(list.kind(), SyntaxKind::List);
+        assert_eq!(list.children().len(), 3);
+        assert_eq!(
+            tree.text(list.children()[1].range()),
+            r#"{:jason, "~> 1.2"}"#
+        );
+    }
+
+    #[test]
+    fn node_span() {
+        let tree = SyntaxTree::parse(DEPS).unwrap();
+        let span = tree.span(deps_list(&tree).children()[1].range());
+
+        assert_eq!(span.start().line(), 9);
+        assert_eq!(span.start().column(), 7);
+        assert_eq!(&DEPS[span.range()], r#"{:jason, "~> 1.2"}"#);
+    }
+
+    #[test]
+    fn node_expr() {
+        let tree = SyntaxTree::parse("[app: :foo, version: \"0.1.0\"]").unwrap();
+        let pair = &tree.root().children()[0].children()[1];
+
+        assert_eq!(pair.kind(), SyntaxKind::Pair);
+        assert_eq!(
+            tree.expr(pair).unwrap(),
+            Expr::Tuple(vec![
+                Expr::Atom("version".to_string()),
+                Expr::String("0.1.0".to_string())
+            ])
+        );
+
+        // a map pair is no keyword pair
+        let tree = SyntaxTree::parse("%{\"a\" => 1}").unwrap();
+        let pair = &tree.root().children()[0].children()[0];
+
+        assert_eq!(pair.kind(), SyntaxKind::Pair);
+        assert!(tree.expr(pair).is_err());
+    }
+
+    #[test]
+    fn replace() {
+        let mut tree = SyntaxTree::parse(DEPS).unwrap();
+        let range = deps_list(&tree).children()[1].range();
+
+        tree.replace(range, r#"{:jason, "~> 1.3"}"#).unwrap();
+
+        assert_eq!(tree.to_string(), DEPS.replace("1.2", "1.3"));
+    }
+
+    #[test]
+    fn remove_middle_element() {
+        let mut tree = SyntaxTree::parse(DEPS).unwrap();
+        let range = deps_list(&tree).children()[1].range();
+
+        tree.remove(range).unwrap();
+
+        assert_eq!(
+            tree.to_string(),
+            DEPS.replace("      {:jason, \"~> 1.2\"},\n", "")
+        );
+    }
+
+    #[test]
+    fn remove_commented_element() {
+        let mut tree = SyntaxTree::parse(DEPS).unwrap();
+        let range = deps_list(&tree).children()[0].range();
+
+        tree.remove(range).unwrap();
+
+        assert_eq!(
+            tree.to_string(),
+            DEPS.replace("      {:phoenix, \"~> 1.6.6\"},   # web\n", "")
+        );
+    }
+
+    #[test]
+    fn remove_last_element() {
+        let mut tree = SyntaxTree::parse(DEPS).unwrap();
+        let range = deps_list(&tree).children()[2].range();
+
+        tree.remove(range).unwrap();
+
+        let expected = DEPS.replace(
+            ",\n      {:credo, \"~> 1.6\", only: [:dev, :test], runtime: false}",
+            "",
+        );
+        assert_eq!(tree.to_string(), expected);
+    }
+
+    #[test]
+    fn remove_inline_element() {
+        let mut tree = SyntaxTree::parse("[:dev, :test, :prod]").unwrap();
+        let list = &tree.root().children()[0];
+
+        tree.remove(list.children()[1].range()).unwrap();
+        assert_eq!(tree.to_string(), "[:dev, :prod]");
+
+        let list = &tree.root().children()[0];
+        tree.remove(list.children()[1].range()).unwrap();
+        assert_eq!(tree.to_string(), "[:dev]");
+    }
+
+    #[test]
+    fn insert_element() {
+        let mut tree = SyntaxTree::parse(DEPS).unwrap();
+        let list = deps_list(&tree).clone();
+
+        tree.insert(&list, 1, r#"{:ecto, "~> 3.6"}"#).unwrap();
+
+        let expected = DEPS.replace("      {:jason", "      {:ecto, \"~> 3.6\"},\n      {:jason");
+        assert_eq!(tree.to_string(), expected);
+    }
+
+    #[test]
+    fn insert_last_element() {
+        let mut tree = SyntaxTree::parse(DEPS).unwrap();
+        let list = deps_list(&tree).clone();
+
+        tree.insert(&list, 3, r#"{:mox, "~> 1.0"}"#).unwrap();
+
+        let expected = DEPS.replace(
+            "runtime: false}\n",
+            "runtime: false},\n      {:mox, \"~> 1.0\"}\n",
+        );
+        assert_eq!(tree.to_string(), expected);
+    }
+
+    #[test]
+    fn insert_inline_element() {
+        let mut tree = SyntaxTree::parse("{[], [:dev]}").unwrap();
+
+        let list = tree.root().children()[0].children()[1].clone();
+        tree.insert(&list, 1, ":test").unwrap();
+        assert_eq!(tree.to_string(), "{[], [:dev, :test]}");
+
+        let empty = tree.root().children()[0].children()[0].clone();
+        tree.insert(&empty, 0, ":prod").unwrap();
+        assert_eq!(tree.to_string(), "{[:prod], [:dev, :test]}");
+    }
+
+    #[test]
+    fn remove_statement() {
+        let source = "import Config\n\nconfig :foo,\n  bar: 1\n\nconfig :baz, qux: 2\n";
+        let mut tree = SyntaxTree::parse(source).unwrap();
+        let range = tree.root().children()[1].range();
+
+        tree.remove(range).unwrap();
+
+        assert_eq!(tree.to_string(), "import Config\n\nconfig :baz, qux: 2\n");
+    }
+
+    #[test]
+    fn remove_first_statement() {
+        let source = "def start do\n  Foo.setup()\n\n  Bar.run()\nend\n";
+        let mut tree = SyntaxTree::parse(source).unwrap();
+        let range = tree.root().children()[0].do_body().unwrap().children()[0].range();
+
+        tree.remove(range).unwrap();
+
+        assert_eq!(tree.to_string(), "def start do\n  Bar.run()\nend\n");
+    }
+}
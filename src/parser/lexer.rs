@@ -1,19 +1,39 @@
-use self::token::{Token, TokenKind};
+use self::{
+    diagnostic::Diagnostic,
+    token::{Fragment, Position, Span, Token, TokenKind},
+};
 use std::char;
 
+pub mod diagnostic;
 pub mod token;
 
+const TOKEN_CATEGORIES: [&str; 9] = [
+    "atom",
+    "char",
+    "comment",
+    "delimiter",
+    "identifier",
+    "number",
+    "operator",
+    "sigil",
+    "string",
+];
+
 #[derive(Clone, Debug)]
 pub struct Lexer {
     cursor: usize,
+    diagnostics: Vec<Diagnostic>,
     input: Vec<char>,
+    position: Position,
 }
 
 impl Lexer {
     pub fn new(string: &str) -> Self {
         Lexer {
             cursor: usize::MIN,
+            diagnostics: vec![],
             input: string.chars().collect(),
+            position: Position::default(),
         }
     }
 
@@ -21,6 +41,7 @@ impl Lexer {
     fn read(&mut self) -> Option<&char> {
         if let Some(ch) = self.input.get(self.cursor) {
             self.cursor += 1;
+            self.position.advance(*ch);
 
             return Some(ch);
         }
@@ -62,31 +83,59 @@ impl Lexer {
         self.input.get(self.cursor)
     }
 
-    fn is_done(&self) -> bool {
+    pub fn is_done(&self) -> bool {
         self.cursor >= self.input.len()
     }
+
+    // where the next token starts
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    // one for each `Error` token so far
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
 }
 
 impl Iterator for Lexer {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let (cursor, start) = (self.cursor, self.position);
         let peek = self.peek()?;
 
-        match peek {
+        let token = match peek {
             '#' => read_comment(self),
             ',' => read_comma(self),
             ':' => read_atom(self),
             '?' => read_char(self),
             ch if is_newline(ch) => read_newline(self),
-            ch if is_quote(ch) => read_quote(self),
+            ch if is_quote(ch) => read_string(self),
             ch if is_delim(ch) => read_delim(self),
+            '~' if is_sigil(self) => read_sigil(self),
             ch if is_operator(ch) => read_operator(self),
             ch if ch.is_numeric() => read_number(self),
             ch if is_identifier(ch) => read_identifier(self),
             ch if ch.is_whitespace() => read_whitespace(self),
             _ => None,
+        };
+
+        if let Some(mut token) = token {
+            token.set_span(Span::new(start, self.position));
+
+            return Some(token);
         }
+
+        // start over from the offending char
+        self.cursor = cursor;
+        self.position = start;
+
+        let (mut token, expected) = read_error(self)?;
+        token.set_span(Span::new(start, self.position));
+        self.diagnostics.push(Diagnostic::new(&token, expected));
+
+        Some(token)
     }
 }
 
@@ -115,8 +164,8 @@ fn read_char(lex: &mut Lexer) -> Option<Token> {
 }
 
 fn read_atom(lex: &mut Lexer) -> Option<Token> {
-    let next = lex.peek_ahead(1)?;
-    if next.is_alphanumeric() || is_quote(next) {
+    let next = lex.peek_ahead(1);
+    if next.is_some_and(|ch| ch.is_alphanumeric() || is_quote(ch)) {
         let atom = lex.read_while(is_atom)?;
 
         return Some(Token::new(TokenKind::Atom, atom));
@@ -127,14 +176,296 @@ fn read_atom(lex: &mut Lexer) -> Option<Token> {
     read_operator(lex)
 }
 
-fn read_quote(lex: &mut Lexer) -> Option<Token> {
-    let quote = lex.read()?.to_string();
+// `"..."`, `'...'` and their heredoc forms,
+// the lexeme keeps the source as written
+fn read_string(lex: &mut Lexer) -> Option<Token> {
+    let start = lex.cursor;
+    let quote = *lex.peek()?;
+
+    let fragments = read_quoted(lex, quote, true)?
+        .into_iter()
+        .map(|fragment| match fragment {
+            Fragment::Literal(raw) => Fragment::Literal(unescape(&raw)),
+            interpolation => interpolation,
+        })
+        .collect();
+
+    let kind = match quote {
+        '"' => TokenKind::String,
+        _ => TokenKind::Charlist,
+    };
+    let lexeme = lex.input[start..lex.cursor].iter().collect();
+
+    let mut token = Token::new(kind, lexeme);
+    token.set_fragments(fragments);
+
+    Some(token)
+}
+
+// `~r/regex/i`, `~w(a b c)a`, `~D[2022-06-01]`...
+// only lowercase sigils interpolate, and
+// escapes are left to the sigil but for
+// the strings ones and the closing delimiter
+fn read_sigil(lex: &mut Lexer) -> Option<Token> {
+    let start = lex.cursor;
+
+    lex.read();
+    let name = lex.read_while(char::is_ascii_alphanumeric)?;
+    let open = *lex.peek()?;
+    let close = closing_delim(&open)?;
+    let lowercase = name.starts_with(|ch: char| ch.is_ascii_lowercase());
+
+    let fragments = read_quoted(lex, close, lowercase)?
+        .into_iter()
+        .map(|fragment| match fragment {
+            Fragment::Literal(raw) if lowercase && ["c", "s", "w"].contains(&name.as_str()) => {
+                Fragment::Literal(unescape(&raw))
+            }
+            Fragment::Literal(raw) => {
+                Fragment::Literal(raw.replace(&format!("\\{}", close), &close.to_string()))
+            }
+            interpolation => interpolation,
+        })
+        .collect();
+
+    // modifiers
+    lex.read_while(char::is_ascii_alphanumeric);
+
+    let lexeme = lex.input[start..lex.cursor].iter().collect();
+
+    let mut token = Token::new(TokenKind::Sigil, lexeme);
+    token.set_fragments(fragments);
+
+    Some(token)
+}
+
+// contents between the opening char under
+// the cursor and `close`, escapes untouched
+fn read_quoted(lex: &mut Lexer, close: char, interpolate: bool) -> Option<Vec<Fragment>> {
+    let open = *lex.peek()?;
+    let heredoc =
+        is_quote(&open) && lex.peek_ahead(1).eq(&Some(&open)) && lex.peek_ahead(2).eq(&Some(&open));
+
+    if heredoc {
+        lex.read_while(|ch| ch.eq(&open));
+        // contents start on the next line
+        lex.read_while(|ch| ch.is_whitespace() && !is_newline(ch));
+        lex.read_while(is_newline)?;
+    } else {
+        lex.read();
+    }
+
+    let mut fragments = Vec::<Fragment>::new();
+    let mut raw = String::new();
+
+    loop {
+        let ch = *lex.peek()?;
+
+        match ch {
+            '\\' => {
+                raw.push(*lex.read()?);
+                raw.push(*lex.read()?);
+            }
+            '#' if interpolate && lex.peek_ahead(1).eq(&Some(&'{')) => {
+                lex.read();
+                lex.read();
+
+                fragments.push(Fragment::Literal(std::mem::take(&mut raw)));
+                fragments.push(Fragment::Interpolation(read_interpolation(lex)?));
+            }
+            ch if ch.eq(&close) && !heredoc => {
+                lex.read();
+                break;
+            }
+            ch if ch.eq(&close) && is_heredoc_end(lex, &fragments, &raw) => {
+                lex.read_while(|ch| ch.eq(&close));
+                break;
+            }
+            _ => raw.push(*lex.read()?),
+        }
+    }
+
+    fragments.push(Fragment::Literal(raw));
+
+    if heredoc {
+        dedent(&mut fragments);
+    }
+
+    fragments.retain(|fragment| !matches!(fragment, Fragment::Literal(raw) if raw.is_empty()));
+
+    Some(fragments)
+}
+
+// unterminated strings and sigils take the
+// rest of their line, anything else one char
+fn read_error(lex: &mut Lexer) -> Option<(Token, Vec<String>)> {
+    let ch = *lex.peek()?;
+
+    let close = match ch {
+        '~' => {
+            let ahead = (1..).find(|ahead| {
+                !lex.peek_ahead(*ahead)
+                    .is_some_and(char::is_ascii_alphanumeric)
+            })?;
+
+            lex.peek_ahead(ahead).and_then(closing_delim)
+        }
+        ch if is_quote(&ch) => Some(ch),
+        _ => None,
+    };
+
+    if let Some(close) = close {
+        let error = lex.read_while(|ch| !is_newline(ch))?;
+        let expected = vec![format!("closing `{}`", close)];
+
+        return Some((Token::new(TokenKind::Error, error), expected));
+    }
+
+    let error = lex.read()?.to_string();
+    let expected = TOKEN_CATEGORIES.iter().map(|c| c.to_string()).collect();
+
+    Some((Token::new(TokenKind::Error, error), expected))
+}
+
+// tokens up to the matching `}`
+fn read_interpolation(lex: &mut Lexer) -> Option<Vec<Token>> {
+    let mut tokens = Vec::<Token>::new();
+    let mut depth = 0;
+
+    loop {
+        let token = lex.next()?;
+
+        if token.kind().is_delimiter() {
+            match token.lexeme().as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => return Some(tokens),
+                "}" => depth -= 1,
+                _ => {}
+            }
+        }
+
+        tokens.push(token);
+    }
+}
+
+// closing quotes of a heredoc sit
+// on a line of their own
+fn is_heredoc_end(lex: &Lexer, fragments: &[Fragment], raw: &str) -> bool {
+    let quote = lex.peek();
+    let triple = lex.peek_ahead(1).eq(&quote) && lex.peek_ahead(2).eq(&quote);
+
+    let line_start = match raw.rfind('\n') {
+        Some(i) => raw[i + 1..].chars().all(char::is_whitespace),
+        None => fragments.is_empty() && raw.chars().all(char::is_whitespace),
+    };
+
+    triple && line_start
+}
+
+// heredoc lines lose the indentation
+// of the closing quotes
+fn dedent(fragments: &mut [Fragment]) {
+    let indent = match fragments.last_mut() {
+        Some(Fragment::Literal(last)) => {
+            let line_start = last.rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let indent = last[line_start..].chars().count();
+            last.truncate(line_start);
 
-    Some(Token::new(TokenKind::Quote, quote))
+            indent
+        }
+        _ => return,
+    };
+
+    for (index, fragment) in fragments.iter_mut().enumerate() {
+        if let Fragment::Literal(raw) = fragment {
+            let dedented = raw
+                .split('\n')
+                .enumerate()
+                .map(|(line, text)| {
+                    // text after an interpolation
+                    // is not at a line start
+                    if line == 0 && index > 0 {
+                        return text.to_string();
+                    }
+
+                    let skip = text
+                        .chars()
+                        .take(indent)
+                        .take_while(|ch| ch.is_whitespace())
+                        .count();
+
+                    text.chars().skip(skip).collect()
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            *raw = dedented;
+        }
+    }
+}
+
+fn unescape(raw: &str) -> String {
+    let mut string = String::new();
+    let mut chars = raw.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            string.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => string.push('\n'),
+            Some('t') => string.push('\t'),
+            Some('r') => string.push('\r'),
+            Some('s') => string.push(' '),
+            Some('e') => string.push('\u{1b}'),
+            Some('0') => string.push('\0'),
+            Some('a') => string.push('\u{07}'),
+            Some('b') => string.push('\u{08}'),
+            Some('f') => string.push('\u{0c}'),
+            Some('v') => string.push('\u{0b}'),
+            Some('x') => {
+                let hex = read_hex(&mut chars, 2);
+                string.extend(hex);
+            }
+            Some('u') if chars.peek().eq(&Some(&'{')) => {
+                chars.next();
+                let hex = read_hex(&mut chars, 6);
+                chars.next_if_eq(&'}');
+                string.extend(hex);
+            }
+            Some('u') => string.extend(read_hex(&mut chars, 4)),
+            // line continuation
+            Some('\n') => {}
+            Some(escaped) => string.push(escaped),
+            None => string.push(ch),
+        }
+    }
+
+    string
+}
+
+// up to `max` hex digits as a codepoint
+fn read_hex<I>(chars: &mut std::iter::Peekable<I>, max: usize) -> Option<char>
+where
+    I: Iterator<Item = char>,
+{
+    let mut hex = String::new();
+
+    while let Some(ch) = chars.next_if(|ch| ch.is_ascii_hexdigit()) {
+        hex.push(ch);
+
+        if hex.len() == max {
+            break;
+        }
+    }
+
+    u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
 }
 
 fn read_whitespace(lex: &mut Lexer) -> Option<Token> {
-    let ws = lex.read_while(&|c: &char| c.is_whitespace())?;
+    let ws = lex.read_while(|c| c.is_whitespace() && !is_newline(c))?;
 
     Some(Token::new(TokenKind::WhiteSpace, ws))
 }
@@ -161,8 +492,31 @@ fn read_delim(lex: &mut Lexer) -> Option<Token> {
     Some(Token::new(TokenKind::Delimiter, delim.to_string()))
 }
 
+// `&:foo` is a capture of an atom and
+// `<<>>` an empty bitstring, two tokens each
 fn read_operator(lex: &mut Lexer) -> Option<Token> {
-    let op = lex.read_while(is_operator)?;
+    let mut op = String::new();
+
+    while let Some(ch) = lex.peek().copied() {
+        let before_atom = ch.eq(&':')
+            && !op.is_empty()
+            && !op.ends_with(':')
+            && lex
+                .peek_ahead(1)
+                .is_some_and(|next| next.is_alphanumeric() || is_quote(next));
+        let closes_bitstring = op.eq("<<") && ch.eq(&'>');
+
+        if !is_operator(&ch) || before_atom || closes_bitstring {
+            break;
+        }
+
+        op.push(ch);
+        lex.read();
+    }
+
+    if op.is_empty() {
+        return None;
+    }
 
     Some(Token::new(TokenKind::Operator, op))
 }
@@ -179,6 +533,35 @@ fn is_char(ch: &char) -> bool {
     ch.eq(&'?') || ch.is_alphanumeric()
 }
 
+// `~` followed by a name and
+// an opening delimiter
+fn is_sigil(lex: &Lexer) -> bool {
+    if !lex.peek_ahead(1).is_some_and(char::is_ascii_alphabetic) {
+        return false;
+    }
+
+    let mut ahead = 2;
+    while lex
+        .peek_ahead(ahead)
+        .is_some_and(char::is_ascii_alphanumeric)
+    {
+        ahead += 1;
+    }
+
+    lex.peek_ahead(ahead).and_then(closing_delim).is_some()
+}
+
+pub fn closing_delim(open: &char) -> Option<char> {
+    match open {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '<' => Some('>'),
+        '/' | '|' | '"' | '\'' => Some(*open),
+        _ => None,
+    }
+}
+
 fn is_delim(ch: &char) -> bool {
     ch.eq(&'(')
         || ch.eq(&')')
@@ -187,6 +570,7 @@ fn is_delim(ch: &char) -> bool {
         || ch.eq(&'{')
         || ch.eq(&'}')
         || ch.eq(&'%')
+        || ch.eq(&';')
 }
 
 fn is_quote(ch: &char) -> bool {
@@ -195,6 +579,8 @@ fn is_quote(ch: &char) -> bool {
 
 fn is_operator(ch: &char) -> bool {
     ch.is_ascii_punctuation()
+        && !is_delim(ch)
+        && !is_quote(ch)
         && !ch.eq(&'`')
         && !ch.eq(&'_')
         && !ch.eq(&'@')
@@ -210,22 +596,15 @@ fn is_identifier(ch: &char) -> bool {
 }
 
 fn is_number(ch: &char) -> bool {
-    ch.is_ascii_alphanumeric() || ch.eq(&'.')
+    ch.is_ascii_alphanumeric() || ch.eq(&'.') || ch.eq(&'_')
 }
 
 fn is_extra_literal(ch: &char) -> bool {
-    ch.eq(&'_')
-        || ch.eq(&'@')
-        || ch.eq(&'?')
-        || ch.eq(&'!')
-        || ch.eq(&'{')
-        || ch.eq(&'%')
-        || ch.eq(&'}')
-        || ch.eq(&'.')
+    ch.eq(&'_') || ch.eq(&'@') || ch.eq(&'?') || ch.eq(&'!') || ch.eq(&'.')
 }
 
 fn is_newline(ch: &char) -> bool {
-    ch.eq(&'\n') || ch.eq(&'\t') || ch.eq(&'\r')
+    ch.eq(&'\n') || ch.eq(&'\r')
 }
 
 #[cfg(test)]
@@ -260,6 +639,48 @@ mod cursor {
     }
 }
 
+#[cfg(test)]
+mod position {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let lex = Lexer::new("");
+
+        assert_eq!(lex.position(), Position::new(0, 1, 1));
+    }
+
+    #[test]
+    fn same_line() {
+        let mut lex = Lexer::new("abc");
+
+        lex.read();
+        lex.read();
+
+        assert_eq!(lex.position(), Position::new(2, 1, 3));
+    }
+
+    #[test]
+    fn next_line() {
+        let mut lex = Lexer::new("a\nbc");
+
+        lex.read();
+        lex.read();
+        lex.read();
+
+        assert_eq!(lex.position(), Position::new(3, 2, 2));
+    }
+
+    #[test]
+    fn multibyte() {
+        let mut lex = Lexer::new("é!");
+
+        lex.read();
+
+        assert_eq!(lex.position(), Position::new(2, 1, 2));
+    }
+}
+
 #[cfg(test)]
 mod peek {
     use super::*;
@@ -367,7 +788,7 @@ mod is_done {
 
         lex.read();
 
-        assert_eq!(lex.is_done(), false)
+        assert!(!lex.is_done())
     }
 
     #[test]
@@ -383,6 +804,7 @@ mod is_done {
 }
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod lexer {
     use super::*;
 
@@ -402,6 +824,33 @@ mod lexer {
         assert_eq!(token.lexeme(), atom.to_string());
     }
 
+    #[test]
+    fn should_read_atom_before_delim() {
+        let mut lex = Lexer::new("{:ok, :test}");
+
+        lex.next();
+        let ok = lex.next().unwrap();
+        assert!(ok.kind().is_atom());
+        assert_eq!(ok.lexeme(), ":ok");
+
+        lex.next();
+        lex.next();
+        let test = lex.next().unwrap();
+        assert!(test.kind().is_atom());
+        assert_eq!(test.lexeme(), ":test");
+    }
+
+    #[test]
+    fn should_read_bool_before_delim() {
+        let mut lex = Lexer::new("false}");
+
+        let f = lex.next().unwrap();
+        assert!(f.kind().is_boolean());
+        assert_eq!(f.lexeme(), "false");
+
+        assert!(lex.next().unwrap().kind().is_delimiter());
+    }
+
     #[test]
     fn should_read_int() {
         let int = "40";
@@ -426,6 +875,14 @@ mod lexer {
         assert_eq!(token.lexeme(), sci_f.to_string());
     }
 
+    #[test]
+    fn should_read_underscored_int() {
+        let int = "15_000";
+        let token = Lexer::new(int).next().unwrap();
+        assert!(token.kind().is_number());
+        assert_eq!(token.lexeme(), int.to_string());
+    }
+
     #[test]
     fn should_read_bin() {
         let bin = "0b1010";
@@ -452,7 +909,7 @@ mod lexer {
 
     #[test]
     fn should_read_delims() {
-        let delims = "{}()[]";
+        let delims = "{}()[];";
         let mut lex = Lexer::new(delims);
 
         while !lex.is_done() {
@@ -515,18 +972,264 @@ mod lexer {
         assert_eq!(token.lexeme(), id.to_string());
     }
 
+    fn literal(string: &str) -> Fragment {
+        Fragment::Literal(string.to_string())
+    }
+
     #[test]
-    fn should_read_quotes() {
-        let quotes = "'\"";
-        let mut lex = Lexer::new(quotes);
+    fn should_read_string() {
+        let string = r#""git@github.com:solfacil/database.git""#;
+        let token = Lexer::new(string).next().unwrap();
+        assert!(token.kind().is_string());
+        assert_eq!(token.lexeme(), string.to_string());
+        assert_eq!(
+            token.fragments(),
+            &[literal("git@github.com:solfacil/database.git")]
+        );
+    }
+
+    #[test]
+    fn should_read_empty_string() {
+        let mut lex = Lexer::new(r#""", 1"#);
+
+        let token = lex.next().unwrap();
+        assert!(token.kind().is_string());
+        assert!(token.fragments().is_empty());
+
+        assert!(lex.next().unwrap().kind().is_comma());
+    }
+
+    #[test]
+    fn should_read_charlist() {
+        let charlist = "'~> 1.6.6'";
+        let token = Lexer::new(charlist).next().unwrap();
+        assert!(token.kind().is_charlist());
+        assert_eq!(token.lexeme(), charlist.to_string());
+        assert_eq!(token.fragments(), &[literal("~> 1.6.6")]);
+    }
+
+    #[test]
+    fn should_read_escapes() {
+        let string = r#""say \"hi\"\n\t\\ \#{no} \x41\u00e9\u{1F600}""#;
+        let token = Lexer::new(string).next().unwrap();
+        assert_eq!(token.lexeme(), string.to_string());
+        assert_eq!(token.fragments(), &[literal("say \"hi\"\n\t\\ #{no} Aé😀")]);
+    }
+
+    #[test]
+    fn should_read_interpolation() {
+        let string = r#""v#{@version}-#{%{a: 1}[:a]}!""#;
+        let mut lex = Lexer::new(string);
+        let token = lex.next().unwrap();
+        assert!(lex.is_done());
+        assert_eq!(token.lexeme(), string.to_string());
+
+        let fragments = token.fragments();
+        assert_eq!(fragments.len(), 5);
+        assert_eq!(fragments[0], literal("v"));
+        assert_eq!(fragments[2], literal("-"));
+        assert_eq!(fragments[4], literal("!"));
+
+        match &fragments[3] {
+            Fragment::Interpolation(tokens) => {
+                let lexemes = tokens.iter().map(|t| t.lexeme()).collect::<String>();
+                assert_eq!(lexemes, "%{a: 1}[:a]");
+            }
+            literal => panic!("expected an interpolation, found {:?}", literal),
+        }
+    }
+
+    #[test]
+    fn should_read_nested_interpolation() {
+        let string = r#""a#{"b#{c}"}""#;
+        let token = Lexer::new(string).next().unwrap();
+
+        match &token.fragments()[1] {
+            Fragment::Interpolation(tokens) => {
+                assert_eq!(tokens.len(), 1);
+                assert!(tokens[0].kind().is_string());
+                assert_eq!(tokens[0].lexeme(), r#""b#{c}""#);
+                assert_eq!(tokens[0].span().start().column(), 5);
+            }
+            literal => panic!("expected an interpolation, found {:?}", literal),
+        }
+    }
+
+    #[test]
+    fn should_read_sigils() {
+        let sigils = [
+            r#"~r/guides\/local\/.*/"#,
+            "~r/^foo$/iu",
+            "~w(phoenix ecto)a",
+            "~D[2022-06-01]",
+            "~s{a {b}",
+            "~c<abc>",
+            "~S|raw #{x}|",
+            r#"~s"quoted""#,
+            "~r'single'",
+            "~HTML[<p></p>]",
+        ];
+
+        for sigil in sigils {
+            let mut lex = Lexer::new(sigil);
+            let token = lex.next().unwrap();
+
+            assert!(token.kind().is_sigil(), "{}", sigil);
+            assert_eq!(token.lexeme(), sigil.to_string());
+            assert!(lex.is_done(), "{}", sigil);
+        }
+    }
+
+    #[test]
+    fn should_read_sigil_contents() {
+        let token = Lexer::new(r#"~r/guides\/local\/.*/"#).next().unwrap();
+        assert_eq!(token.fragments(), &[literal("guides/local/.*")]);
+
+        let token = Lexer::new(r#"~r/\d+\n/"#).next().unwrap();
+        assert_eq!(token.fragments(), &[literal(r#"\d+\n"#)]);
+
+        let token = Lexer::new(r#"~s(a\tb)"#).next().unwrap();
+        assert_eq!(token.fragments(), &[literal("a\tb")]);
 
-        let single = lex.next().unwrap();
-        assert!(single.kind().is_quote());
-        assert_eq!(single.lexeme(), "'");
+        let token = Lexer::new("~S|raw #{x}|").next().unwrap();
+        assert_eq!(token.fragments(), &[literal("raw #{x}")]);
 
-        let double = lex.next().unwrap();
-        assert!(double.kind().is_quote());
-        assert_eq!(double.lexeme(), "\"");
+        let token = Lexer::new("~s|v#{x}|").next().unwrap();
+        assert!(matches!(token.fragments()[1], Fragment::Interpolation(_)));
+    }
+
+    #[test]
+    fn should_read_sigil_heredoc() {
+        let heredoc = "~S\"\"\"\n  a\\b\n  \"\"\"";
+        let token = Lexer::new(heredoc).next().unwrap();
+
+        assert!(token.kind().is_sigil());
+        assert_eq!(token.lexeme(), heredoc.to_string());
+        assert_eq!(token.fragments(), &[literal("a\\b\n")]);
+    }
+
+    #[test]
+    fn should_read_tilde_operators() {
+        let mut lex = Lexer::new("~> <~");
+
+        assert!(lex.next().unwrap().kind().is_operator());
+        lex.next();
+        assert!(lex.next().unwrap().kind().is_operator());
+    }
+
+    #[test]
+    fn should_read_captured_atom() {
+        let lexemes = Lexer::new("&:erlang.now/0")
+            .map(|t| t.lexeme())
+            .collect::<Vec<String>>();
+
+        assert_eq!(lexemes, ["&", ":erlang.now", "/", "0"]);
+    }
+
+    #[test]
+    fn should_read_empty_bitstring() {
+        let lexemes = Lexer::new("<<>> <<<")
+            .map(|t| t.lexeme())
+            .collect::<Vec<String>>();
+
+        assert_eq!(lexemes, ["<<", ">>", " ", "<<<"]);
+    }
+
+    #[test]
+    fn should_read_unterminated_string_as_error() {
+        let mut lex = Lexer::new(":ok \"abc\n:next");
+
+        lex.next();
+        lex.next();
+
+        let error = lex.next().unwrap();
+        assert!(error.kind().is_error());
+        assert_eq!(error.lexeme(), "\"abc");
+
+        assert!(lex.next().unwrap().kind().is_newline());
+        assert!(lex.next().unwrap().kind().is_atom());
+        assert!(lex.is_done());
+
+        let diagnostic = &lex.diagnostics()[0];
+        assert_eq!(
+            diagnostic.to_string(),
+            "unexpected `\"abc` at 1:5, expected closing `\"`"
+        );
+    }
+
+    #[test]
+    fn should_read_unterminated_sigil_as_error() {
+        let mut lex = Lexer::new("~r/abc");
+
+        let error = lex.next().unwrap();
+        assert!(error.kind().is_error());
+        assert_eq!(error.lexeme(), "~r/abc");
+        assert_eq!(
+            lex.diagnostics()[0].expected(),
+            &["closing `/`".to_string()]
+        );
+    }
+
+    #[test]
+    fn should_recover_from_unknown_chars() {
+        let source = "a `b` c";
+        let tokens = Lexer::new(source).collect::<Vec<Token>>();
+
+        let lexemes = tokens.iter().map(|t| t.lexeme()).collect::<String>();
+        assert_eq!(lexemes, source);
+
+        let errors = tokens
+            .iter()
+            .filter(|t| t.kind().is_error())
+            .map(|t| t.lexeme())
+            .collect::<Vec<String>>();
+        assert_eq!(errors, vec!["`", "`"]);
+    }
+
+    #[test]
+    fn should_report_unknown_chars() {
+        let mut lex = Lexer::new("foo\n  `bar");
+        lex.by_ref().for_each(drop);
+
+        let diagnostics = lex.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].found(), "`");
+        assert_eq!(diagnostics[0].span().start().line(), 2);
+        assert_eq!(diagnostics[0].span().start().column(), 3);
+        assert!(diagnostics[0]
+            .to_string()
+            .starts_with("unexpected ``` at 2:3, expected one of atom, char"));
+    }
+
+    #[test]
+    fn should_read_trailing_colon() {
+        let token = Lexer::new(":").next().unwrap();
+        assert!(token.kind().is_operator());
+    }
+
+    #[test]
+    fn should_read_heredoc() {
+        let heredoc = "\"\"\"\n    Hello\n      \"world\"\n    \"\"\"";
+        let mut lex = Lexer::new(&format!("{}\n:ok", heredoc));
+
+        let token = lex.next().unwrap();
+        assert!(token.kind().is_string());
+        assert_eq!(token.lexeme(), heredoc.to_string());
+        assert_eq!(token.fragments(), &[literal("Hello\n  \"world\"\n")]);
+
+        assert!(lex.next().unwrap().kind().is_newline());
+    }
+
+    #[test]
+    fn should_read_charlist_heredoc() {
+        let heredoc = "\'\'\'\n  #{name}:\n    ok\n  \'\'\'";
+        let token = Lexer::new(heredoc).next().unwrap();
+        assert!(token.kind().is_charlist());
+        assert_eq!(token.lexeme(), heredoc.to_string());
+
+        let fragments = token.fragments();
+        assert!(matches!(fragments[0], Fragment::Interpolation(_)));
+        assert_eq!(fragments[1], literal(":\n  ok\n"));
     }
 
     #[test]
@@ -568,11 +1271,37 @@ mod lexer {
         assert_eq!(token.lexeme(), newline.to_string());
     }
 
-    use std::fs::File;
-    use std::io::Write;
+    #[test]
+    fn should_read_tab_as_whitespace() {
+        let mut lex = Lexer::new("\t \n");
+
+        let ws = lex.next().unwrap();
+        assert!(ws.kind().is_whitespace());
+        assert_eq!(ws.lexeme(), "\t ");
+
+        assert!(lex.next().unwrap().kind().is_newline());
+    }
+
+    #[test]
+    fn should_span_tokens() {
+        let source = "def foo,\n  do: :bar";
+        let tokens = Lexer::new(source).collect::<Vec<Token>>();
+
+        let def = tokens[0].span();
+        assert_eq!(def.start(), Position::new(0, 1, 1));
+        assert_eq!(def.end(), Position::new(3, 1, 4));
+
+        let newline = tokens[4].span();
+        assert_eq!(newline.start(), Position::new(8, 1, 9));
+        assert_eq!(newline.end(), Position::new(9, 2, 1));
+
+        let bar = tokens.last().unwrap().span();
+        assert_eq!(bar.start(), Position::new(15, 2, 7));
+        assert_eq!(&source[bar.range()], ":bar");
+    }
+
     use std::path::Path;
 
-    // for manual token checking
     #[test]
     fn read_mix_exs() {
         let root = Path::new(env!("CARGO_MANIFEST_DIR"));
@@ -580,18 +1309,11 @@ mod lexer {
         let c = std::fs::read_to_string(p).unwrap();
 
         let mut lex = Lexer::new(&c);
-        let mut f = File::create("tokens.txt").unwrap();
-
-        loop {
-            if lex.cursor > 0 && lex.is_done() {
-                break;
-            }
-
-            let t = lex.next();
-            println!("{:?}", t);
-            writeln!(&mut f, "{:?}", t.unwrap());
-        }
+        let tokens = lex.by_ref().collect::<Vec<Token>>();
 
-        assert!(true);
+        assert!(lex.is_done());
+        assert!(lex.diagnostics().is_empty(), "{:?}", lex.diagnostics());
+        assert!(!tokens.iter().any(|t| t.kind().is_error()));
+        assert_eq!(tokens.iter().map(|t| t.lexeme()).collect::<String>(), c);
     }
 }
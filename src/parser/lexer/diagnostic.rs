@@ -0,0 +1,45 @@
+use super::token::{Span, Token};
+use std::fmt;
+
+// what the lexer could not make sense of,
+// it goes on right after it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    span: Span,
+    found: String,
+    expected: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(token: &Token, expected: Vec<String>) -> Self {
+        Diagnostic {
+            span: token.span(),
+            found: token.lexeme(),
+            expected,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn found(&self) -> &str {
+        &self.found
+    }
+
+    pub fn expected(&self) -> &[String] {
+        &self.expected
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unexpected `{}` at {}", self.found, self.span.start())?;
+
+        match self.expected.as_slice() {
+            [] => Ok(()),
+            [expected] => write!(f, ", expected {}", expected),
+            expected => write!(f, ", expected one of {}", expected.join(", ")),
+        }
+    }
+}
@@ -1,7 +1,33 @@
+use std::fmt;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Token {
     kind: TokenKind,
     lexeme: String,
+    span: Span,
+    fragments: Vec<Fragment>, // strings, charlists and sigils only
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fragment {
+    Literal(String),           // escapes already applied
+    Interpolation(Vec<Token>), // between `#{` and `}`
+}
+
+// `line` and `column` start at 1, `column`
+// counts chars while `offset` counts bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+// `end` is right after the last char
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    start: Position,
+    end: Position,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -9,20 +35,28 @@ pub enum TokenKind {
     Atom,
     Boolean, // bools are atoms although
     Char,    // codepoint ?a
+    Charlist,
     Comment,
     Comma,
     Delimiter, // (), {}, []
+    Error,     // see `Lexer::diagnostics`
     Identifier,
     Newline,
     Number, // int, float, bin, oct, hex
     Operator,
-    Quote,
+    Sigil,
+    String, // heredocs included
     WhiteSpace,
 }
 
 impl Token {
     pub fn new(kind: TokenKind, lexeme: String) -> Self {
-        Token { kind, lexeme }
+        Token {
+            kind,
+            lexeme,
+            span: Span::default(),
+            fragments: vec![],
+        }
     }
 
     pub fn kind(&self) -> TokenKind {
@@ -30,7 +64,96 @@ impl Token {
     }
 
     pub fn lexeme(&self) -> String {
-        (&self.lexeme).to_string()
+        self.lexeme.to_string()
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn set_span(&mut self, span: Span) -> &mut Token {
+        self.span = span;
+
+        self
+    }
+
+    pub fn fragments(&self) -> &[Fragment] {
+        &self.fragments
+    }
+
+    pub fn set_fragments(&mut self, fragments: Vec<Fragment>) -> &mut Token {
+        self.fragments = fragments;
+
+        self
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl Position {
+    pub fn new(offset: usize, line: usize, column: usize) -> Self {
+        Position {
+            offset,
+            line,
+            column,
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    // moves past `ch`
+    pub fn advance(&mut self, ch: char) {
+        self.offset += ch.len_utf8();
+
+        if ch.eq(&'\n') {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Span { start, end }
+    }
+
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    pub fn end(&self) -> Position {
+        self.end
+    }
+
+    // byte range into the source
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.start.offset..self.end.offset
     }
 }
 
@@ -47,6 +170,10 @@ impl TokenKind {
         matches!(self, TokenKind::Char)
     }
 
+    pub fn is_charlist(&self) -> bool {
+        matches!(self, TokenKind::Charlist)
+    }
+
     pub fn is_comment(&self) -> bool {
         matches!(self, TokenKind::Comment)
     }
@@ -59,6 +186,10 @@ impl TokenKind {
         matches!(self, TokenKind::Delimiter)
     }
 
+    pub fn is_error(&self) -> bool {
+        matches!(self, TokenKind::Error)
+    }
+
     pub fn is_identifier(&self) -> bool {
         matches!(self, TokenKind::Identifier)
     }
@@ -75,8 +206,12 @@ impl TokenKind {
         matches!(self, TokenKind::Operator)
     }
 
-    pub fn is_quote(&self) -> bool {
-        matches!(self, TokenKind::Quote)
+    pub fn is_sigil(&self) -> bool {
+        matches!(self, TokenKind::Sigil)
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self, TokenKind::String)
     }
 
     pub fn is_whitespace(&self) -> bool {
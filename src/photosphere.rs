@@ -1,4 +1,9 @@
+pub mod deps;
+pub mod manifest;
+pub mod plan;
 pub mod service;
 pub mod setup;
+pub mod staging;
 pub mod str_utils;
+pub mod template;
 pub mod validations;
@@ -0,0 +1,175 @@
+use super::service::{
+    de,
+    dep::{Dep, Env},
+    graph::DepGraph,
+    lock::Lock,
+    outdated,
+    registry::Registry,
+    resolver, ser,
+    version::VersionRequirement,
+};
+use crate::{
+    DepsAddArgs, DepsCheckArgs, DepsOutdatedArgs, DepsRemoveArgs, DepsTreeArgs, DepsWhyArgs,
+};
+use anyhow::{anyhow, bail, Result};
+use std::path::{Path, PathBuf};
+
+const ANY_VERSION: &str = ">= 0.0.0";
+
+pub fn print_tree(args: &DepsTreeArgs) -> Result<()> {
+    let graph = DepGraph::load(Path::new(&args.path))?;
+
+    println!("{}", graph.render_tree(args.name.as_deref(), args.format)?);
+
+    Ok(())
+}
+
+pub fn print_why(args: &DepsWhyArgs) -> Result<()> {
+    let graph = DepGraph::load(Path::new(&args.path))?;
+
+    println!("{}", graph.render_why(&args.name, args.format)?);
+
+    Ok(())
+}
+
+pub fn check(args: &DepsCheckArgs) -> Result<()> {
+    let root = Path::new(&args.path);
+    let deps = de::parse_deps(root)?;
+    let registry = Registry::load(&registry_dir(args.registry.as_deref())?)?;
+
+    let problems = resolver::check(&deps, &registry);
+
+    if problems.is_empty() {
+        println!("All {} dependencies resolve", deps.len());
+
+        return Ok(());
+    }
+
+    for problem in problems.iter() {
+        println!("{}", problem);
+    }
+
+    bail!(
+        "{} problem(s) found in {}",
+        problems.len(),
+        root.join("mix.exs").display()
+    )
+}
+
+pub fn print_outdated(args: &DepsOutdatedArgs) -> Result<()> {
+    let root = Path::new(&args.path);
+    let deps = de::parse_deps(root)?;
+    let registry = Registry::load(&registry_dir(args.registry.as_deref())?)?;
+    let lock = match root.join("mix.lock").exists() {
+        true => Some(Lock::load(root)?),
+        false => None,
+    };
+
+    let outdated = outdated::outdated(&deps, &registry, lock.as_ref());
+
+    if outdated.is_empty() {
+        println!("All dependencies are up to date");
+
+        return Ok(());
+    }
+
+    let mut rows = vec![[
+        "Dependency".to_string(),
+        "Current".to_string(),
+        "Latest".to_string(),
+        "Requirement".to_string(),
+        "Drift".to_string(),
+        "Allowed".to_string(),
+    ]];
+    for o in outdated.iter() {
+        rows.push([
+            o.name().to_string(),
+            o.current().to_string(),
+            o.latest().to_string(),
+            o.requirement().to_string(),
+            o.drift().to_string(),
+            if o.allowed() { "yes" } else { "no" }.to_string(),
+        ]);
+    }
+
+    let widths = (0..6)
+        .map(|i| rows.iter().map(|r| r[i].len()).max().unwrap_or_default())
+        .collect::<Vec<usize>>();
+    for row in rows.iter() {
+        let cells = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<String>>();
+
+        println!("{}", cells.join("  ").trim_end());
+    }
+
+    if args.update {
+        let updated = outdated::update_requirements(root, &outdated)?;
+        let skipped = outdated
+            .iter()
+            .filter(|o| !o.allowed() && !updated.iter().any(|u| u.name() == o.name()))
+            .map(|o| format!("{} ({})", o.name(), o.requirement()))
+            .collect::<Vec<String>>();
+
+        println!("\nUpdated {} requirement(s) in mix.exs", updated.len());
+
+        if !skipped.is_empty() {
+            println!("Left to update by hand: {}", skipped.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+// a hex dep given no requirement takes any release
+pub fn add(args: &DepsAddArgs) -> Result<()> {
+    let version = match (&args.version, args.git.is_some() || args.github.is_some()) {
+        (Some(version), _) => Some(VersionRequirement::parse(version)?),
+        (None, false) => Some(VersionRequirement::parse(ANY_VERSION)?),
+        (None, true) => None,
+    };
+    let envs = args
+        .only
+        .iter()
+        .map(|env| Env::from_atom(env))
+        .collect::<Vec<Env>>();
+
+    let dep = Dep::new()
+        .set_name(args.name.to_string())
+        .set_version(version)
+        .set_git(args.git.clone())
+        .set_github(args.github.clone())
+        .set_tag(args.tag.clone())
+        .set_branch(args.branch.clone())
+        .set_git_ref(args.git_ref.clone())
+        .set_envs(Some(envs).filter(|envs| !envs.is_empty()))
+        .set_runtime(args.runtime)
+        .set_conflict(Some(true).filter(|_| args.conflict))
+        .clone();
+
+    ser::add_dep(Path::new(&args.path), &dep)?;
+
+    println!("Added {} to mix.exs", dep);
+
+    Ok(())
+}
+
+pub fn remove(args: &DepsRemoveArgs) -> Result<()> {
+    let dep = ser::remove_dep(Path::new(&args.path), &args.name)?;
+
+    println!("Removed {} from mix.exs", dep);
+
+    Ok(())
+}
+
+// `--registry` or the snapshot in the home directory
+pub fn registry_dir(registry: Option<&str>) -> Result<PathBuf> {
+    match registry {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => std::env::var("HOME")
+            .map(|home| Path::new(&home).join(".photosphere").join("registry"))
+            .map_err(|_| anyhow!("no $HOME, pass the registry with --registry")),
+    }
+}
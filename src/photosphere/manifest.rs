@@ -0,0 +1,285 @@
+use super::str_utils;
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+// the built-in manifest, for templates without one
+const MANIFEST: &str = include_str!("../../priv/photosphere.toml");
+
+pub const MANIFEST_FILE: &str = "photosphere.toml";
+
+// What the template declares about itself, so it can
+// change its names and layout without a new photosphere
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct Manifest {
+    #[serde(default)]
+    pub(super) exclude: Vec<String>,
+    #[serde(default)]
+    pub(super) instructions: Vec<String>,
+    pub(super) placeholders: Placeholders,
+    #[serde(default, rename = "ref")]
+    pub(super) git_ref: Option<String>, // `ref`
+    #[serde(default)]
+    pub(super) rename: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct Placeholders {
+    pub(super) kebab_case: String,
+    pub(super) pascal_case: String,
+    pub(super) snake_case: String,
+    pub(super) title: String,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest::parse(MANIFEST).expect("built-in manifest")
+    }
+}
+
+impl Manifest {
+    // the template manifest, the built-in one if there is none
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(MANIFEST_FILE);
+
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+
+        Manifest::parse(&fs::read_to_string(&path)?)
+            .map_err(|e| anyhow!("invalid manifest {}: {}", path.display(), e))
+    }
+
+    pub fn parse(data: &str) -> Result<Self> {
+        let manifest: Manifest = toml::from_str(data)?;
+
+        let placeholders = &manifest.placeholders;
+        if [
+            &placeholders.kebab_case,
+            &placeholders.pascal_case,
+            &placeholders.snake_case,
+            &placeholders.title,
+        ]
+        .iter()
+        .any(|p| p.is_empty())
+        {
+            bail!("placeholders cannot be empty");
+        }
+
+        if let Some(path) = manifest.rename.iter().find(|p| Path::new(p).is_absolute()) {
+            bail!("`{}` is not relative to the template root", path);
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn git_ref(&self) -> Option<&str> {
+        self.git_ref.as_deref()
+    }
+
+    pub fn placeholders(&self) -> &Placeholders {
+        &self.placeholders
+    }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy().replace('\\', "/");
+
+        self.exclude.iter().any(|pattern| glob(pattern, &path))
+    }
+
+    // deepest first, so a directory is renamed after what's
+    // in it; only the last segment changes, the directories
+    // above are still to be renamed
+    pub fn renames(&self, name: &str) -> Vec<(String, String)> {
+        let mut renames = self
+            .rename
+            .iter()
+            .map(|from| {
+                let to = match from.rsplit_once('/') {
+                    Some((parent, last)) => {
+                        format!("{}/{}", parent, self.placeholders.replace(last, name))
+                    }
+                    None => self.placeholders.replace(from, name),
+                };
+
+                (from.to_string(), to)
+            })
+            .collect::<Vec<(String, String)>>();
+        renames.sort_by_key(|(from, _)| std::cmp::Reverse(from.matches('/').count()));
+
+        renames
+    }
+
+    pub fn instructions(&self, name: &str, path: &Path) -> Vec<String> {
+        self.instructions
+            .iter()
+            .map(|i| {
+                i.replace("{name}", name)
+                    .replace("{path}", &path.display().to_string())
+            })
+            .collect()
+    }
+}
+
+impl Placeholders {
+    pub fn snake_case(&self) -> &str {
+        &self.snake_case
+    }
+
+    // `name` is already snake_case
+    pub fn replace(&self, text: &str, name: &str) -> String {
+        self.forms(name)
+            .iter()
+            .fold(text.to_string(), |text, (from, to)| text.replace(from, to))
+    }
+
+    // each placeholder found in `text`, what it
+    // becomes and how many times it is there
+    pub fn substitutions(&self, text: &str, name: &str) -> Vec<(String, String, usize)> {
+        self.forms(name)
+            .into_iter()
+            .map(|(from, to)| {
+                let count = text.matches(from.as_str()).count();
+
+                (from, to, count)
+            })
+            .filter(|(_, _, count)| *count > 0)
+            .collect()
+    }
+
+    // in the order they are replaced
+    fn forms(&self, name: &str) -> [(String, String); 4] {
+        [
+            (self.snake_case.clone(), name.to_string()),
+            (self.title.clone(), str_utils::to_title(name)),
+            (self.kebab_case.clone(), str_utils::to_kebab_case(name)),
+            (self.pascal_case.clone(), str_utils::to_pascal_case(name)),
+        ]
+    }
+}
+
+// `*` within a path segment, `**` across them
+fn glob(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.split('/').collect::<Vec<&str>>();
+    let path = path.split('/').collect::<Vec<&str>>();
+
+    glob_segments(&pattern, &path)
+}
+
+fn glob_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.first(), path.first()) {
+        (None, None) => true,
+        (Some(&"**"), _) => {
+            glob_segments(&pattern[1..], path)
+                || (!path.is_empty() && glob_segments(pattern, &path[1..]))
+        }
+        (Some(p), Some(s)) => glob_segment(p, s) && glob_segments(&pattern[1..], &path[1..]),
+        _ => false,
+    }
+}
+
+fn glob_segment(pattern: &str, segment: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == segment,
+        Some((prefix, rest)) => {
+            let segment = match segment.strip_prefix(prefix) {
+                Some(segment) => segment,
+                None => return false,
+            };
+
+            (0..=segment.len())
+                .filter(|i| segment.is_char_boundary(*i))
+                .any(|i| glob_segment(rest, &segment[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+ref = "main"
+instructions = ["cd {path}", "mix setup"]
+rename = ["lib/acme_template", "lib/acme_template/acme_template.ex"]
+exclude = ["assets/**", "**/*.png"]
+
+[placeholders]
+snake_case = "acme_template"
+pascal_case = "AcmeTemplate"
+kebab_case = "acme-template"
+title = "Acme Template"
+"#;
+
+    #[test]
+    fn builtin_manifest() {
+        let manifest = Manifest::default();
+
+        assert_eq!(manifest.git_ref(), Some("photosphere-test"));
+        assert_eq!(manifest.placeholders().snake_case(), "service_template");
+        assert_eq!(
+            manifest.placeholders().replace(
+                "defmodule ServiceTemplate.Repo, otp_app: :service_template",
+                "foo_bar"
+            ),
+            "defmodule FooBar.Repo, otp_app: :foo_bar"
+        );
+        assert_eq!(
+            manifest.placeholders().substitutions(
+                "ServiceTemplate.Repo, otp_app: :service_template, app: :service_template",
+                "foo_bar"
+            ),
+            [
+                ("service_template".to_string(), "foo_bar".to_string(), 2),
+                ("ServiceTemplate".to_string(), "FooBar".to_string(), 1)
+            ]
+        );
+        assert!(manifest.renames("foo_bar").contains(&(
+            "lib/service_template_web".to_string(),
+            "lib/foo_bar_web".to_string()
+        )));
+    }
+
+    #[test]
+    fn load_template_manifest() {
+        let root = std::env::temp_dir().join("photosphere_load_template_manifest");
+        fs::create_dir_all(&root).unwrap();
+
+        assert_eq!(Manifest::load(&root).unwrap(), Manifest::default());
+
+        fs::write(root.join(MANIFEST_FILE), MANIFEST).unwrap();
+        let manifest = Manifest::load(&root).unwrap();
+
+        assert_eq!(manifest.git_ref(), Some("main"));
+        assert_eq!(
+            manifest.instructions("foo", Path::new("./foo")),
+            ["cd ./foo", "mix setup"]
+        );
+        assert_eq!(
+            manifest.renames("foo"),
+            [
+                (
+                    "lib/acme_template/acme_template.ex".to_string(),
+                    "lib/acme_template/foo.ex".to_string()
+                ),
+                ("lib/acme_template".to_string(), "lib/foo".to_string())
+            ]
+        );
+
+        fs::write(root.join(MANIFEST_FILE), "ref = \"main\"").unwrap();
+        assert!(Manifest::load(&root).is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn exclude_paths() {
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+
+        assert!(manifest.is_excluded(Path::new("assets/css/app.css")));
+        assert!(manifest.is_excluded(Path::new("priv/static/images/logo.png")));
+        assert!(manifest.is_excluded(Path::new("logo.png")));
+        assert!(!manifest.is_excluded(Path::new("lib/acme_template.ex")));
+        assert!(!manifest.is_excluded(Path::new("lib/assets/app.css")));
+    }
+}
@@ -0,0 +1,544 @@
+use super::manifest::Manifest;
+use crate::PlanFormat;
+use anyhow::Result;
+use serde_json::{json, Value};
+use similar::{ChangeTag, TextDiff};
+use std::{collections::BTreeMap, fs, path::Path};
+use walkdir::WalkDir;
+
+// file contents by their path relative to the root
+type Snapshot = BTreeMap<String, Vec<u8>>;
+
+// What `service new` does to the template, step by
+// step, noted while it runs on the staging dir
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub(super) dropped_deps: Vec<(String, String)>, // dep and the feature it goes with
+    pub(super) recording: bool,
+    pub(super) steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub(super) changes: Vec<Change>,
+    pub(super) name: String,
+    pub(super) patches: Vec<String>, // unified diffs of the files it touched
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    Created(String),
+    Deleted(String),
+    Modified {
+        path: String,
+        removed: Vec<String>,
+        added: Vec<String>,
+    },
+    Renamed {
+        from: String,
+        to: String,
+    },
+    Substituted {
+        path: String,
+        from: String,
+        to: String,
+        count: usize,
+    },
+}
+
+impl Plan {
+    // one that doesn't record anything just runs the steps
+    pub fn new(recording: bool) -> Self {
+        Plan {
+            recording,
+            ..Plan::default()
+        }
+    }
+
+    pub fn dropped_deps(&self) -> &[(String, String)] {
+        &self.dropped_deps
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    pub fn set_dropped_deps(&mut self, dropped_deps: Vec<(String, String)>) -> &mut Plan {
+        self.dropped_deps = dropped_deps;
+
+        self
+    }
+
+    // runs `f` as the step `name`, noting what it
+    // created, deleted and modified under `root`
+    pub fn record<F>(&mut self, name: &str, root: &Path, f: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        if !self.recording {
+            return f();
+        }
+
+        let before = snapshot(root)?;
+        f()?;
+        let after = snapshot(root)?;
+
+        let changes = diff(&before, &after, |path| path.to_string());
+        let patches = patches(&before, &after, |path| path.to_string());
+        self.push(name, changes, patches);
+
+        Ok(())
+    }
+
+    // `rename_source`, its renames and placeholder
+    // substitutions rather than every line it touched
+    pub fn record_rename<F>(
+        &mut self,
+        manifest: &Manifest,
+        service: &str,
+        root: &Path,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        if !self.recording {
+            return f();
+        }
+
+        let renames = manifest.renames(service);
+        let before = snapshot(root)?;
+        f()?;
+        let after = snapshot(root)?;
+
+        let mut changes = renames
+            .iter()
+            .filter(|(from, _)| before.keys().any(|path| is_within(path, from)))
+            .map(|(from, to)| Change::Renamed {
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+            .collect::<Vec<Change>>();
+
+        for (path, data) in before.iter() {
+            if manifest.is_excluded(Path::new(path)) {
+                continue;
+            }
+
+            let text = String::from_utf8_lossy(data);
+            let substitutions = manifest.placeholders().substitutions(&text, service);
+
+            changes.extend(substitutions.into_iter().map(|(from, to, count)| {
+                Change::Substituted {
+                    path: renamed_path(&renames, path),
+                    from,
+                    to,
+                    count,
+                }
+            }));
+        }
+
+        // anything else, substitutions aside
+        let moved = diff(&before, &after, |path| renamed_path(&renames, path));
+        changes.extend(
+            moved
+                .into_iter()
+                .filter(|c| !matches!(c, Change::Modified { .. })),
+        );
+
+        let patches = patches(&before, &after, |path| renamed_path(&renames, path));
+        self.push("rename", changes, patches);
+
+        Ok(())
+    }
+
+    pub fn render(&self, format: PlanFormat) -> Result<String> {
+        let output = match format {
+            PlanFormat::Text => self.text(),
+            PlanFormat::Json => serde_json::to_string_pretty(&self.json())?,
+        };
+
+        Ok(output)
+    }
+
+    // every step's patches under its name, in the order they ran
+    pub fn unified_diff(&self) -> String {
+        self.steps
+            .iter()
+            .filter(|s| !s.patches.is_empty())
+            .map(|s| format!("# {}\n{}", s.name, s.patches.concat()))
+            .collect()
+    }
+
+    fn push(&mut self, name: &str, changes: Vec<Change>, patches: Vec<String>) {
+        self.steps.push(Step {
+            changes,
+            name: name.to_string(),
+            patches,
+        });
+    }
+
+    fn text(&self) -> String {
+        let mut lines = vec![];
+
+        if !self.dropped_deps.is_empty() {
+            lines.push("deps dropped".to_string());
+            lines.extend(
+                self.dropped_deps
+                    .iter()
+                    .map(|(dep, feature)| format!("  {} ({})", dep, feature)),
+            );
+        }
+
+        for step in self.steps.iter().filter(|s| !s.changes.is_empty()) {
+            lines.push(step.name.to_string());
+
+            for change in step.changes.iter() {
+                match change {
+                    Change::Created(path) => lines.push(format!("  create {}", path)),
+                    Change::Deleted(path) => lines.push(format!("  delete {}", path)),
+                    Change::Renamed { from, to } => {
+                        lines.push(format!("  rename {} -> {}", from, to))
+                    }
+                    Change::Substituted {
+                        path,
+                        from,
+                        to,
+                        count,
+                    } => lines.push(format!(
+                        "  replace {} -> {} in {} ({})",
+                        from, to, path, count
+                    )),
+                    Change::Modified {
+                        path,
+                        removed,
+                        added,
+                    } => {
+                        lines.push(format!("  modify {}", path));
+                        lines.extend(removed.iter().map(|l| format!("    - {}", l)));
+                        lines.extend(added.iter().map(|l| format!("    + {}", l)));
+                    }
+                }
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    fn json(&self) -> Value {
+        let dropped_deps = self
+            .dropped_deps
+            .iter()
+            .map(|(dep, feature)| json!({ "name": dep, "feature": feature }))
+            .collect::<Vec<Value>>();
+
+        let steps = self
+            .steps
+            .iter()
+            .map(|step| {
+                let changes = step
+                    .changes
+                    .iter()
+                    .map(|change| match change {
+                        Change::Created(path) => json!({ "create": path }),
+                        Change::Deleted(path) => json!({ "delete": path }),
+                        Change::Renamed { from, to } => json!({ "rename": from, "to": to }),
+                        Change::Substituted {
+                            path,
+                            from,
+                            to,
+                            count,
+                        } => json!({ "replace": from, "with": to, "in": path, "count": count }),
+                        Change::Modified {
+                            path,
+                            removed,
+                            added,
+                        } => json!({ "modify": path, "removed": removed, "added": added }),
+                    })
+                    .collect::<Vec<Value>>();
+
+                json!({ "name": step.name, "changes": changes })
+            })
+            .collect::<Vec<Value>>();
+
+        json!({ "dropped_deps": dropped_deps, "steps": steps })
+    }
+}
+
+// every file under `root` but the git history
+fn snapshot(root: &Path) -> Result<Snapshot> {
+    let mut files = Snapshot::new();
+
+    let entries = WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file());
+
+    for entry in entries {
+        let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        let path = relative.to_string_lossy().replace('\\', "/");
+
+        files.insert(path, fs::read(entry.path())?);
+    }
+
+    Ok(files)
+}
+
+// `moved` says where a file from `before` is expected in `after`
+fn diff<M>(before: &Snapshot, after: &Snapshot, moved: M) -> Vec<Change>
+where
+    M: Fn(&str) -> String,
+{
+    let mut changes = vec![];
+    let mut kept = vec![];
+
+    for (path, data) in before.iter() {
+        let new_path = moved(path);
+
+        match after.get(&new_path) {
+            None => changes.push(Change::Deleted(path.to_string())),
+            Some(new_data) if new_data != data => {
+                let (removed, added) = changed_lines(data, new_data);
+
+                changes.push(Change::Modified {
+                    path: new_path.to_string(),
+                    removed,
+                    added,
+                });
+                kept.push(new_path);
+            }
+            Some(_) => kept.push(new_path),
+        }
+    }
+
+    changes.extend(
+        after
+            .keys()
+            .filter(|path| !kept.contains(path))
+            .map(|path| Change::Created(path.to_string())),
+    );
+
+    changes
+}
+
+// git style, `/dev/null` for created and deleted files
+fn patches<M>(before: &Snapshot, after: &Snapshot, moved: M) -> Vec<String>
+where
+    M: Fn(&str) -> String,
+{
+    let mut patches = vec![];
+    let mut kept = vec![];
+
+    for (path, data) in before.iter() {
+        let new_path = moved(path);
+
+        match after.get(&new_path) {
+            Some(new_data) if new_data == data => kept.push(new_path),
+            Some(new_data) => {
+                patches.push(patch(Some((path, data)), Some((&new_path, new_data))));
+                kept.push(new_path);
+            }
+            None => patches.push(patch(Some((path, data)), None)),
+        }
+    }
+
+    for (path, data) in after.iter().filter(|(path, _)| !kept.contains(path)) {
+        patches.push(patch(None, Some((path, data))));
+    }
+
+    patches
+}
+
+// a file's path and contents before and after, `None` where it doesn't exist
+fn patch(old: Option<(&str, &[u8])>, new: Option<(&str, &[u8])>) -> String {
+    let name = |prefix: &str, file: Option<(&str, &[u8])>| match file {
+        Some((path, _)) => format!("{}/{}", prefix, path),
+        None => "/dev/null".to_string(),
+    };
+    let (a, b) = (name("a", old), name("b", new));
+
+    let (from, to) = (old.or(new).map(|f| f.0), new.or(old).map(|f| f.0));
+    let header = format!(
+        "diff --git a/{} b/{}\n",
+        from.unwrap_or_default(),
+        to.unwrap_or_default()
+    );
+
+    let old_data = old.map(|f| f.1).unwrap_or_default();
+    let new_data = new.map(|f| f.1).unwrap_or_default();
+
+    match (std::str::from_utf8(old_data), std::str::from_utf8(new_data)) {
+        // the file names even for an empty file, which has no hunks
+        (Ok(old), Ok(new)) => format!(
+            "{}--- {}\n+++ {}\n{}",
+            header,
+            a,
+            b,
+            TextDiff::from_lines(old, new)
+                .unified_diff()
+                .missing_newline_hint(false)
+        ),
+        _ => format!("{}Binary files {} and {} differ\n", header, a, b),
+    }
+}
+
+fn changed_lines(old: &[u8], new: &[u8]) -> (Vec<String>, Vec<String>) {
+    let (old, new) = (String::from_utf8_lossy(old), String::from_utf8_lossy(new));
+    let diff = TextDiff::from_lines(old.as_ref(), new.as_ref());
+
+    let lines = |tag: ChangeTag| {
+        diff.iter_all_changes()
+            .filter(|c| c.tag() == tag)
+            .map(|c| c.value().trim_end_matches('\n').to_string())
+            .collect::<Vec<String>>()
+    };
+
+    (lines(ChangeTag::Delete), lines(ChangeTag::Insert))
+}
+
+// deepest renames first, a file in a renamed
+// directory follows it
+fn renamed_path(renames: &[(String, String)], path: &str) -> String {
+    renames.iter().fold(path.to_string(), |path, (from, to)| {
+        match is_within(&path, from) {
+            true => format!("{}{}", to, &path[from.len()..]),
+            false => path,
+        }
+    })
+}
+
+// `path` is `dir` or a file in it
+fn is_within(path: &str, dir: &str) -> bool {
+    path.strip_prefix(dir)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_steps() {
+        let root = std::env::temp_dir().join("photosphere_record_steps");
+        fs::create_dir_all(root.join("config")).unwrap();
+        fs::write(root.join("config").join("config.exs"), "a\nb\nc\n").unwrap();
+        fs::write(root.join(".env-sample"), "PORT=4000\n").unwrap();
+
+        let mut plan = Plan::new(true);
+        plan.record("without foo", &root, || {
+            fs::write(root.join("config").join("config.exs"), "a\nc\n")?;
+            fs::remove_file(root.join(".env-sample"))?;
+            fs::write(root.join("health.ex"), "")?;
+
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            plan.steps()[0].changes,
+            [
+                Change::Deleted(".env-sample".to_string()),
+                Change::Modified {
+                    path: "config/config.exs".to_string(),
+                    removed: vec!["b".to_string()],
+                    added: vec![],
+                },
+                Change::Created("health.ex".to_string())
+            ]
+        );
+        assert_eq!(
+            plan.render(PlanFormat::Text).unwrap(),
+            "without foo\n  delete .env-sample\n  modify config/config.exs\n    - b\n  create health.ex"
+        );
+        assert_eq!(
+            plan.unified_diff(),
+            "# without foo\n\
+             diff --git a/.env-sample b/.env-sample\n\
+             --- a/.env-sample\n\
+             +++ /dev/null\n\
+             @@ -1 +0,0 @@\n\
+             -PORT=4000\n\
+             diff --git a/config/config.exs b/config/config.exs\n\
+             --- a/config/config.exs\n\
+             +++ b/config/config.exs\n\
+             @@ -1,3 +1,2 @@\n \
+             a\n\
+             -b\n \
+             c\n\
+             diff --git a/health.ex b/health.ex\n\
+             --- /dev/null\n\
+             +++ b/health.ex\n"
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn record_rename() {
+        let root = std::env::temp_dir().join("photosphere_record_rename");
+        let lib = root.join("lib");
+        fs::create_dir_all(lib.join("service_template")).unwrap();
+        fs::write(
+            lib.join("service_template").join("repo.ex"),
+            "defmodule ServiceTemplate.Repo do\nend\n",
+        )
+        .unwrap();
+
+        let manifest = Manifest::default();
+        let mut plan = Plan::new(true);
+        plan.record_rename(&manifest, "foo", &root, || {
+            fs::write(
+                lib.join("service_template").join("repo.ex"),
+                "defmodule Foo.Repo do\nend\n",
+            )?;
+            fs::rename(lib.join("service_template"), lib.join("foo"))?;
+
+            Ok(())
+        })
+        .unwrap();
+
+        let changes = &plan.steps()[0].changes;
+        assert!(changes.contains(&Change::Renamed {
+            from: "lib/service_template".to_string(),
+            to: "lib/foo".to_string(),
+        }));
+        assert!(changes.contains(&Change::Substituted {
+            path: "lib/foo/repo.ex".to_string(),
+            from: "ServiceTemplate".to_string(),
+            to: "Foo".to_string(),
+            count: 1,
+        }));
+        // nothing deleted nor created, only moved
+        assert_eq!(changes.len(), 2);
+
+        assert!(plan.unified_diff().contains(
+            "diff --git a/lib/service_template/repo.ex b/lib/foo/repo.ex\n\
+             --- a/lib/service_template/repo.ex\n\
+             +++ b/lib/foo/repo.ex\n"
+        ));
+
+        let json = plan.render(PlanFormat::Json).unwrap();
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["steps"][0]["name"], "rename");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn skipped_recording() {
+        let mut plan = Plan::new(false);
+        let mut ran = false;
+
+        plan.record("deps", Path::new("/nonexistent"), || {
+            ran = true;
+
+            Ok(())
+        })
+        .unwrap();
+
+        assert!(ran);
+        assert!(plan.steps().is_empty());
+    }
+}
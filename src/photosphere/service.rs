@@ -1,112 +1,111 @@
-use crate::{setup::SNAKE_CASE_DEFAULT, Protocol};
+use super::manifest::Manifest;
+use crate::Protocol;
+use anyhow::Result;
 use dep::Dep;
-use std::path::PathBuf;
+use feature::{Catalog, Feature};
+use rules::{Choice, Outcome, Reason};
+use std::{collections::BTreeMap, path::PathBuf};
 
 pub mod de;
 pub mod dep;
+pub mod feature;
+pub mod graph;
+pub mod lock;
+pub mod outdated;
+pub mod project;
+pub mod registry;
+pub mod resolver;
+pub mod rules;
 pub mod ser;
+pub mod version;
+
+// what serves the health endpoint of a gRPC-only service
+pub const HEALTH_SERVER: &str = "plug_cowboy";
 
 #[derive(Clone, Debug)]
 pub struct Service {
-    pub(super) auth: bool, // both authentication and authorization
-    pub(super) database: bool,
+    pub(super) catalog: Catalog,
     pub(super) deps: Vec<Dep>,
-    pub(super) graphql: bool,
-    pub(super) http_client: bool,
-    pub(super) mailer: bool,
-    pub(super) messaging: bool,
-    pub(super) monitoring: bool,
+    pub(super) choices: BTreeMap<String, Choice>, // by feature name
+    pub(super) health: bool,                      // HTTP health endpoint without REST
     pub(super) name: String,
     pub(super) path: PathBuf,
     pub(super) protocol: Protocol,
     pub(super) ssh: bool,
+    pub(super) taken_out: Vec<Dep>, // by disabled features
 }
 
-impl Service {
-    pub fn default() -> Self {
-        let default_path = PathBuf::from(&format!("./{}", SNAKE_CASE_DEFAULT));
+impl Default for Service {
+    fn default() -> Self {
+        let manifest = Manifest::default();
+        let name = manifest.placeholders().snake_case();
+        let default_path = PathBuf::from(&format!("./{}", name));
 
         Service {
-            auth: true,
-            database: true,
+            catalog: Catalog::default(),
             deps: vec![],
-            graphql: true,
-            http_client: true,
-            mailer: true,
-            monitoring: true,
-            messaging: true,
-            name: SNAKE_CASE_DEFAULT.to_string(),
+            choices: BTreeMap::new(),
+            health: false,
+            name: name.to_string(),
             path: default_path,
             protocol: Protocol::Rest,
             ssh: false,
+            taken_out: vec![],
         }
     }
+}
 
-    pub fn set_no_auth(&mut self, no_auth: bool) -> &mut Service {
-        if no_auth {
-            self.auth = false;
-            self.deps.retain(|d| !d.is_auth());
-
-            return self;
-        }
-
-        self
+impl Service {
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
     }
 
-    pub fn set_deps(&mut self, deps: Vec<Dep>) -> &mut Service {
-        self.deps = deps;
-
-        self
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.choices.get(name).is_none_or(|c| c.enabled())
     }
 
-    pub fn set_no_database(&mut self, no_database: bool) -> &mut Service {
-        if no_database {
-            self.database = false;
-            self.deps.retain(|d| !d.is_database());
-
-            return self;
-        }
-
-        self
+    // in catalog order, the order they are taken out
+    pub fn disabled_features(&self) -> Vec<&Feature> {
+        self.catalog
+            .features()
+            .iter()
+            .filter(|f| !self.has_feature(f.name()))
+            .collect()
     }
 
-    pub fn set_no_graphql(&mut self, no_graphql: bool) -> &mut Service {
-        if no_graphql {
-            self.graphql = false;
-            self.deps.retain(|d| !d.is_graphql());
+    // takes out what the catalog rules say has to go
+    // along with what was asked, or fails to
+    pub fn apply_rules(&mut self) -> Result<Outcome> {
+        let outcome = rules::evaluate(&self.catalog, &self.choices)?;
 
-            return self;
+        for decision in outcome.decisions().iter().filter(|d| !d.enabled()) {
+            self.set_feature(decision.name(), false, decision.reason().clone());
         }
 
-        self
+        Ok(outcome)
     }
 
-    pub fn set_no_http_client(&mut self, no_http_client: bool) -> &mut Service {
-        if no_http_client {
-            self.http_client = false;
-            self.deps.retain(|d| !d.is_http_client());
-
-            return self;
-        }
+    pub fn set_catalog(&mut self, catalog: Catalog) -> &mut Service {
+        self.catalog = catalog;
 
         self
     }
 
-    pub fn set_no_mailer(&mut self, no_mailer: bool) -> &mut Service {
-        if no_mailer {
-            self.mailer = false;
-            self.deps.retain(|d| !d.is_mailer());
-
-            return self;
-        }
+    pub fn set_deps(&mut self, deps: Vec<Dep>) -> &mut Service {
+        self.deps = deps;
+        self.taken_out.clear();
 
         self
     }
 
-    pub fn set_no_messaging(&mut self, no_messaging: bool) -> &mut Service {
-        if no_messaging {
-            self.messaging = false;
-            self.deps.retain(|d| !d.is_messaging());
+    pub fn set_no_feature(&mut self, name: &str, no_feature: bool) -> &mut Service {
+        if no_feature {
+            let flag = match self.catalog.get(name) {
+                Some(feature) => feature.cli_flag(),
+                None => format!("--without {}", name),
+            };
+
+            self.set_feature(name, false, Reason::Flag(flag));
 
             return self;
         }
@@ -114,12 +113,14 @@ impl Service {
         self
     }
 
-    pub fn set_no_monitoring(&mut self, no_monitoring: bool) -> &mut Service {
-        if no_monitoring {
-            self.monitoring = false;
-            self.deps.retain(|d| !d.is_monitoring());
+    // its server stays whenever it is set
+    pub fn set_health(&mut self, health: bool) -> &mut Service {
+        self.health = health;
 
-            return self;
+        let server = self.taken_out.iter().position(|d| d.name == HEALTH_SERVER);
+        if let (true, Some(index)) = (health, server) {
+            let dep = self.taken_out.remove(index);
+            self.deps.push(dep);
         }
 
         self
@@ -138,15 +139,26 @@ impl Service {
     }
 
     pub fn set_protocol(&mut self, protocol: Protocol) -> &mut Service {
-        if protocol.is_grpc() {
-            self.protocol = protocol;
-            self.set_no_rest();
-
-            return self;
-        }
+        let (kept, taken_out) = match protocol {
+            Protocol::Rest => ("rest", "grpc"),
+            Protocol::Grpc => ("grpc", "rest"),
+        };
 
         self.protocol = protocol;
-        self.set_no_grpc();
+        self.set_feature(kept, true, Reason::Protocol(protocol));
+        self.set_feature(taken_out, false, Reason::Protocol(protocol));
+
+        self
+    }
+
+    // solfacil git deps cloned the same way the template was
+    pub fn set_git_scheme(&mut self, rewrite: bool) -> &mut Service {
+        if rewrite {
+            let ssh = self.ssh;
+            self.deps.iter_mut().for_each(|d| {
+                d.set_git_scheme(ssh);
+            });
+        }
 
         self
     }
@@ -158,14 +170,23 @@ impl Service {
         self
     }
 
-    fn set_no_grpc(&mut self) {
-        // TODO remove all grpc stuff
-        self.deps.retain(|d| !d.is_grpc())
-    }
+    // features missing from the catalog have nothing to take out
+    fn set_feature(&mut self, name: &str, enabled: bool, reason: Reason) {
+        if !enabled {
+            if let Some(feature) = self.catalog.get(name) {
+                let health = self.health;
+                let (kept, taken_out): (Vec<Dep>, Vec<Dep>) = self
+                    .deps
+                    .drain(..)
+                    .partition(|d| !feature.owns(d) || (health && d.name == HEALTH_SERVER));
+
+                self.deps = kept;
+                self.taken_out.extend(taken_out);
+            }
+        }
 
-    fn set_no_rest(&mut self) {
-        // TODO remove all phoenix stuff
-        self.set_no_graphql(true);
+        self.choices
+            .insert(name.to_string(), Choice::new(enabled, reason));
     }
 }
 
@@ -177,163 +198,197 @@ mod tests {
     const CARGO_ROOT: &str = env!("CARGO_MANIFEST_DIR");
 
     #[test]
-    fn set_no_auth() {
-        let mut default_service = Service::default();
-
-        assert_eq!(default_service.auth, true);
-
+    fn set_no_feature() {
         let path = Path::new(CARGO_ROOT).join("priv");
         let deps = de::parse_deps(path.as_path()).unwrap();
-        let service = default_service.set_deps(deps).set_no_auth(true);
+        let catalog = Catalog::default();
 
-        assert_eq!(service.auth, false);
-        assert!(service.deps.iter().all(|d| !d.is_auth()));
-    }
+        for feature in catalog.features().iter().filter(|f| f.flag().is_some()) {
+            let mut default_service = Service::default();
 
-    #[test]
-    fn set_no_database() {
-        let mut default_service = Service::default();
+            assert!(default_service.has_feature(feature.name()));
+            assert!(deps.iter().any(|d| feature.owns(d)));
 
-        assert_eq!(default_service.database, true);
+            let service = default_service
+                .set_deps(deps.clone())
+                .set_no_feature(feature.name(), true);
 
-        let path = Path::new(CARGO_ROOT).join("priv");
-        let deps = de::parse_deps(path.as_path()).unwrap();
-        let service = default_service.set_deps(deps).set_no_database(true);
-
-        assert_eq!(service.database, false);
-        assert!(service.deps.iter().all(|d| !d.is_database()));
+            assert!(!service.has_feature(feature.name()));
+            assert!(service.deps.iter().all(|d| !feature.owns(d)));
+            assert_eq!(service.disabled_features(), [feature]);
+        }
     }
 
     #[test]
-    fn set_no_graphql() {
+    fn keep_feature() {
         let mut default_service = Service::default();
 
-        assert_eq!(default_service.graphql, true);
-
         let path = Path::new(CARGO_ROOT).join("priv");
         let deps = de::parse_deps(path.as_path()).unwrap();
-        let service = default_service.set_deps(deps).set_no_graphql(true);
-
-        assert_eq!(service.graphql, false);
-        assert!(service.deps.iter().all(|d| !d.is_graphql()));
+        let service = default_service
+            .set_deps(deps.clone())
+            .set_no_feature("auth", false)
+            .set_no_feature("cache", true); // not in the catalog
+
+        assert!(service.has_feature("auth"));
+        assert!(!service.has_feature("cache"));
+        assert_eq!(service.deps, deps);
+        assert!(service.disabled_features().is_empty());
     }
 
     #[test]
-    fn set_no_http_client() {
+    fn set_name() {
         let mut default_service = Service::default();
 
-        assert_eq!(default_service.http_client, true);
+        assert_eq!(default_service.name, "service_template".to_string());
 
-        let path = Path::new(CARGO_ROOT).join("priv");
-        let deps = de::parse_deps(path.as_path()).unwrap();
-        let service = default_service.set_deps(deps).set_no_http_client(true);
+        let name = "a_name".to_string();
+        let service = default_service.set_name(name.clone());
 
-        assert_eq!(service.http_client, false);
-        assert!(service.deps.iter().all(|d| !d.is_http_client()));
+        assert_eq!(service.name, name);
     }
 
     #[test]
-    fn set_no_mailer() {
+    fn set_path() {
         let mut default_service = Service::default();
 
-        assert_eq!(default_service.mailer, true);
+        assert_eq!(default_service.path, Path::new("./service_template"));
 
-        let path = Path::new(CARGO_ROOT).join("priv");
-        let deps = de::parse_deps(path.as_path()).unwrap();
-        let service = default_service.set_deps(deps).set_no_mailer(true);
+        let path = Path::new("a_path").to_str().unwrap_or_default().to_string();
+        let service = default_service.set_path(path.clone());
 
-        assert_eq!(service.mailer, false);
-        assert!(service.deps.iter().all(|d| !d.is_mailer()));
+        assert_eq!(service.path, Path::new(&path));
     }
 
     #[test]
-    fn set_no_messaging() {
+    fn set_rest_protocol() {
         let mut default_service = Service::default();
 
-        assert_eq!(default_service.messaging, true);
+        assert_eq!(default_service.protocol, Protocol::Rest);
 
         let path = Path::new(CARGO_ROOT).join("priv");
         let deps = de::parse_deps(path.as_path()).unwrap();
-        let service = default_service.set_deps(deps).set_no_messaging(true);
+        let service = default_service.set_deps(deps).set_protocol(Protocol::Rest);
+        let grpc = Catalog::default().get("grpc").unwrap().clone();
 
-        assert_eq!(service.messaging, false);
-        assert!(service.deps.iter().all(|d| !d.is_messaging()));
+        assert!(service.has_feature("graphql"));
+        assert!(!service.has_feature("grpc"));
+        assert!(service.deps.iter().all(|d| !grpc.owns(d)));
     }
 
     #[test]
-    fn set_no_monitoring() {
+    fn set_grpc_protocol() {
         let mut default_service = Service::default();
 
-        assert_eq!(default_service.monitoring, true);
+        assert_eq!(default_service.protocol, Protocol::Rest);
 
         let path = Path::new(CARGO_ROOT).join("priv");
         let deps = de::parse_deps(path.as_path()).unwrap();
-        let service = default_service.set_deps(deps).set_no_monitoring(true);
+        let service = default_service.set_deps(deps).set_protocol(Protocol::Grpc);
+
+        let grpc = Catalog::default().get("grpc").unwrap().clone();
+        let absinthe = service.deps.iter().any(|d| d.name == "absinthe");
 
-        assert_eq!(service.monitoring, false);
-        assert!(service.deps.iter().all(|d| !d.is_monitoring()));
+        assert_eq!(service.protocol, Protocol::Grpc);
+        assert!(service.deps.iter().any(|d| grpc.owns(d)));
+        assert!(!service.has_feature("rest"));
+        // until the rules are applied
+        assert!(service.has_feature("graphql") && absinthe);
+
+        let outcome = service.apply_rules().unwrap();
+
+        assert!(!service.has_feature("graphql"));
+        assert!(service.deps.iter().all(|d| d.name != "absinthe"));
+        assert_eq!(
+            outcome.get("graphql").unwrap().reason(),
+            &Reason::Requires("rest".to_string())
+        );
     }
 
     #[test]
-    fn set_name() {
-        let mut default_service = Service::default();
-
-        assert_eq!(default_service.name, "service_template".to_string());
+    fn set_health() {
+        let path = Path::new(CARGO_ROOT).join("priv");
+        let deps = de::parse_deps(path.as_path()).unwrap();
+        let has_dep = |service: &Service, name: &str| service.deps.iter().any(|d| d.name == name);
 
-        let name = "a_name".to_string();
-        let service = default_service.set_name(name.clone());
+        let mut service = Service::default();
+        service.set_deps(deps.clone()).set_protocol(Protocol::Grpc);
 
-        assert_eq!(service.name, name);
-    }
+        assert!(!has_dep(&service, "phoenix"));
+        assert!(!has_dep(&service, HEALTH_SERVER));
 
-    #[test]
-    fn set_path() {
-        let mut default_service = Service::default();
+        let mut service = Service::default();
+        service
+            .set_deps(deps.clone())
+            .set_health(true)
+            .set_protocol(Protocol::Grpc);
 
-        assert_eq!(default_service.path, Path::new("./service_template"));
+        assert!(!has_dep(&service, "open_api_spex"));
+        assert!(has_dep(&service, HEALTH_SERVER));
 
-        let path = Path::new("a_path").to_str().unwrap_or_default().to_string();
-        let service = default_service.set_path(path.clone());
+        // the other way around
+        let mut service = Service::default();
+        service
+            .set_deps(deps)
+            .set_protocol(Protocol::Grpc)
+            .set_health(true);
 
-        assert_eq!(service.path, Path::new(&path));
+        assert!(!has_dep(&service, "open_api_spex"));
+        assert!(has_dep(&service, HEALTH_SERVER));
     }
 
     #[test]
-    fn set_rest_protocol() {
+    fn apply_rules() {
         let mut default_service = Service::default();
 
-        assert_eq!(default_service.protocol, Protocol::Rest);
+        // nothing disables `grpc` or `rest`
+        assert!(default_service.apply_rules().is_err());
 
-        let path = Path::new(CARGO_ROOT).join("priv");
-        let deps = de::parse_deps(path.as_path()).unwrap();
-        let service = default_service.set_deps(deps);
+        let service = default_service
+            .set_protocol(Protocol::Rest)
+            .set_no_feature("mailer", true);
+        let outcome = service.apply_rules().unwrap();
 
-        // IMPROVEME on
-        assert!(service.deps.iter().any(|d| d.is_graphql()));
+        assert!(outcome.warnings().is_empty());
+        assert_eq!(
+            outcome.get("mailer").unwrap().reason(),
+            &Reason::Flag("--no-mailer".to_string())
+        );
     }
 
     #[test]
-    fn set_grpc_protocol() {
+    fn set_ssh() {
         let mut default_service = Service::default();
 
-        assert_eq!(default_service.protocol, Protocol::Rest);
+        assert!(!default_service.ssh);
 
-        let path = Path::new(CARGO_ROOT).join("priv");
-        let deps = de::parse_deps(path.as_path()).unwrap();
-        let service = default_service.set_deps(deps).set_protocol(Protocol::Grpc);
+        let service = default_service.set_ssh(true);
 
-        assert_eq!(service.protocol, Protocol::Grpc);
-        assert!(service.deps.iter().any(|d| d.is_grpc()));
+        assert!(service.ssh);
     }
 
     #[test]
-    fn set_ssh() {
+    fn set_git_scheme() {
         let mut default_service = Service::default();
 
-        assert_eq!(default_service.ssh, false);
-
-        let service = default_service.set_ssh(true);
-
-        assert_eq!(service.ssh, true);
+        let path = Path::new(CARGO_ROOT).join("priv");
+        let deps = de::parse_deps(path.as_path()).unwrap();
+        let service = default_service.set_deps(deps.clone());
+
+        // nothing changes unless asked
+        assert_eq!(service.set_git_scheme(false).deps, deps);
+
+        let database = service
+            .set_git_scheme(true)
+            .deps
+            .iter()
+            .find(|d| d.name == "database")
+            .unwrap();
+
+        assert_eq!(
+            database.git,
+            Some("https://github.com/solfacil/database.git".to_string())
+        );
+        assert_eq!(database.tag, Some("0.0.7".to_string()));
     }
 }
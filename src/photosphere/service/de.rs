@@ -1,118 +1,196 @@
-use super::dep::{Dep, Env};
-use anyhow::Result;
-use std::path::Path;
-
-pub const DEPS_START: &'static &str = &"# start deps";
-const DEPS_END: &'static &str = &"# end deps";
+use super::{
+    dep::{Compile, Dep, Env},
+    project,
+    version::VersionRequirement,
+};
+use crate::parser::{
+    self,
+    ast::Expr,
+    cst::{SyntaxKind, SyntaxNode, SyntaxTree},
+};
+use anyhow::{anyhow, bail, Result};
+use std::{fs, ops::Range, path::Path};
 
 pub fn parse_deps(root: &Path) -> Result<Vec<Dep>> {
-    let mix_exs_path = root.join("mix.exs");
-    let mix_exs = std::fs::read_to_string(mix_exs_path)?;
-
-    let start = mix_exs.find(DEPS_START).unwrap_or(0) + DEPS_START.len();
-    let end = mix_exs.find(DEPS_END).unwrap_or(mix_exs.len());
-    let raw_deps = &mix_exs[start..end];
-
-    let mut deps = Vec::<Dep>::new();
-
-    for line in raw_deps.lines() {
-        deps.push(parse_dep(line));
-    }
-
-    // remove "deps" parsed from empty chars
-    deps.retain(|d| !d.name.is_empty());
+    let mix_exs = fs::read_to_string(root.join("mix.exs"))?;
+    let tree = SyntaxTree::parse(&mix_exs)?;
 
-    Ok(deps)
+    Ok(parse_dep_nodes(&tree)?
+        .into_iter()
+        .map(|(_, dep)| dep)
+        .collect())
 }
 
-fn parse_dep(line: &str) -> Dep {
-    let mut default_dep = Dep::default();
-
-    let raw_dep = line
-        .chars()
-        .filter(|c| !c.is_whitespace())
-        .collect::<String>();
-
-    default_dep
-        .set_conflict(parse_override(&raw_dep))
-        .set_envs(parse_envs(&raw_dep))
-        .set_git(parse_is_git(&raw_dep))
-        .set_name(parse_name(&raw_dep))
-        .set_runtime(parse_runtime(&raw_dep))
-        .set_version(parse_version(&raw_dep))
-        .clone()
+// each element of the `deps/0` list, by
+// its token range in the mix.exs tree
+pub(super) fn parse_dep_nodes(tree: &SyntaxTree) -> Result<Vec<(Range<usize>, Dep)>> {
+    deps_node(tree)?
+        .children()
+        .iter()
+        .map(|node| Ok((node.range(), parse_dep(&tree.text(node.range()))?)))
+        .collect()
 }
 
-fn parse_override(raw_dep: &str) -> Option<bool> {
-    let is_conflict = raw_dep.contains("override:true");
-    let no_conflict = raw_dep.contains("override:false");
-
-    match (is_conflict, no_conflict) {
-        (true, false) => Some(true),
-        (false, true) => Some(false),
-        _ => None,
-    }
+pub(super) fn deps_node(tree: &SyntaxTree) -> Result<&SyntaxNode> {
+    project::def_node(tree, "deps")
+        .filter(|n| n.kind() == SyntaxKind::List)
+        .ok_or_else(|| anyhow!("no `deps/0` list found in mix.exs"))
 }
 
-fn parse_envs(raw_dep: &str) -> Option<Vec<Env>> {
-    if !raw_dep.contains("only:") {
-        return None;
+// `{:name, requirement}`, `{:name, opts}`
+// or `{:name, requirement, opts}`
+fn parse_dep(raw_dep: &str) -> Result<Dep> {
+    let expr = parser::parse(raw_dep.trim().trim_end_matches(','))?;
+
+    let (name, rest) = match expr.as_tuple().and_then(|t| t.split_first()) {
+        Some((name, rest)) if name.as_atom().is_some() => (name.as_atom().unwrap(), rest),
+        _ => bail!("`{}` is not a dependency", raw_dep.trim()),
+    };
+
+    let (version, options) = match rest {
+        [] => bail!("`{}` dependency has no requirement nor options", name),
+        [version] if version.as_string().is_some() => (version.as_string(), vec![]),
+        [options] => (None, parse_options(name, options)?),
+        [version, options] => (version.as_string(), parse_options(name, options)?),
+        _ => bail!("too many elements in `{}` dependency", name),
+    };
+
+    let version = match version {
+        Some(version) => Some(
+            VersionRequirement::parse(version)
+                .map_err(|e| anyhow!("invalid `{}` dependency requirement: {}", name, e))?,
+        ),
+        None => None,
+    };
+
+    let mut dep = Dep::new();
+    dep.set_name(name.to_string()).set_version(version);
+
+    // `[{:key, value}]` options have no pairs to take the source of
+    let mut others = vec![];
+    let mut sources = option_sources(raw_dep)?;
+    if sources.len() != options.len() {
+        sources = options.iter().map(|(_, value)| value.to_string()).collect();
     }
 
-    let mut envs = Vec::<Env>::new();
-
-    if raw_dep.contains(":dev") {
-        envs.push(Env::Dev);
-    }
-
-    if raw_dep.contains(":test") {
-        envs.push(Env::Test);
+    for ((key, value), source) in options.into_iter().zip(sources) {
+        let string = || parse_string(name, key, value);
+        let boolean = || parse_bool(name, key, value);
+
+        match key {
+            "app" => dep.set_app(Some(boolean()?)),
+            "branch" => dep.set_branch(Some(string()?)),
+            "compile" => dep.set_compile(Some(parse_compile(name, value)?)),
+            "git" => dep.set_git(Some(string()?)),
+            "github" => dep.set_github(Some(string()?)),
+            "hex" => dep.set_hex(Some(parse_package(name, key, value)?)),
+            "in_umbrella" => dep.set_in_umbrella(Some(boolean()?)),
+            "manager" => dep.set_manager(Some(parse_package(name, key, value)?)),
+            "only" => dep.set_envs(Some(parse_envs(name, value)?)),
+            "optional" => dep.set_optional(Some(boolean()?)),
+            "organization" => dep.set_organization(Some(string()?)),
+            "override" => dep.set_conflict(Some(boolean()?)),
+            "path" => dep.set_path(Some(string()?)),
+            "ref" => dep.set_git_ref(Some(string()?)),
+            "repo" => dep.set_repo(Some(string()?)),
+            "runtime" => dep.set_runtime(Some(boolean()?)),
+            "sparse" => dep.set_sparse(Some(string()?)),
+            "submodules" => dep.set_submodules(Some(boolean()?)),
+            "tag" => dep.set_tag(Some(string()?)),
+            "targets" => dep.set_targets(Some(parse_atoms(name, key, value)?)),
+            _ => {
+                others.push((key.to_string(), source));
+                &mut dep
+            }
+        };
     }
 
-    if raw_dep.contains(":prod") {
-        envs.push(Env::Prod);
-    }
+    dep.set_others(others);
 
-    Some(envs)
+    Ok(dep)
 }
 
-fn parse_is_git(raw_dep: &str) -> bool {
-    raw_dep.contains("git:")
+// the source of each option value, in order, for
+// the ones we don't know to be written back as is
+fn option_sources(raw_dep: &str) -> Result<Vec<String>> {
+    let tree = SyntaxTree::parse(raw_dep.trim().trim_end_matches(','))?;
+
+    let sources = tree
+        .root()
+        .descendants()
+        .into_iter()
+        .filter(|n| n.kind() == SyntaxKind::Tuple)
+        .take(1)
+        .flat_map(|tuple| tuple.children())
+        .flat_map(|node| match node.kind() {
+            SyntaxKind::List => node.children().iter().collect(),
+            _ => vec![node],
+        })
+        .filter(|node| node.kind() == SyntaxKind::Pair)
+        .filter_map(|pair| pair.children().last())
+        .map(|value| tree.text(value.range()))
+        .collect();
+
+    Ok(sources)
 }
 
-fn parse_name(raw_dep: &str) -> String {
-    // `find` stops on first char match
-    let start = raw_dep.find(':').unwrap_or(0);
-    let end = raw_dep.find(',').unwrap_or(raw_dep.len());
+fn parse_options<'a>(name: &str, options: &'a Expr) -> Result<Vec<(&'a str, &'a Expr)>> {
+    options
+        .as_keyword()
+        .ok_or_else(|| anyhow!("`{}` dependency options must be a keyword list", name))
+}
 
-    raw_dep[start..end].replace(':', "")
+fn parse_string(name: &str, key: &str, value: &Expr) -> Result<String> {
+    match value.as_string() {
+        Some(string) => Ok(string.to_string()),
+        None => bail!("`{}` of `{}` dependency must be a string", key, name),
+    }
 }
 
-fn parse_runtime(raw_dep: &str) -> Option<bool> {
-    let is_runtime = raw_dep.contains("runtime:true");
-    let no_runtime = raw_dep.contains("runtime:false");
+fn parse_bool(name: &str, key: &str, value: &Expr) -> Result<bool> {
+    value
+        .as_bool()
+        .ok_or_else(|| anyhow!("`{}` of `{}` dependency must be a boolean", key, name))
+}
 
-    match (is_runtime, no_runtime) {
-        (true, false) => Some(true),
-        (false, true) => Some(false),
-        _ => None,
+// atoms and strings alike, `hex: :foo` or
+// `hex: "foo"`, written back as an atom
+fn parse_package(name: &str, key: &str, value: &Expr) -> Result<String> {
+    match value.as_atom().or_else(|| value.as_string()) {
+        Some(string) => Ok(string.to_string()),
+        None => bail!("`{}` of `{}` dependency must be an atom", key, name),
     }
 }
 
-fn parse_version(raw_dep: &str) -> String {
-    if raw_dep.contains("git:") {
-        let start = raw_dep.find("tag:").unwrap_or(0);
+// a single atom or a list of them
+fn parse_atoms(name: &str, key: &str, value: &Expr) -> Result<Vec<String>> {
+    let atoms = match value {
+        Expr::List(list) => list.iter().map(Expr::as_atom).collect(),
+        atom => atom.as_atom().map(|a| vec![a]),
+    };
 
-        return raw_dep[start..]
-            .chars()
-            .filter(|c| c.is_numeric() || c.eq(&'.'))
-            .collect::<String>();
+    match atoms {
+        Some(atoms) => Ok(atoms.into_iter().map(|a| a.to_string()).collect()),
+        None => bail!("`{}` of `{}` dependency must be atoms", key, name),
     }
+}
+
+fn parse_envs(name: &str, value: &Expr) -> Result<Vec<Env>> {
+    Ok(parse_atoms(name, "only", value)?
+        .iter()
+        .map(|env| Env::from_atom(env))
+        .collect())
+}
 
-    raw_dep
-        .chars()
-        .filter(|c| c.is_numeric() || c.eq(&'.'))
-        .collect::<String>()
+fn parse_compile(name: &str, value: &Expr) -> Result<Compile> {
+    match (value.as_string(), value.as_bool()) {
+        (Some(command), _) => Ok(Compile::Command(command.to_string())),
+        (_, Some(false)) => Ok(Compile::Skip),
+        _ => bail!(
+            "`compile` of `{}` dependency must be a string or false",
+            name
+        ),
+    }
 }
 
 #[cfg(test)]
@@ -124,22 +202,19 @@ mod tests {
         let raw_dep = r#"{:phoenix, "~> 1.6.6"},"#;
 
         let dep = Dep {
-            conflict: None,
-            envs: None,
-            git: false,
             name: "phoenix".to_string(),
-            runtime: None,
-            version: "1.6.6".to_string(),
+            version: VersionRequirement::parse("~> 1.6.6").ok(),
+            ..Dep::default()
         };
 
-        assert_eq!(parse_dep(raw_dep), dep);
+        assert_eq!(parse_dep(raw_dep).unwrap(), dep);
     }
 
     #[test]
     fn parse_only_dep() {
         let raw_dep = r#"{:credo, "~> 1.6", only: [:dev, :test]},"#;
 
-        let dep = parse_dep(raw_dep);
+        let dep = parse_dep(raw_dep).unwrap();
 
         assert_eq!(dep.envs, Some(vec![Env::Dev, Env::Test]));
     }
@@ -148,18 +223,205 @@ mod tests {
     fn parse_runtime_dep() {
         let raw_dep = r#"{:dialyxir, "~> 1.0", runtime: false},"#;
 
-        let dep = parse_dep(raw_dep);
+        let dep = parse_dep(raw_dep).unwrap();
 
         assert_eq!(dep.runtime, Some(false));
     }
 
+    #[test]
+    fn parse_single_env_dep() {
+        let raw_dep = r#"{:mox, "~> 1.0", only: :test}"#;
+
+        let dep = parse_dep(raw_dep).unwrap();
+
+        assert_eq!(dep.envs, Some(vec![Env::Test]));
+    }
+
     #[test]
     fn parse_git_dep() {
         let raw_dep = r#"{:database, git: "git@github.com:solfacil/database.git", tag: "0.0.7"}"#;
 
-        let dep = parse_dep(raw_dep);
+        let dep = parse_dep(raw_dep).unwrap();
+
+        assert_eq!(
+            dep.git,
+            Some("git@github.com:solfacil/database.git".to_string())
+        );
+        assert_eq!(dep.tag, Some("0.0.7".to_string()));
+        assert_eq!(dep.version, None);
+    }
+
+    #[test]
+    fn parse_scm_options() {
+        let raw_dep = r#"{:grpc, github: "elixir-grpc/grpc", ref: "a1b2c3", sparse: "grpc", submodules: true}"#;
+
+        let dep = parse_dep(raw_dep).unwrap();
+
+        assert_eq!(dep.github, Some("elixir-grpc/grpc".to_string()));
+        assert_eq!(dep.git_ref, Some("a1b2c3".to_string()));
+        assert_eq!(dep.sparse, Some("grpc".to_string()));
+        assert_eq!(dep.submodules, Some(true));
+    }
+
+    #[test]
+    fn parse_package_options() {
+        let raw_dep = r#"{:gun, "~> 2.0.0", repo: "hexpm", hex: "grpc_gun", organization: "solfacil", override: true}"#;
+
+        let dep = parse_dep(raw_dep).unwrap();
+
+        assert_eq!(dep.version.unwrap().to_string(), "~> 2.0.0");
+        assert_eq!(dep.repo, Some("hexpm".to_string()));
+        assert_eq!(dep.hex, Some("grpc_gun".to_string()));
+        assert_eq!(dep.organization, Some("solfacil".to_string()));
+        assert_eq!(dep.conflict, Some(true));
+    }
+
+    #[test]
+    fn parse_build_options() {
+        let raw_dep = r#"{:nif, path: "../nif", in_umbrella: false, app: false, optional: true, targets: [:rpi4], compile: "make", manager: :make}"#;
+
+        let dep = parse_dep(raw_dep).unwrap();
+
+        assert_eq!(dep.path, Some("../nif".to_string()));
+        assert_eq!(dep.in_umbrella, Some(false));
+        assert_eq!(dep.app, Some(false));
+        assert_eq!(dep.optional, Some(true));
+        assert_eq!(dep.targets, Some(vec!["rpi4".to_string()]));
+        assert_eq!(dep.compile, Some(Compile::Command("make".to_string())));
+        assert_eq!(dep.manager, Some("make".to_string()));
+    }
+
+    #[test]
+    fn parse_dep_round_trip() {
+        let raw_deps = [
+            r#"{:credo, "~> 1.6", only: [:dev, :test], runtime: false}"#,
+            r#"{:grpc, github: "elixir-grpc/grpc"}"#,
+            r#"{:ex_commons, git: "git@github.com:solfacil/ex_commons.git", tag: "0.0.3", override: true}"#,
+            r#"{:excoveralls, "~> 0.14 and >= 0.14.4", only: [:test]}"#,
+        ];
+
+        for raw_dep in raw_deps {
+            assert_eq!(parse_dep(raw_dep).unwrap().to_string(), raw_dep);
+        }
+    }
+
+    #[test]
+    fn renamed_dep_round_trip() {
+        let raw_dep = r#"{:grpc, "~> 0.5", hex: :grpc_fresha}"#;
+
+        let mut dep = parse_dep(raw_dep).unwrap();
+        dep.set_git_scheme(true);
+
+        assert_eq!(dep.to_string(), raw_dep);
+        assert_eq!(
+            parse_dep(r#"{:grpc, "~> 0.5", hex: "grpc_fresha"}"#)
+                .unwrap()
+                .to_string(),
+            raw_dep
+        );
+    }
+
+    #[test]
+    fn parse_invalid_dep() {
+        assert!(parse_dep(r#"{:foo, runtime: "no"}"#).is_err());
+        assert!(parse_dep(r#"{"foo", "~> 1.0"}"#).is_err());
+        assert!(parse_dep(r#"{:foo}"#).is_err());
+        assert!(parse_dep(r#"{:foo, ">= 1.0"}"#).is_err());
+    }
+
+    #[test]
+    fn parse_custom_env_dep() {
+        let dep = parse_dep(r#"{:jason, "~> 1.2", only: [:dev, :staging]}"#).unwrap();
+
+        assert_eq!(
+            dep.envs,
+            Some(vec![Env::Dev, Env::Other("staging".to_string())])
+        );
+        assert_eq!(
+            dep.to_string(),
+            r#"{:jason, "~> 1.2", only: [:dev, :staging]}"#
+        );
+    }
+
+    #[test]
+    fn parse_skipped_compile_dep() {
+        let raw_dep = r#"{:rustler_src, path: "native", compile: false}"#;
+
+        let dep = parse_dep(raw_dep).unwrap();
+
+        assert_eq!(dep.compile, Some(Compile::Skip));
+        assert_eq!(dep.to_string(), raw_dep);
+        assert!(parse_dep(r#"{:foo, path: "foo", compile: true}"#).is_err());
+    }
+
+    #[test]
+    fn keep_unknown_options() {
+        let raw_dep = r#"{:nif, "~> 0.1", system_env: [{"CC", "clang"}], depth: 1, only: :prod, env: :prod, warn_if_outdated: true}"#;
+
+        let dep = parse_dep(raw_dep).unwrap();
+
+        assert_eq!(dep.envs, Some(vec![Env::Prod]));
+        assert_eq!(
+            dep.others,
+            vec![
+                ("system_env".to_string(), r#"[{"CC", "clang"}]"#.to_string()),
+                ("depth".to_string(), "1".to_string()),
+                ("env".to_string(), ":prod".to_string()),
+                ("warn_if_outdated".to_string(), "true".to_string()),
+            ]
+        );
+        assert_eq!(
+            dep.to_string(),
+            r#"{:nif, "~> 0.1", only: [:prod], system_env: [{"CC", "clang"}], depth: 1, env: :prod, warn_if_outdated: true}"#
+        );
+        assert!(parse_dep(r#"{:nif, [system_env: %{"CC" => "clang"}]}"#).is_ok());
+    }
 
-        assert_eq!(dep.git, true);
-        assert_eq!(dep.version, "0.0.7".to_string());
+    #[test]
+    fn parse_mix_exs_dep_nodes() {
+        let mix_exs = r#"defmodule Foo.MixProject do
+  use Mix.Project
+
+  def project, do: [app: :foo, deps: deps()]
+
+  defp deps do
+    [
+      # start deps
+      {:phoenix, "~> 1.6.6"},
+      {:credo, "~> 1.6", only: [:dev, :test], runtime: false}
+      # end deps
+    ]
+  end
+end
+"#;
+        let tree = SyntaxTree::parse(mix_exs).unwrap();
+
+        let nodes = parse_dep_nodes(&tree).unwrap();
+        let (range, dep) = nodes.last().unwrap();
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(dep.name, "credo".to_string());
+        assert_eq!(
+            tree.text(range.clone()),
+            r#"{:credo, "~> 1.6", only: [:dev, :test], runtime: false}"#
+        );
+    }
+
+    #[test]
+    fn parse_template_dep_nodes() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("priv");
+        let mix_exs = std::fs::read_to_string(root.join("mix.exs")).unwrap();
+        let tree = SyntaxTree::parse(&mix_exs).unwrap();
+
+        let nodes = parse_dep_nodes(&tree).unwrap();
+        let (range, dep) = nodes.last().unwrap();
+
+        assert_eq!(tree.to_string(), mix_exs);
+        assert_eq!(nodes.len(), parse_deps(&root).unwrap().len());
+        assert_eq!(dep.name, "logger_json".to_string());
+        assert_eq!(
+            tree.text(range.clone()),
+            r#"{:logger_json, "~> 5.0", only: [:prod]}"#
+        );
     }
 }
@@ -1,31 +1,73 @@
-use crate::parser::lexer::Lexer;
+use super::version::VersionRequirement;
+use crate::parser::ast::{keyword_key, Expr};
+use anyhow::{bail, Result};
+use std::fmt;
+
+const SOLFACIL_HTTPS: &str = "https://github.com/solfacil/";
+const SOLFACIL_SSH: &str = "git@github.com:solfacil/";
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 // `Option` field for those
 // that cannot be explicit on `mix.exs`
 // but also can be `false`
 pub struct Dep {
-    pub(super) conflict: Option<bool>,
-    pub(super) envs: Option<Vec<Env>>,
-    pub(super) git: bool,
+    pub(super) app: Option<bool>,
+    pub(super) branch: Option<String>,
+    pub(super) compile: Option<Compile>,
+    pub(super) conflict: Option<bool>, // `override`
+    pub(super) envs: Option<Vec<Env>>, // `only`
+    pub(super) git: Option<String>,
+    pub(super) git_ref: Option<String>, // `ref`
+    pub(super) github: Option<String>,
+    pub(super) hex: Option<String>,
+    pub(super) in_umbrella: Option<bool>,
+    pub(super) manager: Option<String>,
     pub(super) name: String,
+    pub(super) optional: Option<bool>,
+    pub(super) organization: Option<String>,
+    pub(super) others: Vec<(String, String)>, // unknown to us, as written
+    pub(super) path: Option<String>,
+    pub(super) repo: Option<String>,
     pub(super) runtime: Option<bool>,
-    pub(super) version: String,
+    pub(super) sparse: Option<String>,
+    pub(super) submodules: Option<bool>,
+    pub(super) tag: Option<String>,
+    pub(super) targets: Option<Vec<String>>,
+    pub(super) version: Option<VersionRequirement>, // none for git, path... deps
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Env {
     Dev,
+    Other(String), // `:staging` and the like
     Prod,
     Test,
 }
 
+// `compile: "make"` or `compile: false`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Compile {
+    Command(String),
+    Skip,
+}
+
 impl Env {
-    pub fn to_str(&self) -> &str {
-        match *self {
-            Env::Dev => ":dev",
-            Env::Test => ":test",
-            Env::Prod => ":prod",
+    pub fn from_atom(atom: &str) -> Env {
+        match atom {
+            "dev" => Env::Dev,
+            "test" => Env::Test,
+            "prod" => Env::Prod,
+            other => Env::Other(other.to_string()),
+        }
+    }
+
+    // without the leading colon
+    pub fn name(&self) -> &str {
+        match self {
+            Env::Dev => "dev",
+            Env::Other(name) => name,
+            Env::Test => "test",
+            Env::Prod => "prod",
         }
     }
 }
@@ -35,57 +77,92 @@ impl Dep {
         Dep::default()
     }
 
-    pub fn is_auth(&self) -> bool {
-        let lc_name = self.name.to_lowercase();
+    // what mix would refuse, caught before it gets to mix.exs
+    pub fn validate(&self) -> Result<()> {
+        let mut chars = self.name.chars();
+        let is_app_name = chars.next().is_some_and(|c| c.is_ascii_lowercase())
+            && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
 
-        lc_name.eq("guardian") || lc_name.eq("bodyguard")
-    }
+        if !is_app_name {
+            bail!("`{}` is not a valid dependency name", self.name);
+        }
 
-    pub fn is_database(&self) -> bool {
-        let lc_name = self.name.to_lowercase();
+        let count = |options: &[bool]| options.iter().filter(|o| **o).count();
+        let sources = count(&[
+            self.git.is_some(),
+            self.github.is_some(),
+            self.path.is_some(),
+        ]);
+        let checkouts = count(&[
+            self.tag.is_some(),
+            self.branch.is_some(),
+            self.git_ref.is_some(),
+        ]);
 
-        lc_name.eq("database")
-            || lc_name.eq("ecto_sql")
-            || lc_name.eq("phoenix_ecto")
-            || lc_name.eq("postgrex")
-    }
+        if sources > 1 {
+            bail!("`{}` can only have one of git, github or path", self.name);
+        }
+
+        // mix refuses a lone `{:name}`
+        if self.version.is_none() && !self.is_scm() {
+            bail!(
+                "`{}` needs a requirement or a git, github or path source",
+                self.name
+            );
+        }
+
+        if checkouts > 1 {
+            bail!("`{}` can only have one of tag, branch or ref", self.name);
+        }
 
-    pub fn is_graphql(&self) -> bool {
-        let lc_name = self.name.to_lowercase();
+        if checkouts > 0 && self.git.is_none() && self.github.is_none() {
+            bail!("tag, branch and ref of `{}` need a git source", self.name);
+        }
+
+        if sources > 0 && (self.hex.is_some() || self.repo.is_some() || self.organization.is_some())
+        {
+            bail!(
+                "hex options of `{}` don't go with a git or path source",
+                self.name
+            );
+        }
 
-        lc_name.eq("absinthe")
-            || lc_name.eq("absinthe_plug")
-            || lc_name.eq("absinthe_phoenix")
-            || lc_name.eq("absinthe_relay")
+        Ok(())
     }
 
-    pub fn is_grpc(&self) -> bool {
-        let lc_name = self.name.to_lowercase();
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 
-        lc_name.eq("grpc")
-            || lc_name.eq("protobuf")
-            || lc_name.eq("google_protos")
-            || lc_name.eq("gun")
+    // the hex package, `hex: :grpc_gun` for `:gun`
+    pub fn package(&self) -> &str {
+        self.hex.as_deref().unwrap_or(&self.name)
     }
 
-    pub fn is_http_client(&self) -> bool {
-        self.name.to_lowercase().eq("http_client")
+    // fetched from git or a path, not from hex
+    pub fn is_scm(&self) -> bool {
+        self.git.is_some()
+            || self.github.is_some()
+            || self.path.is_some()
+            || self.in_umbrella == Some(true)
     }
 
-    pub fn is_mailer(&self) -> bool {
-        let lc_name = self.name.to_lowercase();
+    pub fn set_app(&mut self, app: Option<bool>) -> &mut Dep {
+        self.app = app;
 
-        lc_name.eq("swoosh") || lc_name.eq("gen_smtp")
+        self
     }
 
-    pub fn is_messaging(&self) -> bool {
-        self.name.to_lowercase().eq("messaging")
+    pub fn set_branch(&mut self, branch: Option<String>) -> &mut Dep {
+        self.branch = branch;
+
+        self
     }
 
-    pub fn is_monitoring(&self) -> bool {
-        let lc_name = self.name.to_lowercase();
+    pub fn set_compile(&mut self, compile: Option<Compile>) -> &mut Dep {
+        self.compile = compile;
 
-        lc_name.contains("spandex") || lc_name.eq("prom_ex") || lc_name.eq("ex_coveralls")
+        self
     }
 
     pub fn set_conflict(&mut self, conflict: Option<bool>) -> &mut Dep {
@@ -100,8 +177,55 @@ impl Dep {
         self
     }
 
-    pub fn set_git(&mut self, is_git: bool) -> &mut Dep {
-        self.git = is_git;
+    pub fn set_git(&mut self, git: Option<String>) -> &mut Dep {
+        self.git = git;
+
+        self
+    }
+
+    // solfacil repositories over ssh or https,
+    // any other git url is kept as it is
+    pub fn set_git_scheme(&mut self, ssh: bool) -> &mut Dep {
+        let repo = self.git.as_deref().and_then(|url| {
+            url.strip_prefix(SOLFACIL_SSH)
+                .or_else(|| url.strip_prefix(SOLFACIL_HTTPS))
+                .or_else(|| url.strip_prefix("ssh://git@github.com/solfacil/"))
+        });
+
+        if let Some(repo) = repo {
+            let prefix = if ssh { SOLFACIL_SSH } else { SOLFACIL_HTTPS };
+            self.git = Some(format!("{}{}", prefix, repo));
+        }
+
+        self
+    }
+
+    pub fn set_git_ref(&mut self, git_ref: Option<String>) -> &mut Dep {
+        self.git_ref = git_ref;
+
+        self
+    }
+
+    pub fn set_github(&mut self, github: Option<String>) -> &mut Dep {
+        self.github = github;
+
+        self
+    }
+
+    pub fn set_hex(&mut self, hex: Option<String>) -> &mut Dep {
+        self.hex = hex;
+
+        self
+    }
+
+    pub fn set_in_umbrella(&mut self, in_umbrella: Option<bool>) -> &mut Dep {
+        self.in_umbrella = in_umbrella;
+
+        self
+    }
+
+    pub fn set_manager(&mut self, manager: Option<String>) -> &mut Dep {
+        self.manager = manager;
 
         self
     }
@@ -112,15 +236,265 @@ impl Dep {
         self
     }
 
+    pub fn set_optional(&mut self, optional: Option<bool>) -> &mut Dep {
+        self.optional = optional;
+
+        self
+    }
+
+    pub fn set_organization(&mut self, organization: Option<String>) -> &mut Dep {
+        self.organization = organization;
+
+        self
+    }
+
+    pub fn set_others(&mut self, others: Vec<(String, String)>) -> &mut Dep {
+        self.others = others;
+
+        self
+    }
+
+    pub fn set_path(&mut self, path: Option<String>) -> &mut Dep {
+        self.path = path;
+
+        self
+    }
+
+    pub fn set_repo(&mut self, repo: Option<String>) -> &mut Dep {
+        self.repo = repo;
+
+        self
+    }
+
     pub fn set_runtime(&mut self, runtime: Option<bool>) -> &mut Dep {
         self.runtime = runtime;
 
         self
     }
 
-    pub fn set_version(&mut self, version: String) -> &mut Dep {
+    pub fn set_sparse(&mut self, sparse: Option<String>) -> &mut Dep {
+        self.sparse = sparse;
+
+        self
+    }
+
+    pub fn set_submodules(&mut self, submodules: Option<bool>) -> &mut Dep {
+        self.submodules = submodules;
+
+        self
+    }
+
+    pub fn set_tag(&mut self, tag: Option<String>) -> &mut Dep {
+        self.tag = tag;
+
+        self
+    }
+
+    pub fn set_targets(&mut self, targets: Option<Vec<String>>) -> &mut Dep {
+        self.targets = targets;
+
+        self
+    }
+
+    pub fn set_version(&mut self, version: Option<VersionRequirement>) -> &mut Dep {
         self.version = version;
 
         self
     }
+
+    // options in the order `mix.exs` files
+    // usually have them, only the set ones
+    fn options(&self) -> Vec<(&str, Expr)> {
+        let string = |value: &Option<String>| value.clone().map(Expr::String);
+        let atom = |value: &Option<String>| value.clone().map(Expr::Atom);
+        let boolean = |value: &Option<bool>| value.map(Expr::Boolean);
+        let atoms = |values: Vec<String>| Expr::List(values.into_iter().map(Expr::Atom).collect());
+
+        let envs = self
+            .envs
+            .as_ref()
+            .map(|envs| atoms(envs.iter().map(|env| env.name().to_string()).collect()));
+        let compile = self.compile.as_ref().map(|compile| match compile {
+            Compile::Command(command) => Expr::String(command.to_string()),
+            Compile::Skip => Expr::Boolean(false),
+        });
+
+        vec![
+            ("git", string(&self.git)),
+            ("github", string(&self.github)),
+            ("path", string(&self.path)),
+            ("in_umbrella", boolean(&self.in_umbrella)),
+            ("tag", string(&self.tag)),
+            ("branch", string(&self.branch)),
+            ("ref", string(&self.git_ref)),
+            ("sparse", string(&self.sparse)),
+            ("submodules", boolean(&self.submodules)),
+            ("hex", atom(&self.hex)),
+            ("repo", string(&self.repo)),
+            ("organization", string(&self.organization)),
+            ("only", envs),
+            ("targets", self.targets.clone().map(atoms)),
+            ("runtime", boolean(&self.runtime)),
+            ("optional", boolean(&self.optional)),
+            ("app", boolean(&self.app)),
+            ("compile", compile),
+            ("manager", atom(&self.manager)),
+            ("override", boolean(&self.conflict)),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?)))
+        .collect()
+    }
+}
+
+// the tuple as it goes in `deps/0`, the
+// options we don't know written back as they were
+impl fmt::Display for Dep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut items = vec![Expr::Atom(self.name.to_string()).to_string()];
+
+        if let Some(version) = &self.version {
+            items.push(Expr::String(version.to_string()).to_string());
+        }
+
+        let options = self
+            .options()
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .chain(self.others.iter().cloned())
+            .map(|(key, value)| format!("{} {}", keyword_key(&key), value));
+        items.extend(options);
+
+        write!(f, "{{{}}}", items.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_hex_dep() {
+        let dep = Dep::new()
+            .set_name("credo".to_string())
+            .set_version(VersionRequirement::parse("~> 1.6").ok())
+            .set_envs(Some(vec![Env::Dev, Env::Test]))
+            .set_runtime(Some(false))
+            .clone();
+
+        assert_eq!(
+            dep.to_string(),
+            r#"{:credo, "~> 1.6", only: [:dev, :test], runtime: false}"#
+        );
+    }
+
+    #[test]
+    fn display_scm_dep() {
+        let dep = Dep::new()
+            .set_name("grpc".to_string())
+            .set_github(Some("elixir-grpc/grpc".to_string()))
+            .set_branch(Some("master".to_string()))
+            .set_submodules(Some(true))
+            .clone();
+
+        assert_eq!(
+            dep.to_string(),
+            r#"{:grpc, github: "elixir-grpc/grpc", branch: "master", submodules: true}"#
+        );
+    }
+
+    #[test]
+    fn set_solfacil_git_scheme() {
+        let mut dep = Dep::new()
+            .set_name("database".to_string())
+            .set_git(Some("git@github.com:solfacil/database.git".to_string()))
+            .set_tag(Some("0.0.7".to_string()))
+            .clone();
+
+        dep.set_git_scheme(false);
+        assert_eq!(
+            dep.to_string(),
+            r#"{:database, git: "https://github.com/solfacil/database.git", tag: "0.0.7"}"#
+        );
+
+        dep.set_git_scheme(true);
+        assert_eq!(
+            dep.git,
+            Some("git@github.com:solfacil/database.git".to_string())
+        );
+    }
+
+    #[test]
+    fn keep_other_git_urls() {
+        let mut dep = Dep::new()
+            .set_name("plug".to_string())
+            .set_git(Some("https://gitlab.com/elixir/plug.git".to_string()))
+            .set_branch(Some("main".to_string()))
+            .clone();
+        let github = Dep::new()
+            .set_name("grpc".to_string())
+            .set_github(Some("elixir-grpc/grpc".to_string()))
+            .clone();
+
+        dep.set_git_scheme(true);
+
+        assert_eq!(
+            dep.git,
+            Some("https://gitlab.com/elixir/plug.git".to_string())
+        );
+        assert_eq!(github.clone().set_git_scheme(true), &github);
+    }
+
+    #[test]
+    fn validate_dep() {
+        let dep = |name: &str| Dep::new().set_name(name.to_string()).clone();
+
+        let hex = |name: &str| {
+            dep(name)
+                .set_version(VersionRequirement::parse("~> 1.0").ok())
+                .clone()
+        };
+
+        assert!(hex("phoenix_live_view").validate().is_ok());
+        assert!(hex("Phoenix").validate().is_err());
+        assert!(hex("http-client").validate().is_err());
+        assert!(dep("jason_probe").validate().is_err());
+        assert!(dep("foo").set_in_umbrella(Some(true)).validate().is_ok());
+        assert!(dep("foo")
+            .set_git(Some("git@github.com:solfacil/foo.git".to_string()))
+            .set_tag(Some("0.1.0".to_string()))
+            .validate()
+            .is_ok());
+        assert!(dep("foo")
+            .set_tag(Some("0.1.0".to_string()))
+            .validate()
+            .is_err());
+        assert!(dep("foo")
+            .set_github(Some("solfacil/foo".to_string()))
+            .set_tag(Some("0.1.0".to_string()))
+            .set_branch(Some("main".to_string()))
+            .validate()
+            .is_err());
+        assert!(dep("foo")
+            .set_path(Some("../foo".to_string()))
+            .set_hex(Some("bar".to_string()))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn display_renamed_dep() {
+        let dep = Dep::new()
+            .set_name("gun".to_string())
+            .set_version(VersionRequirement::parse("~> 2.0.0").ok())
+            .set_hex(Some("grpc_gun".to_string()))
+            .set_repo(Some("hexpm".to_string()))
+            .set_conflict(Some(true))
+            .clone();
+
+        assert_eq!(
+            dep.to_string(),
+            r#"{:gun, "~> 2.0.0", hex: :grpc_gun, repo: "hexpm", override: true}"#
+        );
+    }
 }
@@ -0,0 +1,336 @@
+use super::dep::Dep;
+use crate::photosphere::str_utils;
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+// the built-in catalog, for templates without one
+const CATALOG: &str = include_str!("../../../priv/features.toml");
+
+// where a template keeps its own catalog
+pub const CATALOG_DIR: &str = ".photosphere";
+const CATALOG_FILES: &[&str] = &["features.toml", "features.yaml", "features.yml"];
+
+// Every feature of service-template that `service new`
+// can take out, so a new one only needs the catalog:
+//
+// [[features]]
+// name = "mailer"
+// flag = "no-mailer"
+// packages = ["swoosh", "gen_smtp"]
+// paths = ["lib/{app}_web/mailer"]
+// config = ["{module}Web.Mailer"]
+// env = ["MAILER_*"]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct Catalog {
+    pub(super) features: Vec<Feature>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct Feature {
+    #[serde(default)]
+    pub(super) config: Vec<String>, // what refers to it, `:app`, a module or an `@attribute`
+    #[serde(default)]
+    pub(super) conflicts: Vec<String>, // features it cannot go with
+    #[serde(default)]
+    pub(super) description: Option<String>,
+    #[serde(default)]
+    pub(super) env: Vec<String>,
+    #[serde(default)]
+    pub(super) flag: Option<String>, // `no-mailer` for `--no-mailer`
+    #[serde(default)]
+    pub(super) implies: Vec<String>, // features that must stay with it
+    pub(super) name: String,
+    #[serde(default)]
+    pub(super) packages: Vec<String>,
+    #[serde(default)]
+    pub(super) paths: Vec<String>,
+    #[serde(default)]
+    pub(super) requires: Vec<String>, // taken out when one of them is
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Catalog::parse(CATALOG, "toml").expect("built-in feature catalog")
+    }
+}
+
+impl Catalog {
+    // the template catalog, the built-in one if there is none
+    pub fn load(root: &Path) -> Result<Self> {
+        let dir = root.join(CATALOG_DIR);
+
+        for file in CATALOG_FILES {
+            let path = dir.join(file);
+
+            if !path.exists() {
+                continue;
+            }
+
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default();
+
+            return Catalog::parse(&fs::read_to_string(&path)?, extension)
+                .map_err(|e| anyhow!("invalid feature catalog {}: {}", path.display(), e));
+        }
+
+        Ok(Catalog::default())
+    }
+
+    pub fn parse(data: &str, format: &str) -> Result<Self> {
+        let catalog: Catalog = match format {
+            "toml" => toml::from_str(data)?,
+            "yaml" | "yml" => serde_yaml::from_str(data)?,
+            _ => bail!("unknown catalog format `{}`", format),
+        };
+
+        for (i, feature) in catalog.features.iter().enumerate() {
+            let duplicate = catalog.features[..i]
+                .iter()
+                .find(|f| f.name == feature.name || (f.flag.is_some() && f.flag == feature.flag));
+
+            if let Some(duplicate) = duplicate {
+                bail!(
+                    "features `{}` and `{}` have the same name or flag",
+                    duplicate.name,
+                    feature.name
+                );
+            }
+
+            let related = feature
+                .conflicts
+                .iter()
+                .chain(feature.implies.iter())
+                .chain(feature.requires.iter());
+
+            for name in related {
+                if name == &feature.name || catalog.get(name).is_none() {
+                    bail!("feature `{}` cannot refer to `{}`", feature.name, name);
+                }
+            }
+        }
+
+        Ok(catalog)
+    }
+
+    pub fn features(&self) -> &[Feature] {
+        &self.features
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Feature> {
+        self.features.iter().find(|f| f.name == name)
+    }
+
+    // by its flag, `no-mailer`, or by its name for
+    // the features this release has no flag for
+    pub fn find_flag(&self, flag: &str) -> Result<&Feature> {
+        let flag = flag.trim_start_matches("--");
+
+        self.features
+            .iter()
+            .filter(|f| f.flag.is_some())
+            .find(|f| f.flag.as_deref() == Some(flag) || f.name == flag)
+            .ok_or_else(|| {
+                let flags = self
+                    .features
+                    .iter()
+                    .filter_map(|f| f.flag.as_deref())
+                    .collect::<Vec<&str>>();
+
+                anyhow!(
+                    "unknown feature `{}`, expected one of: {}",
+                    flag,
+                    flags.join(", ")
+                )
+            })
+    }
+}
+
+impl Feature {
+    pub fn config(&self, app: &str) -> Vec<String> {
+        self.config.iter().map(|c| expand(c, app)).collect()
+    }
+
+    pub fn conflicts(&self) -> &[String] {
+        &self.conflicts
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn env(&self) -> &[String] {
+        &self.env
+    }
+
+    pub fn flag(&self) -> Option<&str> {
+        self.flag.as_deref()
+    }
+
+    pub fn implies(&self) -> &[String] {
+        &self.implies
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn packages(&self) -> &[String] {
+        &self.packages
+    }
+
+    pub fn paths(&self, app: &str) -> Vec<String> {
+        self.paths.iter().map(|p| expand(p, app)).collect()
+    }
+
+    pub fn requires(&self) -> &[String] {
+        &self.requires
+    }
+
+    // `--no-mailer`, or `--without` for those with no flag
+    pub fn cli_flag(&self) -> String {
+        match &self.flag {
+            Some(flag) => format!("--{}", flag),
+            None => format!("--without {}", self.name),
+        }
+    }
+
+    pub fn owns(&self, dep: &Dep) -> bool {
+        let name = dep.name.to_lowercase();
+
+        self.packages.iter().any(|p| matches(p, &name))
+    }
+
+    pub fn owns_env(&self, var: &str) -> bool {
+        self.env.iter().any(|e| matches(e, var))
+    }
+
+    // `Foo.Tracer` takes `Foo.Tracer.start_trace` too
+    pub fn owns_reference(&self, app: &str, lexeme: &str) -> bool {
+        self.config(app).iter().any(|c| {
+            matches(c, lexeme)
+                || lexeme
+                    .strip_prefix(c.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    }
+}
+
+// `spandex*` takes `spandex_datadog` too
+pub fn matches(pattern: &str, value: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => value.starts_with(prefix),
+        None => value == pattern,
+    }
+}
+
+fn expand(template: &str, app: &str) -> String {
+    template
+        .replace("{app}", app)
+        .replace("{module}", &str_utils::to_pascal_case(app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"features:
+  - name: cache
+    flag: no-cache
+    packages: [cachex]
+    paths: ["lib/{app}/cache.ex"]
+    env: ["CACHE_*"]
+"#;
+
+    #[test]
+    fn builtin_catalog() {
+        let catalog = Catalog::default();
+        let dep = |name: &str| Dep::new().set_name(name.to_string()).clone();
+
+        let monitoring = catalog.get("monitoring").unwrap();
+        assert!(monitoring.owns(&dep("spandex_datadog")));
+        assert!(!monitoring.owns(&dep("sentry")));
+
+        assert!(monitoring.owns_reference("foo", "Foo.Tracer.start_trace"));
+        assert!(monitoring.owns_reference("foo", ":spandex_phoenix"));
+        assert!(!monitoring.owns_reference("foo", "Foo.TracerTest"));
+
+        assert!(catalog.get("grpc").unwrap().owns(&dep("gun")));
+        assert!(catalog.get("grpc").unwrap().flag().is_none());
+        assert_eq!(
+            catalog.get("mailer").unwrap().paths("foo_bar"),
+            ["lib/foo_bar_web/mailer"]
+        );
+        assert_eq!(
+            catalog.get("database").unwrap().config("foo_bar")[0],
+            "FooBar.Repo"
+        );
+    }
+
+    #[test]
+    fn find_feature_flag() {
+        let catalog = Catalog::default();
+
+        assert_eq!(catalog.find_flag("no-auth").unwrap().name(), "auth");
+        assert_eq!(catalog.find_flag("--no-mailer").unwrap().name(), "mailer");
+        assert_eq!(
+            catalog.find_flag("http_client").unwrap().name(),
+            "http_client"
+        );
+        // follows `--protocol`
+        assert!(catalog.find_flag("grpc").is_err());
+        assert!(catalog.find_flag("no-cache").is_err());
+    }
+
+    #[test]
+    fn load_template_catalog() {
+        let root = std::env::temp_dir().join("photosphere_load_template_catalog");
+        fs::create_dir_all(root.join(CATALOG_DIR)).unwrap();
+
+        assert_eq!(Catalog::load(&root).unwrap(), Catalog::default());
+
+        fs::write(root.join(CATALOG_DIR).join("features.yml"), YAML).unwrap();
+        let catalog = Catalog::load(&root).unwrap();
+
+        assert_eq!(catalog.features().len(), 1);
+        assert_eq!(catalog.find_flag("no-cache").unwrap().name(), "cache");
+        assert!(catalog.get("cache").unwrap().owns_env("CACHE_TTL"));
+
+        fs::write(root.join(CATALOG_DIR).join("features.toml"), "features = 1").unwrap();
+        assert!(Catalog::load(&root).is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn duplicate_features() {
+        let toml = r#"
+[[features]]
+name = "cache"
+flag = "no-cache"
+
+[[features]]
+name = "redis"
+flag = "no-cache"
+"#;
+
+        assert!(Catalog::parse(toml, "toml").is_err());
+    }
+
+    #[test]
+    fn unknown_related_feature() {
+        let toml = r#"
+[[features]]
+name = "cache"
+requires = ["redis"]
+"#;
+
+        assert!(Catalog::parse(toml, "toml").is_err());
+        assert_eq!(
+            Catalog::default().get("graphql").unwrap().requires(),
+            ["rest"]
+        );
+    }
+}
@@ -0,0 +1,569 @@
+use super::{
+    de,
+    dep::Dep,
+    lock::{Lock, LockSource},
+    project::MixProject,
+};
+use crate::Format;
+use anyhow::{bail, Result};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path::Path,
+};
+
+// The project deps from `mix.exs` as roots and
+// every locked package below them, edges come
+// from the nested deps `mix.lock` keeps
+#[derive(Clone, Debug)]
+pub struct DepGraph {
+    pub(super) app: String,
+    pub(super) nodes: BTreeMap<String, Node>,
+    pub(super) roots: Vec<Edge>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    pub(super) deps: Vec<Edge>,
+    pub(super) name: String,
+    pub(super) version: Option<String>, // none when not locked
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub(super) name: String,
+    pub(super) optional: bool,
+    pub(super) requirement: Option<String>,
+}
+
+impl DepGraph {
+    pub fn new(app: &str, deps: &[Dep], lock: Option<&Lock>) -> Self {
+        let mut nodes = BTreeMap::new();
+
+        for entry in lock.map(Lock::entries).unwrap_or_default() {
+            let version = match entry.source() {
+                LockSource::Hex(hex) => hex.version.to_string(),
+                LockSource::Git(git) => match git.tag() {
+                    Some(tag) => tag.to_string(),
+                    None => git.revision.chars().take(7).collect(),
+                },
+            };
+            let deps = entry
+                .deps()
+                .iter()
+                .map(|dep| Edge {
+                    name: dep.name().to_string(),
+                    optional: dep.optional(),
+                    requirement: dep.requirement().map(|r| r.to_string()),
+                })
+                .collect();
+
+            nodes.insert(
+                entry.name().to_string(),
+                Node {
+                    deps,
+                    name: entry.name().to_string(),
+                    version: Some(version),
+                },
+            );
+        }
+
+        let roots = deps
+            .iter()
+            .map(|dep| Edge {
+                name: dep.name.to_string(),
+                optional: dep.optional.unwrap_or(false),
+                requirement: dep.version.as_ref().map(|v| v.to_string()),
+            })
+            .collect::<Vec<Edge>>();
+
+        // unlocked deps are still part of the graph
+        for root in roots.iter() {
+            nodes.entry(root.name.to_string()).or_insert_with(|| Node {
+                deps: vec![],
+                name: root.name.to_string(),
+                version: None,
+            });
+        }
+
+        DepGraph {
+            app: app.to_string(),
+            nodes,
+            roots,
+        }
+    }
+
+    pub fn load(root: &Path) -> Result<Self> {
+        let project = MixProject::load(root)?;
+        let deps = de::parse_deps(root)?;
+        let lock = match root.join("mix.lock").exists() {
+            true => Some(Lock::load(root)?),
+            false => None,
+        };
+
+        Ok(DepGraph::new(project.app(), &deps, lock.as_ref()))
+    }
+
+    pub fn app(&self) -> &str {
+        &self.app
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Node> {
+        self.nodes.get(name)
+    }
+
+    pub fn roots(&self) -> &[Edge] {
+        &self.roots
+    }
+
+    // everything `name` pulls in, directly or not
+    pub fn dependencies(&self, name: &str) -> Vec<&str> {
+        let mut reachable = self.reachable(&[name]);
+        reachable.remove(name);
+
+        reachable.into_iter().collect()
+    }
+
+    // every chain of deps from the project down to
+    // `name`, the answer to "why is it here"
+    pub fn paths(&self, name: &str) -> Vec<Vec<&str>> {
+        let mut paths = vec![];
+
+        for root in self.roots.iter() {
+            self.collect_paths(&root.name, name, &mut vec![self.app.as_str()], &mut paths);
+        }
+
+        paths
+    }
+
+    // what only `name` keeps in the project, itself
+    // included; removing it would orphan them all
+    pub fn orphans(&self, name: &str) -> Vec<&str> {
+        let others = self
+            .roots
+            .iter()
+            .map(|r| r.name.as_str())
+            .filter(|r| !r.eq(&name))
+            .collect::<Vec<&str>>();
+        let kept = self.reachable(&others);
+
+        self.reachable(&[name])
+            .into_iter()
+            .filter(|n| !kept.contains(n))
+            .collect()
+    }
+
+    // the whole project, or what `from` pulls in
+    pub fn render_tree(&self, from: Option<&str>, format: Format) -> Result<String> {
+        let (name, edges) = match from {
+            Some(from) => match self.get(from) {
+                Some(node) => (from, node.deps.as_slice()),
+                None => bail!("`{}` is not a dependency of {}", from, self.app),
+            },
+            None => (self.app.as_str(), self.roots.as_slice()),
+        };
+
+        let output = match format {
+            Format::Text => {
+                let mut lines = vec![self.label(name, None)];
+                self.text_tree(edges, "", &mut vec![name], &mut lines);
+
+                lines.join("\n")
+            }
+            Format::Dot => {
+                let mut seen = HashSet::new();
+                let mut lines = vec![];
+                self.dot_edges(name, edges, &mut seen, &mut lines);
+
+                dot(name, lines)
+            }
+            Format::Json => {
+                let deps = self.json_tree(edges, &mut vec![name]);
+                let version = self.get(name).and_then(|n| n.version.as_deref());
+
+                pretty(json!({ "name": name, "version": version, "deps": deps }))?
+            }
+        };
+
+        Ok(output)
+    }
+
+    pub fn render_why(&self, name: &str, format: Format) -> Result<String> {
+        if self.get(name).is_none() {
+            bail!("`{}` is not a dependency of {}", name, self.app);
+        }
+
+        let paths = self.paths(name);
+
+        let output = match format {
+            Format::Text => paths
+                .iter()
+                .map(|path| path.join(" > "))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Format::Dot => {
+                let edges = paths
+                    .iter()
+                    .flat_map(|path| path.windows(2))
+                    .map(|pair| format!("  \"{}\" -> \"{}\";", pair[0], pair[1]))
+                    .collect::<BTreeSet<String>>();
+
+                dot(name, edges.into_iter().collect())
+            }
+            Format::Json => pretty(json!({ "name": name, "paths": paths }))?,
+        };
+
+        Ok(output)
+    }
+
+    fn reachable(&self, roots: &[&str]) -> BTreeSet<&str> {
+        let mut seen = BTreeSet::new();
+        let mut stack = roots
+            .iter()
+            .filter_map(|r| self.nodes.get(*r))
+            .collect::<Vec<&Node>>();
+
+        while let Some(node) = stack.pop() {
+            if seen.insert(node.name.as_str()) {
+                stack.extend(node.deps.iter().filter_map(|e| self.nodes.get(&e.name)));
+            }
+        }
+
+        seen
+    }
+
+    fn collect_paths<'a>(
+        &'a self,
+        current: &'a str,
+        target: &str,
+        path: &mut Vec<&'a str>,
+        paths: &mut Vec<Vec<&'a str>>,
+    ) {
+        // locks have no cycles, but don't hang on one
+        if path.contains(&current) {
+            return;
+        }
+
+        path.push(current);
+
+        if current == target {
+            paths.push(path.clone());
+        } else if let Some(node) = self.nodes.get(current) {
+            for edge in node.deps.iter() {
+                self.collect_paths(&edge.name, target, path, paths);
+            }
+        }
+
+        path.pop();
+    }
+
+    // `name requirement (version)`, like `mix deps.tree`
+    fn label(&self, name: &str, edge: Option<&Edge>) -> String {
+        let mut label = name.to_string();
+
+        if let Some(requirement) = edge.and_then(|e| e.requirement.as_ref()) {
+            label.push_str(&format!(" {}", requirement));
+        }
+
+        if let Some(version) = self.get(name).and_then(|n| n.version.as_ref()) {
+            label.push_str(&format!(" ({})", version));
+        }
+
+        if edge.is_some_and(|e| e.optional) {
+            label.push_str(" *optional*");
+        }
+
+        label
+    }
+
+    fn text_tree<'a>(
+        &'a self,
+        edges: &'a [Edge],
+        prefix: &str,
+        ancestors: &mut Vec<&'a str>,
+        lines: &mut Vec<String>,
+    ) {
+        for (i, edge) in edges.iter().enumerate() {
+            let last = i == edges.len() - 1;
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+
+            lines.push(format!(
+                "{}{}{}",
+                prefix,
+                branch,
+                self.label(&edge.name, Some(edge))
+            ));
+
+            match self.nodes.get(&edge.name) {
+                Some(node) if !ancestors.contains(&node.name.as_str()) => {
+                    ancestors.push(&node.name);
+                    self.text_tree(
+                        &node.deps,
+                        &format!("{}{}", prefix, indent),
+                        ancestors,
+                        lines,
+                    );
+                    ancestors.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn dot_edges<'a>(
+        &'a self,
+        from: &'a str,
+        edges: &'a [Edge],
+        seen: &mut HashSet<&'a str>,
+        lines: &mut Vec<String>,
+    ) {
+        if !seen.insert(from) {
+            return;
+        }
+
+        for edge in edges {
+            let label = edge.requirement.as_deref().unwrap_or_default();
+            lines.push(format!(
+                "  \"{}\" -> \"{}\" [label=\"{}\"];",
+                from, edge.name, label
+            ));
+
+            if let Some(node) = self.nodes.get(&edge.name) {
+                self.dot_edges(&node.name, &node.deps, seen, lines);
+            }
+        }
+    }
+
+    fn json_tree<'a>(&'a self, edges: &'a [Edge], ancestors: &mut Vec<&'a str>) -> Vec<Value> {
+        edges
+            .iter()
+            .map(|edge| {
+                let node = self.nodes.get(&edge.name);
+                let deps = match node {
+                    Some(node) if !ancestors.contains(&node.name.as_str()) => {
+                        ancestors.push(&node.name);
+                        let deps = self.json_tree(&node.deps, ancestors);
+                        ancestors.pop();
+
+                        deps
+                    }
+                    _ => vec![],
+                };
+
+                json!({
+                    "name": edge.name,
+                    "requirement": edge.requirement,
+                    "version": node.and_then(|n| n.version.as_deref()),
+                    "optional": edge.optional,
+                    "deps": deps,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Node {
+    pub fn deps(&self) -> &[Edge] {
+        &self.deps
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+}
+
+impl Edge {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn optional(&self) -> bool {
+        self.optional
+    }
+
+    pub fn requirement(&self) -> Option<&str> {
+        self.requirement.as_deref()
+    }
+}
+
+fn dot(name: &str, lines: Vec<String>) -> String {
+    format!("digraph \"{}\" {{\n{}\n}}", name, lines.join("\n"))
+}
+
+fn pretty(value: Value) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const MIX_EXS: &str = r#"defmodule Foo.MixProject do
+  use Mix.Project
+
+  def project, do: [app: :foo, deps: deps()]
+
+  defp deps do
+    [
+      {:spandex_phoenix, "~> 1.0"},
+      {:plug_cowboy, "~> 2.5"},
+      {:database, git: "git@github.com:solfacil/database.git", tag: "0.0.7"}
+    ]
+  end
+end
+"#;
+
+    const MIX_LOCK: &str = r#"%{
+  "cowboy": {:hex, :cowboy, "2.9.0", "865dd8b6", [:make, :rebar3], [{:cowlib, "2.11.0", [hex: :cowlib, repo: "hexpm", optional: false]}], "hexpm", "2c729f93"},
+  "cowlib": {:hex, :cowlib, "2.11.0", "0b9ff9c3", [:make, :rebar3], [], "hexpm", "2b3e9da0"},
+  "database": {:git, "git@github.com:solfacil/database.git", "9d3c51d6e0a4", [tag: "0.0.7"]},
+  "mime": {:hex, :mime, "2.0.2", "0b9e1a4c", [:mix], [], "hexpm", "e6a3f76b"},
+  "plug": {:hex, :plug, "1.13.6", "187beb6b", [:mix], [{:mime, "~> 1.0 or ~> 2.0", [hex: :mime, repo: "hexpm", optional: false]}], "hexpm", "02b9c640"},
+  "plug_cowboy": {:hex, :plug_cowboy, "2.5.2", "62894ccd", [:mix], [{:cowboy, "~> 2.7", [hex: :cowboy, repo: "hexpm", optional: false]}, {:plug, "~> 1.7", [hex: :plug, repo: "hexpm", optional: false]}], "hexpm", "ea6e87f7"},
+  "spandex": {:hex, :spandex, "3.1.0", "8e4a8b3c", [:mix], [{:plug, ">= 1.0.0", [hex: :plug, repo: "hexpm", optional: true]}], "hexpm", "9c2a5b1d"},
+  "spandex_phoenix": {:hex, :spandex_phoenix, "1.0.6", "2a2e4f7a", [:mix], [{:plug, ">= 1.0.0", [hex: :plug, repo: "hexpm", optional: false]}, {:spandex, "~> 2.2 or ~> 3.0", [hex: :spandex, repo: "hexpm", optional: false]}], "hexpm", "8f6a4b3d"},
+}
+"#;
+
+    fn graph(name: &str) -> DepGraph {
+        let root = std::env::temp_dir().join(name);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("mix.exs"), MIX_EXS).unwrap();
+        fs::write(root.join("mix.lock"), MIX_LOCK).unwrap();
+
+        let graph = DepGraph::load(&root).unwrap();
+        fs::remove_dir_all(root).unwrap();
+
+        graph
+    }
+
+    #[test]
+    fn load_graph() {
+        let graph = graph("photosphere_load_graph");
+
+        assert_eq!(graph.app(), "foo");
+        assert_eq!(graph.roots().len(), 3);
+        assert_eq!(graph.get("database").unwrap().version(), Some("0.0.7"));
+        assert_eq!(
+            graph.get("plug").unwrap().deps()[0].requirement(),
+            Some("~> 1.0 or ~> 2.0")
+        );
+    }
+
+    #[test]
+    fn unlocked_graph() {
+        let deps = vec![Dep::new().set_name("jason".to_string()).clone()];
+
+        let graph = DepGraph::new("foo", &deps, None);
+
+        assert_eq!(graph.get("jason").unwrap().version(), None);
+        assert!(graph.dependencies("jason").is_empty());
+    }
+
+    #[test]
+    fn dependencies() {
+        let graph = graph("photosphere_graph_dependencies");
+
+        assert_eq!(
+            graph.dependencies("spandex_phoenix"),
+            ["mime", "plug", "spandex"]
+        );
+        assert!(graph.dependencies("database").is_empty());
+    }
+
+    #[test]
+    fn why_paths() {
+        let graph = graph("photosphere_graph_why_paths");
+
+        assert_eq!(
+            graph.paths("cowlib"),
+            [["foo", "plug_cowboy", "cowboy", "cowlib"]]
+        );
+        assert_eq!(graph.paths("plug").len(), 3);
+        assert!(graph.paths("unknown").is_empty());
+    }
+
+    #[test]
+    fn orphans() {
+        let graph = graph("photosphere_graph_orphans");
+
+        // plug is still pulled in by plug_cowboy
+        assert_eq!(
+            graph.orphans("spandex_phoenix"),
+            ["spandex", "spandex_phoenix"]
+        );
+        assert_eq!(
+            graph.orphans("plug_cowboy"),
+            ["cowboy", "cowlib", "plug_cowboy"]
+        );
+    }
+
+    #[test]
+    fn render_text_tree() {
+        let graph = graph("photosphere_render_text_tree");
+
+        let expected = "spandex_phoenix (1.0.6)
+├── plug >= 1.0.0 (1.13.6)
+│   └── mime ~> 1.0 or ~> 2.0 (2.0.2)
+└── spandex ~> 2.2 or ~> 3.0 (3.1.0)
+    └── plug >= 1.0.0 (1.13.6) *optional*
+        └── mime ~> 1.0 or ~> 2.0 (2.0.2)";
+
+        assert_eq!(
+            graph
+                .render_tree(Some("spandex_phoenix"), Format::Text)
+                .unwrap(),
+            expected
+        );
+        assert!(graph.render_tree(Some("unknown"), Format::Text).is_err());
+    }
+
+    #[test]
+    fn render_dot_tree() {
+        let graph = graph("photosphere_render_dot_tree");
+
+        let dot = graph.render_tree(Some("plug_cowboy"), Format::Dot).unwrap();
+
+        assert!(dot.starts_with("digraph \"plug_cowboy\" {\n"));
+        assert!(dot.contains("  \"cowboy\" -> \"cowlib\" [label=\"2.11.0\"];"));
+        assert_eq!(dot.matches("->").count(), 4);
+    }
+
+    #[test]
+    fn render_json_tree() {
+        let graph = graph("photosphere_render_json_tree");
+
+        let json = graph.render_tree(None, Format::Json).unwrap();
+        let value: Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["name"], "foo");
+        assert_eq!(value["deps"][1]["deps"][0]["name"], "cowboy");
+        assert_eq!(value["deps"][2]["version"], "0.0.7");
+    }
+
+    #[test]
+    fn render_why() {
+        let graph = graph("photosphere_render_why");
+
+        assert_eq!(
+            graph.render_why("mime", Format::Text).unwrap(),
+            "foo > spandex_phoenix > plug > mime
+foo > spandex_phoenix > spandex > plug > mime
+foo > plug_cowboy > plug > mime"
+        );
+
+        let json: Value =
+            serde_json::from_str(&graph.render_why("cowlib", Format::Json).unwrap()).unwrap();
+        assert_eq!(json["paths"][0][1], "plug_cowboy");
+        assert!(graph.render_why("unknown", Format::Dot).is_err());
+    }
+}
@@ -0,0 +1,548 @@
+use super::{de, version::VersionRequirement, Service};
+use crate::parser::{
+    ast::Expr,
+    cst::{SyntaxKind, SyntaxNode, SyntaxTree},
+};
+use anyhow::{anyhow, bail, Result};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// `mix.lock`, a map from app names to the
+// exact package or checkout mix resolved;
+// entries are removed from the tree in place
+#[derive(Clone, Debug)]
+pub struct Lock {
+    pub(super) entries: Vec<LockEntry>,
+    pub(super) path: PathBuf,
+    pub(super) tree: SyntaxTree,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockEntry {
+    pub(super) name: String,
+    pub(super) source: LockSource,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LockSource {
+    Git(GitLock),
+    Hex(HexLock),
+}
+
+// `{:hex, :pkg, "1.0.0", "inner", [:mix], [deps], "hexpm", "outer"}`,
+// older locks stop before the repo or the outer checksum
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HexLock {
+    pub(super) checksum: String,
+    pub(super) deps: Vec<LockDep>,
+    pub(super) managers: Vec<String>,
+    pub(super) outer_checksum: Option<String>,
+    pub(super) package: String,
+    pub(super) repo: String,
+    pub(super) version: String,
+}
+
+// `{:git, "url", "sha", [tag: "0.0.7"]}`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitLock {
+    pub(super) branch: Option<String>,
+    pub(super) git_ref: Option<String>,
+    pub(super) revision: String,
+    pub(super) submodules: bool,
+    pub(super) tag: Option<String>,
+    pub(super) url: String,
+}
+
+// `{:plug, "~> 1.7", [hex: :plug, repo: "hexpm", optional: true]}`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockDep {
+    pub(super) name: String,
+    pub(super) optional: bool,
+    pub(super) package: Option<String>, // the `hex` name when renamed
+    pub(super) repo: Option<String>,
+    pub(super) requirement: Option<VersionRequirement>,
+}
+
+impl Lock {
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join("mix.lock");
+        let tree = SyntaxTree::parse(&fs::read_to_string(&path)?)?;
+
+        Ok(Lock {
+            entries: parse_entries(&tree)?,
+            path,
+            tree,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::write(&self.path, self.tree.to_string())?;
+
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[LockEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LockEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    // drops what only `removed` deps brought in; whatever a
+    // `kept` dep still reaches stays, and so do entries no root
+    // reaches, git deps don't list theirs in the lock
+    pub fn prune(&mut self, removed: &[&str], kept: &[&str]) -> Result<Vec<String>> {
+        let kept = self.reachable(kept);
+        let pruned = self
+            .reachable(removed)
+            .into_iter()
+            .filter(|name| !kept.contains(name))
+            .collect::<HashSet<String>>();
+
+        let ranges = entry_nodes(&self.tree)?
+            .iter()
+            .zip(self.entries.iter())
+            .filter(|(_, entry)| pruned.contains(&entry.name))
+            .map(|(node, _)| node.range())
+            .collect::<Vec<_>>();
+
+        // from the end, so earlier ranges stay valid
+        for range in ranges.into_iter().rev() {
+            self.tree.remove(range)?;
+        }
+
+        let (removed, entries) = self
+            .entries
+            .drain(..)
+            .partition(|entry| pruned.contains(&entry.name));
+        self.entries = entries;
+
+        Ok(removed
+            .into_iter()
+            .map(|entry: LockEntry| entry.name)
+            .collect())
+    }
+
+    // the roots and every locked dep below them
+    fn reachable(&self, roots: &[&str]) -> HashSet<String> {
+        let mut seen = HashSet::new();
+        let mut stack = roots.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+
+        while let Some(name) = stack.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+
+            if let Some(entry) = self.get(&name) {
+                stack.extend(entry.deps().iter().map(|d| d.name.to_string()));
+            }
+        }
+
+        seen.retain(|name| self.get(name).is_some());
+
+        seen
+    }
+}
+
+impl LockEntry {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &LockSource {
+        &self.source
+    }
+
+    pub fn deps(&self) -> &[LockDep] {
+        match &self.source {
+            LockSource::Hex(hex) => &hex.deps,
+            LockSource::Git(_) => &[],
+        }
+    }
+
+    // the locked hex version or the git revision
+    pub fn version(&self) -> &str {
+        match &self.source {
+            LockSource::Hex(hex) => &hex.version,
+            LockSource::Git(git) => &git.revision,
+        }
+    }
+}
+
+impl HexLock {
+    pub fn checksum(&self) -> &str {
+        &self.checksum
+    }
+
+    pub fn managers(&self) -> &[String] {
+        &self.managers
+    }
+
+    pub fn outer_checksum(&self) -> Option<&str> {
+        self.outer_checksum.as_deref()
+    }
+
+    pub fn package(&self) -> &str {
+        &self.package
+    }
+
+    pub fn repo(&self) -> &str {
+        &self.repo
+    }
+}
+
+impl GitLock {
+    pub fn branch(&self) -> Option<&str> {
+        self.branch.as_deref()
+    }
+
+    pub fn git_ref(&self) -> Option<&str> {
+        self.git_ref.as_deref()
+    }
+
+    pub fn submodules(&self) -> bool {
+        self.submodules
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl LockDep {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn optional(&self) -> bool {
+        self.optional
+    }
+
+    pub fn package(&self) -> &str {
+        self.package.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn repo(&self) -> Option<&str> {
+        self.repo.as_deref()
+    }
+
+    pub fn requirement(&self) -> Option<&VersionRequirement> {
+        self.requirement.as_ref()
+    }
+}
+
+// keeps the template lock for the deps the
+// service still has, instead of deleting it
+pub fn prune_lock(service: &Service) -> Result<()> {
+    if !service.path.join("mix.lock").exists() {
+        return Ok(());
+    }
+
+    let template_deps = de::parse_deps(&service.path)?;
+    let kept = service
+        .deps
+        .iter()
+        .map(|d| d.name.as_str())
+        .collect::<Vec<&str>>();
+    let removed = template_deps
+        .iter()
+        .map(|d| d.name.as_str())
+        .filter(|name| !kept.contains(name))
+        .collect::<Vec<&str>>();
+
+    let mut lock = Lock::load(&service.path)?;
+    lock.prune(&removed, &kept)?;
+    lock.save()
+}
+
+fn entry_nodes(tree: &SyntaxTree) -> Result<&[SyntaxNode]> {
+    tree.root()
+        .descendants()
+        .into_iter()
+        .find(|n| n.kind() == SyntaxKind::Map)
+        .map(SyntaxNode::children)
+        .ok_or_else(|| anyhow!("mix.lock is not a map"))
+}
+
+fn parse_entries(tree: &SyntaxTree) -> Result<Vec<LockEntry>> {
+    entry_nodes(tree)?
+        .iter()
+        .map(
+            |node| match tree.expr(node)?.as_tuple().map(Vec::as_slice) {
+                Some([Expr::Atom(name), value]) => parse_entry(name, value),
+                _ => bail!("`{}` is not a mix.lock entry", tree.text(node.range())),
+            },
+        )
+        .collect()
+}
+
+fn parse_entry(name: &str, value: &Expr) -> Result<LockEntry> {
+    let items = value
+        .as_tuple()
+        .ok_or_else(|| anyhow!("`{}` lock entry must be a tuple", name))?;
+    let string = |index: usize| -> Result<String> {
+        items
+            .get(index)
+            .and_then(Expr::as_string)
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("malformed `{}` lock entry", name))
+    };
+
+    let source = match items.first().and_then(Expr::as_atom) {
+        Some("hex") if (6..=8).contains(&items.len()) => LockSource::Hex(HexLock {
+            checksum: string(3)?,
+            deps: match items[5].as_list() {
+                Some(deps) => deps
+                    .iter()
+                    .map(|d| parse_lock_dep(name, d))
+                    .collect::<Result<_>>()?,
+                None => bail!("malformed `{}` lock entry deps", name),
+            },
+            managers: parse_atoms(&items[4])
+                .ok_or_else(|| anyhow!("malformed `{}` lock entry managers", name))?,
+            outer_checksum: string(7).ok(),
+            package: items[1]
+                .as_atom()
+                .ok_or_else(|| anyhow!("malformed `{}` lock entry package", name))?
+                .to_string(),
+            repo: string(6).unwrap_or_else(|_| "hexpm".to_string()),
+            version: string(2)?,
+        }),
+        Some("git") if items.len() == 4 => {
+            let options = items[3]
+                .as_keyword()
+                .ok_or_else(|| anyhow!("malformed `{}` lock entry options", name))?;
+            let option = |key: &str| {
+                options
+                    .iter()
+                    .find(|(k, _)| k.eq(&key))
+                    .and_then(|(_, value)| value.as_string())
+                    .map(str::to_string)
+            };
+
+            LockSource::Git(GitLock {
+                branch: option("branch"),
+                git_ref: option("ref"),
+                revision: string(2)?,
+                submodules: options
+                    .iter()
+                    .any(|(k, v)| k.eq(&"submodules") && v.as_bool() == Some(true)),
+                tag: option("tag"),
+                url: string(1)?,
+            })
+        }
+        Some(scm) => bail!("unsupported `{}` lock entry for `{}`", scm, name),
+        None => bail!("malformed `{}` lock entry", name),
+    };
+
+    Ok(LockEntry {
+        name: name.to_string(),
+        source,
+    })
+}
+
+fn parse_lock_dep(entry: &str, dep: &Expr) -> Result<LockDep> {
+    let (name, requirement, options) = match dep.as_tuple().map(Vec::as_slice) {
+        Some([Expr::Atom(name), requirement, options]) => (name, requirement, options),
+        _ => bail!("malformed dependency in `{}` lock entry", entry),
+    };
+
+    let requirement = match requirement {
+        Expr::Nil => None,
+        Expr::String(requirement) => Some(VersionRequirement::parse(requirement)?),
+        _ => bail!("malformed `{}` requirement in `{}` lock entry", name, entry),
+    };
+    let options = options
+        .as_keyword()
+        .ok_or_else(|| anyhow!("malformed `{}` options in `{}` lock entry", name, entry))?;
+    let get = |key: &str| options.iter().find(|(k, _)| k.eq(&key)).map(|(_, v)| *v);
+
+    Ok(LockDep {
+        name: name.to_string(),
+        optional: get("optional").and_then(Expr::as_bool).unwrap_or(false),
+        package: get("hex").and_then(Expr::as_atom).map(str::to_string),
+        repo: get("repo").and_then(Expr::as_string).map(str::to_string),
+        requirement,
+    })
+}
+
+fn parse_atoms(value: &Expr) -> Option<Vec<String>> {
+    value
+        .as_list()?
+        .iter()
+        .map(|atom| atom.as_atom().map(str::to_string))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photosphere::service::dep::Dep;
+
+    const MIX_LOCK: &str = r#"%{
+  "absinthe": {:hex, :absinthe, "1.6.8", "a4c2b1c3", [:mix], [{:dataloader, "~> 1.0.0", [hex: :dataloader, repo: "hexpm", optional: true]}, {:nimble_parsec, "~> 0.5 or ~> 1.0", [hex: :nimble_parsec, repo: "hexpm", optional: false]}, {:telemetry, "~> 1.0 or ~> 0.4", [hex: :telemetry, repo: "hexpm", optional: false]}], "hexpm", "e7f9a1b2"},
+  "database": {:git, "git@github.com:solfacil/database.git", "9d3c51d6e0a4", [tag: "0.0.7"]},
+  "dataloader": {:hex, :dataloader, "1.0.10", "a42f07641", [:mix], [{:telemetry, "~> 1.0 or ~> 0.4", [hex: :telemetry, repo: "hexpm", optional: false]}], "hexpm", "54cd70cec"},
+  "gun": {:hex, :grpc_gun, "2.0.1", "221b792df", [:make, :rebar3], [{:cowlib, "~> 2.11", [hex: :cowlib, repo: "hexpm", optional: false]}], "hexpm", "795a65a86"},
+  "cowlib": {:hex, :cowlib, "2.11.0", "0b9ff9c3", [:make, :rebar3], [], "hexpm", "2b3e9da0"},
+  "nimble_parsec": {:hex, :nimble_parsec, "1.2.3", "244836e6", [:mix], [], "hexpm", "c8d789e3"},
+  "telemetry": {:hex, :telemetry, "1.1.0", "a589817", [:rebar3], [], "hexpm", "b727b2a1"},
+}
+"#;
+
+    fn write_lock(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(name);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("mix.lock"), MIX_LOCK).unwrap();
+
+        root
+    }
+
+    #[test]
+    fn parse_hex_entry() {
+        let root = write_lock("photosphere_parse_hex_entry");
+        let lock = Lock::load(&root).unwrap();
+
+        let gun = lock.get("gun").unwrap();
+        let absinthe = lock.get("absinthe").unwrap();
+
+        assert_eq!(lock.entries().len(), 7);
+        assert_eq!(gun.version(), "2.0.1");
+        assert!(matches!(gun.source(), LockSource::Hex(hex)
+            if hex.package() == "grpc_gun" && hex.managers() == ["make", "rebar3"]
+                && hex.outer_checksum() == Some("795a65a86")));
+        assert_eq!(absinthe.deps().len(), 3);
+        assert!(absinthe.deps()[0].optional());
+        assert_eq!(
+            absinthe.deps()[1].requirement().unwrap().to_string(),
+            "~> 0.5 or ~> 1.0"
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn parse_git_entry() {
+        let root = write_lock("photosphere_parse_git_entry");
+        let lock = Lock::load(&root).unwrap();
+
+        let database = lock.get("database").unwrap();
+
+        assert_eq!(database.version(), "9d3c51d6e0a4");
+        assert!(database.deps().is_empty());
+        assert!(matches!(database.source(), LockSource::Git(git)
+            if git.url() == "git@github.com:solfacil/database.git"
+                && git.tag() == Some("0.0.7") && git.branch().is_none()));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn parse_old_hex_entry() {
+        let tree =
+            SyntaxTree::parse(r#"%{"old": {:hex, :old, "0.1.0", "3f2a1b", [:mix], []}}"#).unwrap();
+
+        let entries = parse_entries(&tree).unwrap();
+
+        assert!(matches!(&entries[0].source, LockSource::Hex(hex)
+            if hex.repo() == "hexpm" && hex.outer_checksum().is_none()));
+    }
+
+    #[test]
+    fn parse_invalid_lock() {
+        let short = SyntaxTree::parse(r#"%{"old": {:hex, :old, "0.1.0", "3f2a1b"}}"#).unwrap();
+        let path = SyntaxTree::parse(r#"%{"nif": {:path, "../nif"}}"#).unwrap();
+
+        assert!(parse_entries(&short).is_err());
+        assert!(parse_entries(&path).is_err());
+    }
+
+    #[test]
+    fn prune_entries() {
+        let root = write_lock("photosphere_prune_entries");
+        let mut lock = Lock::load(&root).unwrap();
+
+        let mut removed = lock.prune(&["absinthe"], &["gun", "database"]).unwrap();
+        removed.sort();
+
+        // telemetry is not reached by a kept dep either
+        assert_eq!(
+            removed,
+            ["absinthe", "dataloader", "nimble_parsec", "telemetry"]
+        );
+        assert_eq!(lock.entries().len(), 3);
+
+        lock.save().unwrap();
+        let expected = MIX_LOCK
+            .lines()
+            .filter(|l| {
+                !["absinthe", "dataloader", "nimble_parsec", "telemetry"]
+                    .iter()
+                    .any(|k| l.starts_with(&format!("  \"{}\"", k)))
+            })
+            .map(|l| format!("{}\n", l))
+            .collect::<String>();
+        assert_eq!(fs::read_to_string(root.join("mix.lock")).unwrap(), expected);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn prune_shared_entries() {
+        let root = write_lock("photosphere_prune_shared_entries");
+        let mut lock = Lock::load(&root).unwrap();
+
+        let removed = lock.prune(&["dataloader"], &["absinthe"]).unwrap();
+
+        assert!(removed.is_empty());
+        assert_eq!(lock.entries().len(), 7);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn prune_service_lock() {
+        let root = write_lock("photosphere_prune_service_lock");
+        fs::write(
+            root.join("mix.exs"),
+            r#"defmodule Foo.MixProject do
+  use Mix.Project
+
+  defp deps do
+    [
+      {:absinthe, "~> 1.6.0"},
+      {:gun, "~> 2.0.0", repo: "hexpm", hex: "grpc_gun", override: true}
+    ]
+  end
+end
+"#,
+        )
+        .unwrap();
+
+        let mut service = Service::default();
+        let deps = de::parse_deps(&root)
+            .unwrap()
+            .into_iter()
+            .filter(|d: &Dep| d.name != "absinthe")
+            .collect();
+        service.set_path(root.display().to_string()).set_deps(deps);
+
+        prune_lock(&service).unwrap();
+
+        let lock = Lock::load(&root).unwrap();
+        assert!(lock.get("absinthe").is_none());
+        assert!(lock.get("cowlib").is_some());
+        // not reached from the template deps
+        assert!(lock.get("database").is_some());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+use super::{
+    de,
+    dep::Dep,
+    lock::{Lock, LockSource},
+    registry::Registry,
+    version::{Version, VersionRequirement},
+};
+use crate::parser::{ast, cst::SyntaxKind, cst::SyntaxTree};
+use anyhow::Result;
+use std::{fmt, fs, path::Path};
+
+// a declared requirement behind the newest release
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outdated {
+    pub(super) allowed: bool, // the requirement already takes `latest`
+    pub(super) current: Version,
+    pub(super) drift: Drift,
+    pub(super) latest: Version,
+    pub(super) name: String,
+    pub(super) requirement: VersionRequirement,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Drift {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl Outdated {
+    pub fn allowed(&self) -> bool {
+        self.allowed
+    }
+
+    pub fn current(&self) -> &Version {
+        &self.current
+    }
+
+    pub fn drift(&self) -> Drift {
+        self.drift
+    }
+
+    pub fn latest(&self) -> &Version {
+        &self.latest
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn requirement(&self) -> &VersionRequirement {
+        &self.requirement
+    }
+}
+
+impl Drift {
+    fn between(current: &Version, latest: &Version) -> Option<Self> {
+        if latest <= current {
+            return None;
+        }
+
+        match (latest.major(), latest.minor(), latest.patch()) {
+            (major, _, _) if major != current.major() => Some(Drift::Major),
+            (_, minor, _) if minor != current.minor() => Some(Drift::Minor),
+            _ => Some(Drift::Patch),
+        }
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Drift::Patch => write!(f, "patch"),
+            Drift::Minor => write!(f, "minor"),
+            Drift::Major => write!(f, "major"),
+        }
+    }
+}
+
+// hex deps whose newest release is past the locked
+// version, or past the lowest one the requirement
+// takes when there is no lock
+pub fn outdated(deps: &[Dep], registry: &Registry, lock: Option<&Lock>) -> Vec<Outdated> {
+    deps.iter()
+        .filter(|dep| !dep.is_scm())
+        .filter_map(|dep| {
+            let requirement = dep.version.as_ref()?;
+            let latest = registry.get(dep.package())?.latest()?.version();
+
+            let locked =
+                lock.and_then(|lock| lock.get(&dep.name))
+                    .and_then(|entry| match entry.source() {
+                        LockSource::Hex(hex) => Version::parse(&hex.version).ok(),
+                        LockSource::Git(_) => None,
+                    });
+            let current = locked.or_else(|| requirement.minimum().cloned())?;
+
+            Some(Outdated {
+                allowed: requirement.matches(latest),
+                drift: Drift::between(&current, latest)?,
+                current,
+                latest: latest.clone(),
+                name: dep.name.to_string(),
+                requirement: requirement.clone(),
+            })
+        })
+        .collect()
+}
+
+// moves the requirements that don't take the newest
+// release yet, only their strings change in mix.exs;
+// the ones actually rewritten are returned
+pub fn update_requirements(root: &Path, outdated: &[Outdated]) -> Result<Vec<Outdated>> {
+    let path = root.join("mix.exs");
+    let mut tree = SyntaxTree::parse(&fs::read_to_string(&path)?)?;
+
+    let updates = outdated
+        .iter()
+        .filter(|o| !o.allowed)
+        .collect::<Vec<&Outdated>>();
+
+    let edits = de::parse_dep_nodes(&tree)?
+        .iter()
+        .zip(de::deps_node(&tree)?.children())
+        .filter_map(|((_, dep), node)| {
+            let update = updates.iter().find(|o| o.name == dep.name)?;
+            let requirement = node
+                .children()
+                .get(1)
+                .filter(|n| n.kind() == SyntaxKind::String)?;
+            let bumped = update.requirement.bump(&update.latest)?;
+
+            Some((requirement.range(), bumped, (*update).clone()))
+        })
+        .collect::<Vec<_>>();
+
+    // from the end, so earlier ranges stay valid
+    for (range, requirement, _) in edits.iter().rev() {
+        tree.replace(range.clone(), &ast::quote(&requirement.to_string(), '"'))?;
+    }
+
+    fs::write(&path, tree.to_string())?;
+
+    Ok(edits.into_iter().map(|(_, _, update)| update).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photosphere::service::registry::tests::write_registry;
+
+    const MIX_EXS: &str = r#"defmodule Foo.MixProject do
+  use Mix.Project
+
+  def project, do: [app: :foo, deps: deps()]
+
+  defp deps do
+    [
+      # web server
+      {:plug_cowboy, "~> 2.5"},
+      {:cowboy, "~> 2.8.0"}, # pinned for now
+      {:ranch, ">= 0.0.0"},
+      {:jason, "== 1.2.2", only: [:dev, :test]},
+      {:gun, "~> 2.0.0", hex: "grpc_gun"},
+      {:database, git: "git@github.com:solfacil/database.git", tag: "0.0.7"}
+    ]
+  end
+end
+"#;
+
+    const MIX_LOCK: &str = r#"%{
+  "plug_cowboy": {:hex, :plug_cowboy, "2.5.2", "62894ccd", [:mix], [], "hexpm", "ea6e87f7"},
+  "ranch": {:hex, :ranch, "1.8.0", "8c7a100a", [:make, :rebar3], [], "hexpm", "49fbcfd3"},
+}
+"#;
+
+    fn registry(name: &str) -> Registry {
+        let dir = write_registry(name);
+        let registry = Registry::load(&dir).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+
+        registry
+    }
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    fn setup(name: &str) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(name);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("mix.exs"), MIX_EXS).unwrap();
+        fs::write(root.join("mix.lock"), MIX_LOCK).unwrap();
+
+        root
+    }
+
+    #[test]
+    fn drift() {
+        let drift =
+            |current: &str, latest: &str| Drift::between(&version(current), &version(latest));
+
+        assert_eq!(drift("1.2.2", "2.0.0"), Some(Drift::Major));
+        assert_eq!(drift("1.2.2", "1.3.0"), Some(Drift::Minor));
+        assert_eq!(drift("1.2.2", "1.2.9"), Some(Drift::Patch));
+        assert_eq!(drift("1.3.0", "1.3.0"), None);
+    }
+
+    #[test]
+    fn find_outdated() {
+        let root = setup("photosphere_find_outdated");
+        let deps = de::parse_deps(&root).unwrap();
+        let lock = Lock::load(&root).unwrap();
+
+        let outdated = outdated(
+            &deps,
+            &registry("photosphere_find_outdated_registry"),
+            Some(&lock),
+        );
+        let names = outdated.iter().map(Outdated::name).collect::<Vec<&str>>();
+
+        // plug_cowboy and ranch are locked at their latest
+        assert_eq!(names, ["cowboy", "jason", "gun"]);
+
+        assert_eq!(outdated[0].current(), &version("2.8.0"));
+        assert_eq!(outdated[0].latest(), &version("2.9.0"));
+        assert_eq!(outdated[0].drift(), Drift::Minor);
+        assert!(!outdated[0].allowed());
+
+        assert_eq!(outdated[2].current(), &version("2.0.0"));
+        assert_eq!(outdated[2].drift(), Drift::Patch);
+        assert!(outdated[2].allowed());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn update_outdated_requirements() {
+        let root = setup("photosphere_update_outdated_requirements");
+        let deps = de::parse_deps(&root).unwrap();
+
+        let registry = registry("photosphere_update_outdated_registry");
+
+        let outdated = outdated(&deps, &registry, None);
+        let updated = update_requirements(&root, &outdated).unwrap();
+
+        assert_eq!(updated.len(), 2);
+        assert_eq!(
+            fs::read_to_string(root.join("mix.exs")).unwrap(),
+            MIX_EXS
+                .replace(r#""~> 2.8.0""#, r#""~> 2.9.0""#)
+                .replace(r#""== 1.2.2""#, r#""== 1.3.0""#)
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn skip_unbumpable_requirements() {
+        let root = setup("photosphere_skip_unbumpable_requirements");
+        let ranch = Outdated {
+            allowed: false,
+            current: version("1.8.0"),
+            drift: Drift::Major,
+            latest: version("2.1.0"),
+            name: "ranch".to_string(),
+            requirement: VersionRequirement::parse(">= 1.0.0 and < 2.0.0").unwrap(),
+        };
+
+        let updated = update_requirements(&root, &[ranch]).unwrap();
+
+        assert!(updated.is_empty());
+        assert_eq!(fs::read_to_string(root.join("mix.exs")).unwrap(), MIX_EXS);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,699 @@
+use crate::parser::{
+    ast::{self, Call, Expr, Module},
+    cst::{SyntaxKind, SyntaxNode, SyntaxTree},
+};
+use anyhow::{anyhow, bail, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// What `mix.exs` says about the project, setters
+// edit the syntax tree in place and `save` writes
+// it back, so formatting and comments are kept
+#[derive(Clone, Debug)]
+pub struct MixProject {
+    pub(super) aliases: Vec<Alias>,
+    pub(super) app: String,
+    pub(super) application: Application,
+    pub(super) docs: Option<Docs>,
+    pub(super) elixir: String,
+    pub(super) elixirc_paths: Vec<ElixircPaths>,
+    pub(super) path: PathBuf,
+    pub(super) releases: Vec<Release>,
+    pub(super) test_coverage: Option<String>, // the `tool`
+    pub(super) tree: SyntaxTree,
+    pub(super) version: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alias {
+    pub(super) name: String,
+    pub(super) tasks: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Application {
+    pub(super) extra_applications: Vec<String>,
+    pub(super) module: Option<String>, // `mod: {Foo.Application, []}`
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Docs {
+    pub(super) extras: Vec<String>,
+    pub(super) formatters: Vec<String>,
+    pub(super) main: Option<String>,
+}
+
+// one `elixirc_paths/1` clause, `env`
+// is `None` for the catch-all one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElixircPaths {
+    pub(super) env: Option<String>,
+    pub(super) paths: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Release {
+    pub(super) applications: Vec<(String, String)>, // app and its type
+    pub(super) name: String,
+}
+
+impl MixProject {
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join("mix.exs");
+        let tree = SyntaxTree::parse(&fs::read_to_string(&path)?)?;
+
+        let source = tree.expr(tree.root())?;
+        let module = source
+            .exprs()
+            .into_iter()
+            .find_map(Expr::as_module)
+            .ok_or_else(|| anyhow!("no module found in {}", path.display()))?;
+
+        let project = def_body(module, "project")
+            .ok_or_else(|| anyhow!("no `project/0` found in {}", path.display()))?;
+        let get = |key: &str| project.keyword_get(key).map(|value| resolve(module, value));
+
+        Ok(MixProject {
+            aliases: get("aliases").map(parse_aliases).unwrap_or_default(),
+            app: get("app")
+                .and_then(Expr::as_atom)
+                .unwrap_or_default()
+                .to_string(),
+            application: def_body(module, "application")
+                .map(parse_application)
+                .unwrap_or_default(),
+            docs: get("docs").map(parse_docs),
+            elixir: get("elixir")
+                .and_then(Expr::as_string)
+                .unwrap_or_default()
+                .to_string(),
+            elixirc_paths: parse_elixirc_paths(module),
+            path,
+            releases: get("releases").map(parse_releases).unwrap_or_default(),
+            test_coverage: get("test_coverage")
+                .and_then(|t| t.keyword_get("tool"))
+                .map(|tool| tool.to_string()),
+            tree,
+            version: get("version")
+                .and_then(Expr::as_string)
+                .unwrap_or_default()
+                .to_string(),
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::write(&self.path, self.tree.to_string())?;
+
+        Ok(())
+    }
+
+    pub fn aliases(&self) -> &[Alias] {
+        &self.aliases
+    }
+
+    pub fn app(&self) -> &str {
+        &self.app
+    }
+
+    pub fn application(&self) -> &Application {
+        &self.application
+    }
+
+    pub fn docs(&self) -> Option<&Docs> {
+        self.docs.as_ref()
+    }
+
+    pub fn elixir(&self) -> &str {
+        &self.elixir
+    }
+
+    pub fn elixirc_paths(&self) -> &[ElixircPaths] {
+        &self.elixirc_paths
+    }
+
+    pub fn releases(&self) -> &[Release] {
+        &self.releases
+    }
+
+    pub fn test_coverage(&self) -> Option<&str> {
+        self.test_coverage.as_deref()
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn set_app(&mut self, app: &str) -> Result<&mut MixProject> {
+        self.set_project_key("app", &Expr::Atom(app.to_string()))?;
+        self.app = app.to_string();
+
+        Ok(self)
+    }
+
+    pub fn set_elixir(&mut self, elixir: &str) -> Result<&mut MixProject> {
+        self.set_project_key("elixir", &Expr::String(elixir.to_string()))?;
+        self.elixir = elixir.to_string();
+
+        Ok(self)
+    }
+
+    pub fn set_version(&mut self, version: &str) -> Result<&mut MixProject> {
+        self.set_project_key("version", &Expr::String(version.to_string()))?;
+        self.version = version.to_string();
+
+        Ok(self)
+    }
+
+    // replaces the alias tasks or adds the alias
+    pub fn set_alias(&mut self, name: &str, tasks: Vec<String>) -> Result<&mut MixProject> {
+        let value = Expr::List(tasks.iter().cloned().map(Expr::String).collect());
+        let list = self.project_value("aliases")?.clone();
+
+        match keyword_pair(&self.tree, &list, name) {
+            Some(pair) => {
+                let value_range = pair.children()[0].range();
+                self.tree.replace(value_range, &value.to_string())?;
+            }
+            None => {
+                let pair = format!("{} {}", ast::keyword_key(name), value);
+                self.tree.insert(&list, list.children().len(), &pair)?;
+            }
+        }
+
+        match self.aliases.iter_mut().find(|a| a.name.eq(name)) {
+            Some(alias) => alias.tasks = tasks,
+            None => self.aliases.push(Alias {
+                name: name.to_string(),
+                tasks,
+            }),
+        }
+
+        Ok(self)
+    }
+
+    pub fn remove_alias(&mut self, name: &str) -> Result<&mut MixProject> {
+        let list = self.project_value("aliases")?;
+
+        if let Some(range) = keyword_pair(&self.tree, list, name).map(SyntaxNode::range) {
+            self.tree.remove(range)?;
+        }
+
+        self.aliases.retain(|a| a.name.ne(name));
+
+        Ok(self)
+    }
+
+    pub fn rename_release(&mut self, name: &str, new_name: &str) -> Result<&mut MixProject> {
+        let list = self.project_value("releases")?;
+        let pair = keyword_pair(&self.tree, list, name)
+            .ok_or_else(|| anyhow!("no `{}` release in {}", name, self.path.display()))?;
+
+        // only the key token, the release
+        // config stays as written
+        let key = pair.range().start;
+        let new_key = ast::keyword_key(new_name);
+
+        self.tree
+            .replace(key..key + 1, new_key.trim_end_matches(':'))?;
+
+        if let Some(release) = self.releases.iter_mut().find(|r| r.name.eq(name)) {
+            release.name = new_name.to_string();
+        }
+
+        Ok(self)
+    }
+
+    fn set_project_key(&mut self, key: &str, value: &Expr) -> Result<()> {
+        let project = def_node(&self.tree, "project")
+            .ok_or_else(|| anyhow!("no `project/0` found in {}", self.path.display()))?
+            .clone();
+
+        match keyword_pair(&self.tree, &project, key) {
+            Some(pair) => {
+                let range = resolve_node(&self.tree, &pair.children()[0]).range();
+                self.tree.replace(range, &value.to_string())
+            }
+            None => {
+                let pair = format!("{} {}", ast::keyword_key(key), value);
+                self.tree.insert(&project, project.children().len(), &pair)
+            }
+        }
+    }
+
+    // the list a `project/0` key points to,
+    // following `key: key()` calls
+    fn project_value(&self, key: &str) -> Result<&SyntaxNode> {
+        let node = def_node(&self.tree, "project")
+            .and_then(|project| keyword_pair(&self.tree, project, key))
+            .map(|pair| resolve_node(&self.tree, &pair.children()[0]))
+            .ok_or_else(|| anyhow!("no `{}` found in {}", key, self.path.display()))?;
+
+        if node.kind() != SyntaxKind::List {
+            bail!("`{}` is not a list in {}", key, self.path.display());
+        }
+
+        Ok(node)
+    }
+}
+
+impl Alias {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn tasks(&self) -> &[String] {
+        &self.tasks
+    }
+}
+
+impl Application {
+    pub fn extra_applications(&self) -> &[String] {
+        &self.extra_applications
+    }
+
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+}
+
+impl Docs {
+    pub fn extras(&self) -> &[String] {
+        &self.extras
+    }
+
+    pub fn formatters(&self) -> &[String] {
+        &self.formatters
+    }
+
+    pub fn main(&self) -> Option<&str> {
+        self.main.as_deref()
+    }
+}
+
+impl ElixircPaths {
+    pub fn env(&self) -> Option<&str> {
+        self.env.as_deref()
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+}
+
+impl Release {
+    pub fn applications(&self) -> &[(String, String)] {
+        &self.applications
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+// body of a zero arity `def`/`defp`
+pub(super) fn def_node<'a>(tree: &'a SyntaxTree, name: &str) -> Option<&'a SyntaxNode> {
+    tree.root()
+        .descendants()
+        .into_iter()
+        .filter(|n| n.kind() == SyntaxKind::Def)
+        .find(|n| {
+            matches!(
+                tree.expr(n),
+                Ok(Expr::Def(def)) if def.name.eq(name) && def.args.is_empty()
+            )
+        })
+        .and_then(|n| n.do_body())
+}
+
+// `key: value` node of a keyword list
+pub(super) fn keyword_pair<'a>(
+    tree: &SyntaxTree,
+    list: &'a SyntaxNode,
+    key: &str,
+) -> Option<&'a SyntaxNode> {
+    list.children()
+        .iter()
+        .filter(|n| n.kind() == SyntaxKind::Pair)
+        .find(|n| match tree.expr(n) {
+            Ok(pair) => pair
+                .as_tuple()
+                .and_then(|t| t.first())
+                .and_then(Expr::as_atom)
+                .is_some_and(|k| k.eq(key)),
+            Err(_) => false,
+        })
+}
+
+// `deps()` points to the `deps/0` body and
+// `@version` to the module attribute value
+pub(super) fn resolve_node<'a>(tree: &'a SyntaxTree, node: &'a SyntaxNode) -> &'a SyntaxNode {
+    let resolved = match tree.expr(node) {
+        Ok(Expr::Call(Call {
+            target: None,
+            name,
+            args,
+        })) if args.is_empty() => def_node(tree, &name),
+        Ok(Expr::Attribute(name, None)) => tree
+            .root()
+            .descendants()
+            .into_iter()
+            .filter(|n| n.kind() == SyntaxKind::Attribute)
+            .find(|n| matches!(tree.expr(n), Ok(Expr::Attribute(a, Some(_))) if a.eq(&name)))
+            .and_then(|n| n.children().first()),
+        _ => None,
+    };
+
+    resolved.unwrap_or(node)
+}
+
+fn def_body<'a>(module: &'a Module, name: &str) -> Option<&'a Expr> {
+    module.def(name, 0).and_then(|def| def.body.as_deref())
+}
+
+fn resolve<'a>(module: &'a Module, expr: &'a Expr) -> &'a Expr {
+    let resolved = match expr {
+        Expr::Call(Call {
+            target: None,
+            name,
+            args,
+        }) if args.is_empty() => def_body(module, name),
+        Expr::Attribute(name, None) => module.body.exprs().into_iter().find_map(|e| match e {
+            Expr::Attribute(a, Some(value)) if a.eq(name) => Some(value.as_ref()),
+            _ => None,
+        }),
+        _ => None,
+    };
+
+    resolved.unwrap_or(expr)
+}
+
+fn strings(expr: &Expr) -> Vec<String> {
+    match expr {
+        Expr::String(string) => vec![string.to_string()],
+        Expr::List(list) => list
+            .iter()
+            .filter_map(|e| e.as_string().or_else(|| e.as_atom()))
+            .map(|s| s.to_string())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn parse_aliases(expr: &Expr) -> Vec<Alias> {
+    expr.as_keyword()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, tasks)| Alias {
+            name: name.to_string(),
+            tasks: strings(tasks),
+        })
+        .collect()
+}
+
+fn parse_application(expr: &Expr) -> Application {
+    let module = match expr.keyword_get("mod").and_then(Expr::as_tuple) {
+        Some(tuple) => tuple.first().map(|m| m.to_string()),
+        None => None,
+    };
+
+    Application {
+        extra_applications: expr
+            .keyword_get("extra_applications")
+            .map(strings)
+            .unwrap_or_default(),
+        module,
+    }
+}
+
+fn parse_docs(expr: &Expr) -> Docs {
+    // extras may come with options, `"README.md": [title: ...]`
+    let extras = match expr.keyword_get("extras").and_then(Expr::as_list) {
+        Some(extras) => extras
+            .iter()
+            .filter_map(|extra| match extra.as_tuple().map(Vec::as_slice) {
+                Some([name, _]) => name.as_atom(),
+                _ => extra.as_string(),
+            })
+            .map(|s| s.to_string())
+            .collect(),
+        None => vec![],
+    };
+
+    Docs {
+        extras,
+        formatters: expr
+            .keyword_get("formatters")
+            .map(strings)
+            .unwrap_or_default(),
+        main: expr
+            .keyword_get("main")
+            .and_then(Expr::as_string)
+            .map(|s| s.to_string()),
+    }
+}
+
+fn parse_elixirc_paths(module: &Module) -> Vec<ElixircPaths> {
+    module
+        .defs()
+        .into_iter()
+        .filter(|def| def.name.eq("elixirc_paths") && def.args.len() == 1)
+        .filter_map(|def| {
+            Some(ElixircPaths {
+                env: def.args[0].as_atom().map(|env| env.to_string()),
+                paths: strings(def.body.as_deref()?),
+            })
+        })
+        .collect()
+}
+
+fn parse_releases(expr: &Expr) -> Vec<Release> {
+    expr.as_keyword()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, config)| Release {
+            applications: config
+                .keyword_get("applications")
+                .and_then(Expr::as_keyword)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(app, kind)| {
+                    (
+                        app.to_string(),
+                        kind.as_atom().unwrap_or_default().to_string(),
+                    )
+                })
+                .collect(),
+            name: name.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIX_EXS: &str = r#"defmodule Foo.MixProject do
+  use Mix.Project
+
+  @version "0.3.0"
+
+  def project do
+    [
+      app: :foo,
+      version: @version,
+      elixir: "~> 1.12",
+      elixirc_paths: elixirc_paths(Mix.env()),
+      aliases: aliases(),
+      releases: releases(),
+      docs: [
+        main: "Foo",
+        formatters: ["html"],
+        extras: ["README.md", "guides/setup.md": [title: "Setup"]]
+      ],
+      test_coverage: [tool: ExCoveralls]
+    ]
+  end
+
+  def application do
+    [
+      mod: {Foo.Application, []},
+      extra_applications: [:logger, :runtime_tools]
+    ]
+  end
+
+  # Specifies which paths to compile per environment.
+  defp elixirc_paths(:test), do: ["lib", "test/support"]
+  defp elixirc_paths(_), do: ["lib"]
+
+  defp aliases do
+    [
+      setup: ["deps.get", "ecto.setup"],
+      # database
+      "ecto.reset": ["ecto.drop", "ecto.setup"],
+      test: ["ecto.create --quiet", "test"]
+    ]
+  end
+
+  defp releases do
+    [
+      foo: [
+        applications: [
+          foo: :permanent
+        ]
+      ]
+    ]
+  end
+end
+"#;
+
+    fn load(name: &str, mix_exs: &str) -> MixProject {
+        let root = std::env::temp_dir().join(name);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("mix.exs"), mix_exs).unwrap();
+
+        MixProject::load(&root).unwrap()
+    }
+
+    fn saved(project: &MixProject) -> String {
+        project.save().unwrap();
+        let mix_exs = fs::read_to_string(&project.path).unwrap();
+        fs::remove_dir_all(project.path.parent().unwrap()).unwrap();
+
+        mix_exs
+    }
+
+    #[test]
+    fn load_project() {
+        let project = load("photosphere_load_project", MIX_EXS);
+
+        assert_eq!(project.app(), "foo");
+        assert_eq!(project.version(), "0.3.0");
+        assert_eq!(project.elixir(), "~> 1.12");
+        assert_eq!(project.test_coverage(), Some("ExCoveralls"));
+
+        let docs = project.docs().unwrap();
+        assert_eq!(docs.main(), Some("Foo"));
+        assert_eq!(docs.extras(), &["README.md", "guides/setup.md"]);
+
+        saved(&project);
+    }
+
+    #[test]
+    fn load_definitions() {
+        let project = load("photosphere_load_definitions", MIX_EXS);
+
+        assert_eq!(project.application().module(), Some("Foo.Application"));
+        assert_eq!(
+            project.application().extra_applications(),
+            &["logger", "runtime_tools"]
+        );
+
+        let paths = project.elixirc_paths();
+        assert_eq!(paths[0].env(), Some("test"));
+        assert_eq!(paths[0].paths(), &["lib", "test/support"]);
+        assert_eq!(paths[1].env(), None);
+
+        let aliases = project.aliases();
+        assert_eq!(aliases.len(), 3);
+        assert_eq!(aliases[1].name(), "ecto.reset");
+        assert_eq!(aliases[1].tasks(), &["ecto.drop", "ecto.setup"]);
+
+        let release = &project.releases()[0];
+        assert_eq!(release.name(), "foo");
+        assert_eq!(
+            release.applications(),
+            &[("foo".to_string(), "permanent".to_string())]
+        );
+
+        saved(&project);
+    }
+
+    #[test]
+    fn load_template() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("priv");
+        let project = MixProject::load(&root).unwrap();
+
+        assert_eq!(project.app(), "service_template");
+        assert_eq!(project.elixir(), "~> 1.12");
+        assert_eq!(project.aliases().len(), 6);
+        assert_eq!(project.releases()[0].name(), "service_template");
+        assert_eq!(project.docs().unwrap().extras().len(), 5);
+    }
+
+    #[test]
+    fn set_project_keys() {
+        let mut project = load("photosphere_set_project_keys", MIX_EXS);
+
+        project
+            .set_app("bar")
+            .unwrap()
+            .set_version("1.0.0")
+            .unwrap()
+            .set_elixir("~> 1.13")
+            .unwrap();
+
+        let expected = MIX_EXS
+            .replace("app: :foo", "app: :bar")
+            .replace("@version \"0.3.0\"", "@version \"1.0.0\"")
+            .replace("~> 1.12", "~> 1.13");
+        assert_eq!(saved(&project), expected);
+    }
+
+    #[test]
+    fn set_missing_project_key() {
+        let mix_exs = "defmodule Foo.MixProject do\n  def project do\n    [\n      app: :foo\n    ]\n  end\nend\n";
+        let mut project = load("photosphere_set_missing_project_key", mix_exs);
+
+        project.set_version("0.1.0").unwrap();
+
+        assert_eq!(
+            saved(&project),
+            mix_exs.replace("app: :foo", "app: :foo,\n      version: \"0.1.0\"")
+        );
+    }
+
+    #[test]
+    fn edit_aliases() {
+        let mut project = load("photosphere_edit_aliases", MIX_EXS);
+
+        project
+            .set_alias("setup", vec!["deps.get".to_string()])
+            .unwrap()
+            .set_alias("ci", vec!["format --check-formatted".to_string()])
+            .unwrap()
+            .remove_alias("ecto.reset")
+            .unwrap();
+
+        assert_eq!(project.aliases().len(), 3);
+
+        let expected = MIX_EXS
+            .replace(
+                r#"setup: ["deps.get", "ecto.setup"]"#,
+                r#"setup: ["deps.get"]"#,
+            )
+            .replace(
+                "      # database\n      \"ecto.reset\": [\"ecto.drop\", \"ecto.setup\"],\n",
+                "      # database\n",
+            )
+            .replace(
+                r#"test: ["ecto.create --quiet", "test"]"#,
+                "test: [\"ecto.create --quiet\", \"test\"],\n      ci: [\"format --check-formatted\"]",
+            );
+        assert_eq!(saved(&project), expected);
+    }
+
+    #[test]
+    fn rename_release() {
+        let mut project = load("photosphere_rename_release", MIX_EXS);
+
+        project.rename_release("foo", "bar").unwrap();
+
+        assert_eq!(project.releases()[0].name(), "bar");
+        assert_eq!(
+            saved(&project),
+            MIX_EXS.replace("      foo: [", "      bar: [")
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use super::version::{Version, VersionRequirement};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+// A Hex registry snapshot, one `<package>.json` per
+// package in a directory, so deps can be resolved
+// without reaching hex.pm:
+//
+// {"name": "gun", "releases": [{"version": "2.0.1", "requirements":
+//   [{"package": "cowlib", "requirement": "~> 2.11", "optional": false}]}]}
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    pub(super) packages: BTreeMap<String, Package>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct Package {
+    pub(super) name: String,
+    pub(super) releases: Vec<Release>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct Release {
+    #[serde(default)]
+    pub(super) requirements: Vec<Requirement>,
+    #[serde(default)]
+    pub(super) retired: bool,
+    pub(super) version: Version,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct Requirement {
+    #[serde(default)]
+    pub(super) app: Option<String>, // when it differs from the package
+    #[serde(default)]
+    pub(super) optional: bool,
+    pub(super) package: String,
+    pub(super) requirement: Option<VersionRequirement>,
+}
+
+impl Registry {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut packages = BTreeMap::new();
+
+        let entries = fs::read_dir(dir)
+            .map_err(|e| anyhow!("cannot read registry {}: {}", dir.display(), e))?;
+
+        for entry in entries {
+            let path = entry?.path();
+
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
+            let package: Package = serde_json::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| anyhow!("invalid registry file {}: {}", path.display(), e))?;
+
+            packages.insert(package.name.to_string(), package);
+        }
+
+        Ok(Registry { packages })
+    }
+
+    pub fn get(&self, package: &str) -> Option<&Package> {
+        self.packages.get(package)
+    }
+
+    pub fn insert(&mut self, package: Package) -> &mut Registry {
+        self.packages.insert(package.name.to_string(), package);
+
+        self
+    }
+}
+
+impl Package {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn releases(&self) -> &[Release] {
+        &self.releases
+    }
+
+    // releases matching all `requirements`, newest first,
+    // skipping retired ones and pre-releases unless asked
+    pub fn candidates(&self, requirements: &[&VersionRequirement]) -> Vec<&Release> {
+        let allows_pre = requirements.iter().any(|req| req.allows_pre());
+
+        let mut releases = self
+            .releases
+            .iter()
+            .filter(|r| !r.retired && (allows_pre || !r.version.is_pre()))
+            .filter(|r| requirements.iter().all(|req| req.matches(&r.version)))
+            .collect::<Vec<&Release>>();
+        releases.sort_by(|a, b| b.version.cmp(&a.version));
+
+        releases
+    }
+
+    pub fn latest(&self) -> Option<&Release> {
+        self.candidates(&[]).into_iter().next()
+    }
+}
+
+impl Release {
+    pub fn requirements(&self) -> &[Requirement] {
+        &self.requirements
+    }
+
+    pub fn retired(&self) -> bool {
+        self.retired
+    }
+
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+}
+
+impl Requirement {
+    pub fn app(&self) -> &str {
+        self.app.as_deref().unwrap_or(&self.package)
+    }
+
+    pub fn optional(&self) -> bool {
+        self.optional
+    }
+
+    pub fn package(&self) -> &str {
+        &self.package
+    }
+
+    pub fn requirement(&self) -> Option<&VersionRequirement> {
+        self.requirement.as_ref()
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // a small slice of hex.pm, `cowboy` pins the
+    // `cowlib` version that `grpc_gun` does not accept
+    pub(in crate::photosphere::service) const REGISTRY: &[(&str, &str)] = &[
+        (
+            "cowboy",
+            r#"{"name": "cowboy", "releases": [
+                {"version": "2.9.0", "requirements": [
+                    {"package": "cowlib", "requirement": "2.11.0"},
+                    {"package": "ranch", "requirement": "1.8.0"}]}]}"#,
+        ),
+        (
+            "cowlib",
+            r#"{"name": "cowlib", "releases": [
+                {"version": "2.11.0"},
+                {"version": "2.12.1"},
+                {"version": "2.13.0-rc.0"}]}"#,
+        ),
+        (
+            "grpc_gun",
+            r#"{"name": "grpc_gun", "releases": [
+                {"version": "2.0.0", "retired": true},
+                {"version": "2.0.1", "requirements": [
+                    {"package": "cowlib", "requirement": "~> 2.12.0"}]}]}"#,
+        ),
+        (
+            "jason",
+            r#"{"name": "jason", "releases": [
+                {"version": "1.2.2", "requirements": [
+                    {"package": "decimal", "requirement": "~> 1.0 or ~> 2.0", "optional": true}]},
+                {"version": "1.3.0", "requirements": [
+                    {"package": "decimal", "requirement": "~> 1.0 or ~> 2.0", "optional": true}]}]}"#,
+        ),
+        (
+            "plug_cowboy",
+            r#"{"name": "plug_cowboy", "releases": [
+                {"version": "2.5.2", "requirements": [
+                    {"package": "cowboy", "requirement": "~> 2.7"}]}]}"#,
+        ),
+        (
+            "ranch",
+            r#"{"name": "ranch", "releases": [{"version": "1.8.0"}]}"#,
+        ),
+    ];
+
+    pub(in crate::photosphere::service) fn write_registry(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        fs::create_dir_all(&dir).unwrap();
+
+        for (package, json) in REGISTRY {
+            fs::write(dir.join(format!("{}.json", package)), json).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn load_registry() {
+        let dir = write_registry("photosphere_load_registry");
+        fs::write(dir.join("README.md"), "not a package").unwrap();
+
+        let registry = Registry::load(&dir).unwrap();
+        let gun = registry.get("grpc_gun").unwrap();
+
+        assert_eq!(registry.packages.len(), REGISTRY.len());
+        assert_eq!(gun.releases().len(), 2);
+        assert_eq!(gun.releases()[1].requirements()[0].app(), "cowlib");
+        assert!(gun.releases()[0].retired());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn load_invalid_registry() {
+        let dir = std::env::temp_dir().join("photosphere_load_invalid_registry");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("foo.json"),
+            r#"{"name": "foo", "releases": [{"version": "1.0"}]}"#,
+        )
+        .unwrap();
+
+        assert!(Registry::load(&dir).is_err());
+        assert!(Registry::load(&dir.join("missing")).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn release_candidates() {
+        let dir = write_registry("photosphere_release_candidates");
+        let registry = Registry::load(&dir).unwrap();
+
+        let cowlib = registry.get("cowlib").unwrap();
+        let versions = |req: &str| {
+            let req = VersionRequirement::parse(req).unwrap();
+            cowlib
+                .candidates(&[&req])
+                .iter()
+                .map(|r| r.version().to_string())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(versions("~> 2.11"), ["2.12.1", "2.11.0"]);
+        assert_eq!(versions(">= 2.13.0-rc.0"), ["2.13.0-rc.0"]);
+        assert_eq!(
+            registry
+                .get("grpc_gun")
+                .unwrap()
+                .latest()
+                .unwrap()
+                .version(),
+            &Version::parse("2.0.1").unwrap()
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,489 @@
+use super::{
+    dep::Dep,
+    registry::Registry,
+    version::{Version, VersionRequirement},
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+// what asked for a root dep
+const MIX_EXS: &str = "mix.exs";
+
+// the version picked for every hex dep, by app
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Resolution {
+    pub(super) versions: BTreeMap<String, Version>,
+}
+
+// why the deps cannot be resolved, the same
+// trouble `mix deps.get` would run into
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    Conflict {
+        name: String,
+        requirements: Vec<(String, String)>, // who asks and what
+    },
+    Unknown {
+        name: String,
+        package: String,
+    },
+    Unsatisfiable {
+        name: String,
+        requirement: String,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Constraint {
+    from: String,
+    optional: bool, // only applies if someone else needs it
+    requirement: Option<VersionRequirement>,
+}
+
+// a dead end and the apps whose picks led there,
+// another release of any other app can't get out
+#[derive(Clone, Debug)]
+struct Failure {
+    culprits: HashSet<String>,
+    problem: Problem,
+}
+
+#[derive(Clone, Debug, Default)]
+struct State {
+    constraints: BTreeMap<String, Vec<Constraint>>,
+    packages: BTreeMap<String, String>, // app to hex package
+    selected: BTreeMap<String, Version>,
+}
+
+// picks the newest release of each dep, backtracking when
+// a pick leaves another dep without a matching release,
+// straight to the latest pick that took part in it; an
+// `override: true` root ignores transitive requirements
+struct Resolver<'a> {
+    overrides: HashSet<String>,
+    registry: &'a Registry,
+    scm: HashSet<String>, // not on hex, their requirements are unknown
+}
+
+impl Resolution {
+    pub fn get(&self, app: &str) -> Option<&Version> {
+        self.versions.get(app)
+    }
+
+    pub fn versions(&self) -> &BTreeMap<String, Version> {
+        &self.versions
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Conflict { name, requirements } => {
+                let requirements = requirements
+                    .iter()
+                    .map(|(from, requirement)| format!("\"{}\" from {}", requirement, from))
+                    .collect::<Vec<String>>();
+
+                write!(
+                    f,
+                    "`{}` requirements conflict: {}; add `override: true` to `:{}` in mix.exs",
+                    name,
+                    requirements.join(", "),
+                    name
+                )
+            }
+            Problem::Unknown { name, package } if name == package => {
+                write!(f, "`{}` is not in the registry", name)
+            }
+            Problem::Unknown { name, package } => {
+                write!(
+                    f,
+                    "`{}` (hex package `{}`) is not in the registry",
+                    name, package
+                )
+            }
+            Problem::Unsatisfiable { name, requirement } => {
+                write!(f, "no `{}` release matches \"{}\"", name, requirement)
+            }
+        }
+    }
+}
+
+pub fn resolve(deps: &[Dep], registry: &Registry) -> Result<Resolution, Problem> {
+    let mut state = State::default();
+
+    for dep in deps.iter() {
+        state
+            .packages
+            .insert(dep.name.to_string(), dep.package().to_string());
+        state
+            .constraints
+            .entry(dep.name.to_string())
+            .or_default()
+            .push(Constraint {
+                from: MIX_EXS.to_string(),
+                optional: false,
+                requirement: dep.version.clone(),
+            });
+    }
+
+    let resolver = Resolver {
+        overrides: names(deps, |d| d.conflict == Some(true)),
+        registry,
+        scm: names(deps, Dep::is_scm),
+    };
+
+    let selected = resolver.run(state).map_err(|f| f.problem)?;
+
+    Ok(Resolution { versions: selected })
+}
+
+// every problem with the deps, those of each
+// root on its own first, then the resolution
+pub fn check(deps: &[Dep], registry: &Registry) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    for dep in deps.iter().filter(|d| !d.is_scm()) {
+        let requirements = dep.version.iter().collect::<Vec<&VersionRequirement>>();
+
+        match registry.get(dep.package()) {
+            None => problems.push(Problem::Unknown {
+                name: dep.name.to_string(),
+                package: dep.package().to_string(),
+            }),
+            Some(package) if package.candidates(&requirements).is_empty() => {
+                problems.push(Problem::Unsatisfiable {
+                    name: dep.name.to_string(),
+                    requirement: requirement_str(dep.version.as_ref()),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    if problems.is_empty() {
+        if let Err(problem) = resolve(deps, registry) {
+            problems.push(problem);
+        }
+    }
+
+    problems
+}
+
+impl<'a> Resolver<'a> {
+    fn run(&self, state: State) -> Result<BTreeMap<String, Version>, Failure> {
+        let next = state.constraints.iter().find(|(app, constraints)| {
+            !state.selected.contains_key(*app)
+                && !self.scm.contains(*app)
+                && constraints.iter().any(|c| !c.optional)
+        });
+
+        let app = match next {
+            Some((app, _)) => app.to_string(),
+            None => return Ok(state.selected),
+        };
+
+        let active = self.active(&state, &app);
+        // whoever asked for the app, and so limited its releases
+        let mut culprits = active
+            .iter()
+            .map(|c| c.from.to_string())
+            .filter(|from| from != MIX_EXS)
+            .collect::<HashSet<String>>();
+
+        let package = state.packages.get(&app).unwrap_or(&app);
+        let package = self.registry.get(package).ok_or_else(|| Failure {
+            culprits: culprits.clone(),
+            problem: Problem::Unknown {
+                name: app.to_string(),
+                package: package.to_string(),
+            },
+        })?;
+
+        let requirements = active
+            .iter()
+            .filter_map(|c| c.requirement.as_ref())
+            .collect::<Vec<&VersionRequirement>>();
+        let candidates = package.candidates(&requirements);
+
+        let mut problem = self.problem(&app, &active);
+
+        for release in candidates {
+            let mut next = state.clone();
+            next.selected
+                .insert(app.to_string(), release.version().clone());
+
+            let mut conflict = None;
+
+            for requirement in release.requirements() {
+                let dep = requirement.app();
+
+                next.packages
+                    .entry(dep.to_string())
+                    .or_insert_with(|| requirement.package().to_string());
+                next.constraints
+                    .entry(dep.to_string())
+                    .or_default()
+                    .push(Constraint {
+                        from: app.to_string(),
+                        optional: requirement.optional(),
+                        requirement: requirement.requirement().cloned(),
+                    });
+
+                // picked earlier, it has to fit the new requirement
+                let fits = match (next.selected.get(dep), requirement.requirement()) {
+                    (Some(version), Some(req)) => {
+                        self.overrides.contains(dep) || req.matches(version)
+                    }
+                    _ => true,
+                };
+
+                if !fits {
+                    let active = self.active(&next, dep);
+
+                    culprits.insert(dep.to_string());
+                    culprits.extend(active.iter().map(|c| c.from.to_string()));
+                    conflict = Some(self.problem(dep, &active));
+                }
+            }
+
+            if let Some(conflict) = conflict {
+                problem = conflict;
+                continue;
+            }
+
+            match self.run(next) {
+                Ok(selected) => return Ok(selected),
+                // nothing to do with this pick, the
+                // next release would end there too
+                Err(failure) if !failure.culprits.contains(&app) => return Err(failure),
+                Err(failure) => {
+                    culprits.extend(failure.culprits);
+                    problem = failure.problem;
+                }
+            }
+        }
+
+        culprits.remove(&app);
+        culprits.remove(MIX_EXS);
+
+        Err(Failure { culprits, problem })
+    }
+
+    fn active(&self, state: &State, app: &str) -> Vec<Constraint> {
+        state
+            .constraints
+            .get(app)
+            .into_iter()
+            .flatten()
+            .filter(|c| !self.overrides.contains(app) || c.from == MIX_EXS)
+            .cloned()
+            .collect()
+    }
+
+    fn problem(&self, app: &str, active: &[Constraint]) -> Problem {
+        match active {
+            [constraint] => Problem::Unsatisfiable {
+                name: app.to_string(),
+                requirement: requirement_str(constraint.requirement.as_ref()),
+            },
+            constraints => Problem::Conflict {
+                name: app.to_string(),
+                requirements: constraints
+                    .iter()
+                    .map(|c| (c.from.to_string(), requirement_str(c.requirement.as_ref())))
+                    .collect(),
+            },
+        }
+    }
+}
+
+fn names<P>(deps: &[Dep], pred: P) -> HashSet<String>
+where
+    P: Fn(&Dep) -> bool,
+{
+    deps.iter()
+        .filter(|d| pred(d))
+        .map(|d| d.name.to_string())
+        .collect()
+}
+
+fn requirement_str(requirement: Option<&VersionRequirement>) -> String {
+    requirement.map_or(">= 0.0.0".to_string(), |r| r.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photosphere::service::registry::tests::write_registry;
+    use std::fs;
+
+    fn registry(name: &str) -> Registry {
+        let dir = write_registry(name);
+        let registry = Registry::load(&dir).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+
+        registry
+    }
+
+    fn dep(name: &str, requirement: &str) -> Dep {
+        Dep::new()
+            .set_name(name.to_string())
+            .set_version(VersionRequirement::parse(requirement).ok())
+            .clone()
+    }
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn resolve_newest() {
+        let registry = registry("photosphere_resolve_newest");
+        let deps = [dep("plug_cowboy", "~> 2.5"), dep("jason", "~> 1.2")];
+
+        let resolution = resolve(&deps, &registry).unwrap();
+
+        assert_eq!(resolution.get("jason"), Some(&version("1.3.0")));
+        assert_eq!(resolution.get("cowlib"), Some(&version("2.11.0")));
+        assert_eq!(resolution.get("ranch"), Some(&version("1.8.0")));
+        // optional and nobody else needs it
+        assert_eq!(resolution.get("decimal"), None);
+    }
+
+    #[test]
+    fn resolve_renamed_dep() {
+        let registry = registry("photosphere_resolve_renamed_dep");
+        let gun = dep("gun", "~> 2.0.0")
+            .set_hex(Some("grpc_gun".to_string()))
+            .clone();
+
+        let resolution = resolve(&[gun], &registry).unwrap();
+
+        // 2.0.0 is retired
+        assert_eq!(resolution.get("gun"), Some(&version("2.0.1")));
+        assert_eq!(resolution.get("cowlib"), Some(&version("2.12.1")));
+    }
+
+    #[test]
+    fn resolve_conflict() {
+        let registry = registry("photosphere_resolve_conflict");
+        let gun = dep("gun", "~> 2.0.0")
+            .set_hex(Some("grpc_gun".to_string()))
+            .clone();
+        let deps = [dep("cowboy", "~> 2.9"), gun];
+
+        let problem = resolve(&deps, &registry).unwrap_err();
+
+        assert_eq!(
+            problem,
+            Problem::Conflict {
+                name: "cowlib".to_string(),
+                requirements: vec![
+                    ("cowboy".to_string(), "2.11.0".to_string()),
+                    ("gun".to_string(), "~> 2.12.0".to_string())
+                ],
+            }
+        );
+        assert_eq!(
+            problem.to_string(),
+            "`cowlib` requirements conflict: \"2.11.0\" from cowboy, \"~> 2.12.0\" from gun; \
+             add `override: true` to `:cowlib` in mix.exs"
+        );
+    }
+
+    #[test]
+    fn resolve_override() {
+        let registry = registry("photosphere_resolve_override");
+        let gun = dep("gun", "~> 2.0.0")
+            .set_hex(Some("grpc_gun".to_string()))
+            .clone();
+        let cowlib = dep("cowlib", "~> 2.11").set_conflict(Some(true)).clone();
+        let deps = [dep("cowboy", "~> 2.9"), gun, cowlib];
+
+        let resolution = resolve(&deps, &registry).unwrap();
+
+        assert_eq!(resolution.get("cowlib"), Some(&version("2.12.1")));
+    }
+
+    #[test]
+    fn resolve_conflict_among_unrelated_deps() {
+        let mut registry = registry("photosphere_resolve_conflict_among_unrelated_deps");
+        let releases = (0..8)
+            .map(|patch| format!(r#"{{"version": "1.0.{}"}}"#, patch))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let mut deps = (0..24)
+            .map(|n| {
+                let name = format!("lib_{:02}", n);
+                let json = format!(r#"{{"name": "{}", "releases": [{}]}}"#, name, releases);
+                registry.insert(serde_json::from_str(&json).unwrap());
+
+                dep(&name, "~> 1.0")
+            })
+            .collect::<Vec<Dep>>();
+        // picked after every lib, and no release fits the cowlib they all get
+        registry.insert(
+            serde_json::from_str(
+                r#"{"name": "tls", "releases": [
+                  {"version": "1.1.0", "requirements": [{"package": "cowlib", "requirement": "~> 1.0"}]},
+                  {"version": "1.0.0", "requirements": [{"package": "cowlib", "requirement": "~> 1.0"}]}
+                ]}"#,
+            )
+            .unwrap(),
+        );
+        deps.push(dep("cowlib", "~> 2.11"));
+        deps.push(dep("tls", "~> 1.0"));
+
+        let start = std::time::Instant::now();
+        let problem = resolve(&deps, &registry).unwrap_err();
+
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+        assert!(matches!(problem, Problem::Conflict { name, .. } if name == "cowlib"));
+    }
+
+    #[test]
+    fn resolve_scm_dep() {
+        let registry = registry("photosphere_resolve_scm_dep");
+        let database = Dep::new()
+            .set_name("database".to_string())
+            .set_git(Some("git@github.com:solfacil/database.git".to_string()))
+            .clone();
+
+        assert!(resolve(&[database, dep("ranch", "~> 1.8")], &registry).is_ok());
+    }
+
+    #[test]
+    fn check_deps() {
+        let registry = registry("photosphere_check_deps");
+        let deps = [
+            dep("cowboy", "~> 3.0"),
+            dep("phoenix", "~> 1.6.6"),
+            dep("ranch", "~> 1.8"),
+        ];
+
+        let problems = check(&deps, &registry);
+
+        assert_eq!(problems.len(), 2);
+        assert_eq!(
+            problems[0].to_string(),
+            "no `cowboy` release matches \"~> 3.0\""
+        );
+        assert_eq!(problems[1].to_string(), "`phoenix` is not in the registry");
+        assert!(check(&deps[2..], &registry).is_empty());
+    }
+
+    #[test]
+    fn check_transitive_conflict() {
+        let registry = registry("photosphere_check_transitive_conflict");
+        let gun = dep("gun", "~> 2.0.0")
+            .set_hex(Some("grpc_gun".to_string()))
+            .clone();
+
+        let problems = check(&[dep("plug_cowboy", "~> 2.5"), gun], &registry);
+
+        assert!(matches!(&problems[..], [Problem::Conflict { name, .. }] if name == "cowlib"));
+    }
+}
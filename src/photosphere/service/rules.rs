@@ -0,0 +1,339 @@
+use super::feature::Catalog;
+use crate::Protocol;
+use anyhow::{bail, Result};
+use std::{collections::BTreeMap, fmt};
+
+// what was asked about a feature
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Choice {
+    pub(super) enabled: bool,
+    pub(super) reason: Reason,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+    Default,
+    Flag(String), // `--no-mailer`
+    Protocol(Protocol),
+    Requires(String), // the feature it needs
+}
+
+// how a feature ended up, and why
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decision {
+    pub(super) enabled: bool,
+    pub(super) name: String,
+    pub(super) reason: Reason,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Outcome {
+    pub(super) decisions: Vec<Decision>, // in catalog order
+    pub(super) warnings: Vec<String>,
+}
+
+impl Choice {
+    pub fn new(enabled: bool, reason: Reason) -> Self {
+        Choice { enabled, reason }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn reason(&self) -> &Reason {
+        &self.reason
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::Default => write!(f, "default"),
+            Reason::Flag(flag) => write!(f, "{}", flag),
+            Reason::Protocol(Protocol::Rest) => write!(f, "--protocol rest"),
+            Reason::Protocol(Protocol::Grpc) => write!(f, "--protocol grpc"),
+            Reason::Requires(name) => write!(f, "requires `{}`", name),
+        }
+    }
+}
+
+impl Decision {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn reason(&self) -> &Reason {
+        &self.reason
+    }
+}
+
+impl Outcome {
+    pub fn decisions(&self) -> &[Decision] {
+        &self.decisions
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Decision> {
+        self.decisions.iter().find(|d| d.name == name)
+    }
+
+    // one line per feature, for `--explain`
+    pub fn explain(&self) -> String {
+        let width = self.decisions.iter().map(|d| d.name.len()).max();
+
+        self.decisions
+            .iter()
+            .map(|d| {
+                let state = if d.enabled { "enabled" } else { "disabled" };
+
+                format!(
+                    "{:width$}  {:8}  {}",
+                    d.name,
+                    state,
+                    self.because(d),
+                    width = width.unwrap_or_default()
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // down the chain of `requires`, to what started it
+    fn because(&self, decision: &Decision) -> String {
+        match &decision.reason {
+            Reason::Requires(name) => match self.get(name) {
+                Some(required) => format!(
+                    "{}, disabled by {}",
+                    decision.reason,
+                    self.because(required)
+                ),
+                None => decision.reason.to_string(),
+            },
+            reason => reason.to_string(),
+        }
+    }
+}
+
+// Every catalog feature enabled unless chosen otherwise,
+// features go when one they `require` does; a feature
+// kept without one it `implies`, or with one it
+// `conflicts` with, is an error
+pub fn evaluate(catalog: &Catalog, choices: &BTreeMap<String, Choice>) -> Result<Outcome> {
+    let mut decisions = catalog
+        .features()
+        .iter()
+        .map(|f| {
+            let choice = choices
+                .get(f.name())
+                .cloned()
+                .unwrap_or(Choice::new(true, Reason::Default));
+
+            Decision {
+                enabled: choice.enabled,
+                name: f.name().to_string(),
+                reason: choice.reason,
+            }
+        })
+        .collect::<Vec<Decision>>();
+    let mut warnings = vec![];
+
+    let is_enabled =
+        |decisions: &[Decision], name: &str| decisions.iter().any(|d| d.name == name && d.enabled);
+
+    // until nothing else has to go
+    loop {
+        let missing = catalog.features().iter().find_map(|f| {
+            let required = f.requires().iter().find(|r| !is_enabled(&decisions, r))?;
+
+            is_enabled(&decisions, f.name()).then(|| (f.name(), required.to_string()))
+        });
+
+        let (name, required) = match missing {
+            Some(missing) => missing,
+            None => break,
+        };
+
+        warnings.push(format!(
+            "`{}` requires `{}`, which is disabled, so it is disabled too",
+            name, required
+        ));
+
+        if let Some(decision) = decisions.iter_mut().find(|d| d.name == name) {
+            decision.enabled = false;
+            decision.reason = Reason::Requires(required);
+        }
+    }
+
+    let outcome = Outcome {
+        decisions,
+        warnings,
+    };
+
+    for feature in catalog.features() {
+        if !is_enabled(&outcome.decisions, feature.name()) {
+            continue;
+        }
+
+        if let Some(implied) = feature
+            .implies()
+            .iter()
+            .find(|i| !is_enabled(&outcome.decisions, i))
+        {
+            bail!(
+                "`{}` implies `{}`, which is disabled by {}; disable `{}` as well with {}",
+                feature.name(),
+                implied,
+                outcome.because(outcome.get(implied).expect("catalog feature")),
+                feature.name(),
+                feature.cli_flag()
+            );
+        }
+
+        if let Some(conflict) = feature
+            .conflicts()
+            .iter()
+            .find(|c| is_enabled(&outcome.decisions, c))
+        {
+            let flag = |name: &str| catalog.get(name).map(|f| f.cli_flag()).unwrap_or_default();
+
+            bail!(
+                "`{}` conflicts with `{}`, disable one of them with {} or {}",
+                feature.name(),
+                conflict,
+                flag(feature.name()),
+                flag(conflict)
+            );
+        }
+    }
+
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOG: &str = r#"
+[[features]]
+name = "database"
+flag = "no-database"
+
+[[features]]
+name = "audit"
+flag = "no-audit"
+requires = ["database"]
+
+[[features]]
+name = "admin"
+flag = "no-admin"
+requires = ["audit"]
+
+[[features]]
+name = "mailer"
+flag = "no-mailer"
+implies = ["templates"]
+
+[[features]]
+name = "templates"
+flag = "no-templates"
+
+[[features]]
+name = "grpc"
+conflicts = ["rest"]
+
+[[features]]
+name = "rest"
+"#;
+
+    fn choices(choices: &[(&str, bool, Reason)]) -> BTreeMap<String, Choice> {
+        choices
+            .iter()
+            .map(|(name, enabled, reason)| {
+                (name.to_string(), Choice::new(*enabled, reason.clone()))
+            })
+            .collect()
+    }
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::parse(CATALOG, "toml").unwrap();
+
+        catalog
+            .features
+            .retain(|f| f.name != "grpc" && f.name != "rest");
+
+        catalog
+    }
+
+    #[test]
+    fn cascade_requires() {
+        let flag = Reason::Flag("--no-database".to_string());
+        let outcome = evaluate(&catalog(), &choices(&[("database", false, flag)])).unwrap();
+
+        assert!(!outcome.get("audit").unwrap().enabled());
+        assert_eq!(
+            outcome.get("admin").unwrap().reason(),
+            &Reason::Requires("audit".to_string())
+        );
+        assert!(outcome.get("mailer").unwrap().enabled());
+        assert_eq!(outcome.warnings().len(), 2);
+        assert_eq!(
+            outcome.explain().lines().nth(2).unwrap(),
+            "admin      disabled  requires `audit`, disabled by requires `database`, \
+             disabled by --no-database"
+        );
+    }
+
+    #[test]
+    fn broken_implies() {
+        let flag = Reason::Flag("--no-templates".to_string());
+        let error = evaluate(&catalog(), &choices(&[("templates", false, flag)])).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "`mailer` implies `templates`, which is disabled by --no-templates; \
+             disable `mailer` as well with --no-mailer"
+        );
+
+        let both = choices(&[
+            ("mailer", false, Reason::Flag("--no-mailer".to_string())),
+            (
+                "templates",
+                false,
+                Reason::Flag("--no-templates".to_string()),
+            ),
+        ]);
+        assert!(evaluate(&catalog(), &both).is_ok());
+    }
+
+    #[test]
+    fn conflicting_features() {
+        let catalog = Catalog::parse(CATALOG, "toml").unwrap();
+
+        let error = evaluate(&catalog, &BTreeMap::new()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`grpc` conflicts with `rest`, disable one of them with \
+             --without grpc or --without rest"
+        );
+
+        let grpc = choices(&[
+            ("grpc", true, Reason::Protocol(Protocol::Grpc)),
+            ("rest", false, Reason::Protocol(Protocol::Grpc)),
+        ]);
+        let outcome = evaluate(&catalog, &grpc).unwrap();
+
+        assert_eq!(
+            outcome.get("grpc").unwrap().reason().to_string(),
+            "--protocol grpc"
+        );
+        assert!(outcome.warnings().is_empty());
+    }
+}
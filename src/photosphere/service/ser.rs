@@ -1,289 +1,1048 @@
-use super::{de::DEPS_START, dep::Env, Service};
-use anyhow::Result;
-use std::fs;
+use super::{de, dep::Dep, feature::Feature, Service};
+use crate::parser::cst::{SyntaxKind, SyntaxNode, SyntaxTree};
+use crate::photosphere::str_utils;
+use anyhow::{anyhow, bail, Result};
+use std::{
+    collections::BTreeSet,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
 
-const GIT_URL: &'static &str = &"git@github.com:solfacil/PKG.git";
+const HEALTH_MODULE: &str = include_str!("../../../priv/health.ex");
+const HEALTH_PORT: &str = r#"String.to_integer(System.get_env("PORT", "4000"))"#;
 
-// OH GOSH we need a serious parser...
+// where the template keeps its env variables
+const ENV_FILES: &[&str] = &[".env-sample"];
+
+// writes the service deps to mix.exs, entries
+// that didn't change are left as they are
 pub fn dump_deps(service: &Service) -> Result<()> {
     let mix_path = service.path.as_path().join("mix.exs");
-    let mix_exs = fs::read_to_string(mix_path.clone())?;
+    let mut tree = SyntaxTree::parse(&fs::read_to_string(mix_path.clone())?)?;
 
-    let mut deps_str = "".to_owned();
+    let nodes = de::parse_dep_nodes(&tree)?;
+    let missing = service
+        .deps
+        .iter()
+        .filter(|dep| !nodes.iter().any(|(_, d)| d.name.eq(&dep.name)))
+        .collect::<Vec<_>>();
 
-    for dep in service.deps.iter() {
-        let base_str = r#"{:NAME, "~> VERSION""#.to_owned();
-        let mut dep_str = base_str
-            .replace("NAME", &dep.name)
-            .replace("VERSION", &dep.version);
+    // last to first, so earlier ranges stay valid
+    for (range, current) in nodes.into_iter().rev() {
+        match service.deps.iter().find(|d| d.name.eq(&current.name)) {
+            Some(dep) if dep.ne(&current) => tree.replace(range, &dep.to_string())?,
+            Some(_) => {}
+            None => tree.remove(range)?,
+        }
+    }
 
-        if let Some(envs) = &dep.envs {
-            let mut env_str = ", only: [".to_owned();
+    for dep in missing {
+        let list = de::deps_node(&tree)?.clone();
+        tree.insert(&list, list.children().len(), &dep.to_string())?;
+    }
 
-            for env in envs.iter() {
-                if envs.len() == 1 {
-                    env_str.push_str(env.to_str());
-                    break;
-                }
+    fs::write(mix_path, tree.to_string())?;
 
-                let extra_env = &(env.to_str().to_owned() + ", ");
-                env_str.push_str(extra_env);
-            }
+    Ok(())
+}
 
-            env_str.push(']');
-            dep_str.push_str(&env_str);
-        }
+// a single new tuple in `deps/0`, in alphabetical
+// order when the list is sorted, last otherwise
+pub fn add_dep(root: &Path, dep: &Dep) -> Result<()> {
+    dep.validate()?;
 
-        if let Some(runtime) = dep.runtime {
-            dep_str.push_str(&format!(", runtime: {}", runtime));
-        }
+    let mix_path = root.join("mix.exs");
+    let mut tree = SyntaxTree::parse(&fs::read_to_string(&mix_path)?)?;
 
-        if dep.git {
-            let pkg_url = GIT_URL.replace("PKG", &dep.name);
-            let git_str = r#", git: "URL""#;
-            dep_str.push_str(&git_str.replace("URL", &pkg_url));
+    let names = de::parse_dep_nodes(&tree)?
+        .into_iter()
+        .map(|(_, d)| d.name)
+        .collect::<Vec<String>>();
 
-            let tag_str = r#", tag: "V""#;
-            dep_str.push_str(&tag_str.replace('V', &dep.version));
-        }
+    if names.contains(&dep.name) {
+        bail!(
+            "`{}` is already a dependency in {}",
+            dep.name,
+            mix_path.display()
+        );
+    }
 
-        if let Some(conflict) = dep.conflict {
-            dep_str.push_str(&format!(", override: {}", conflict));
-        }
+    let is_sorted = names.windows(2).all(|pair| pair[0] <= pair[1]);
+    let index = match is_sorted {
+        true => names.iter().take_while(|name| **name < dep.name).count(),
+        false => names.len(),
+    };
 
-        dep_str.push_str("}\n");
-        deps_str.push_str(&dep_str);
-    }
+    let list = de::deps_node(&tree)?.clone();
+    tree.insert(&list, index, &dep.to_string())?;
 
-    fs::write(mix_path, mix_exs.replace(DEPS_START, &deps_str))?;
+    fs::write(mix_path, tree.to_string())?;
 
     Ok(())
 }
 
-pub fn nuke_auth(service: &Service) -> Result<()> {
-    let root = service.path.as_path();
+// takes the tuple out with its line, comments
+// around it stay; the removed dep is returned
+pub fn remove_dep(root: &Path, name: &str) -> Result<Dep> {
+    let mix_path = root.join("mix.exs");
+    let mut tree = SyntaxTree::parse(&fs::read_to_string(&mix_path)?)?;
 
-    let auth_path = root
-        .join("lib")
-        .join(format!("{}_web", service.name))
-        .join("auth");
-    fs::remove_dir_all(auth_path)?;
-
-    let runtime_path = root.join("config").join("runtime.exs");
-    let runtime = fs::read_to_string(runtime_path.clone())?;
-
-    let no_auth: String = runtime
-        .lines()
-        .filter(|l| {
-            !l.contains("Guardian")
-                || !l.contains("GUARDIAN")
-                || !l.contains("ttl")
-                || !l.contains("issuer")
-        })
-        .collect::<Vec<&str>>()
-        .join("\n");
-    fs::write(runtime_path, no_auth)?;
+    let (range, dep) = match de::parse_dep_nodes(&tree)?
+        .into_iter()
+        .find(|(_, d)| d.name.eq(name))
+    {
+        Some(node) => node,
+        None => bail!("`{}` is not a dependency in {}", name, mix_path.display()),
+    };
 
-    Ok(())
+    tree.remove(range)?;
+
+    fs::write(mix_path, tree.to_string())?;
+
+    Ok(dep)
 }
 
-// FIXME - needs to also remove ecto lines
-pub fn nuke_database(service: &Service) -> Result<()> {
+// what the catalog says a feature owns: its files and
+// directories, the code that refers to it and its
+// variables in the env files
+pub fn nuke_feature(service: &Service, feature: &Feature) -> Result<()> {
     let root = service.path.as_path();
 
-    let config_path = root.join("config");
+    for path in feature.paths(&service.name) {
+        let path = root.join(path);
 
-    // Clean config files
-    for c in fs::read_dir(config_path)?.filter_map(|f| f.ok()) {
-        let data = fs::read_to_string(c.path())?;
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else if path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
 
-        let new_data: String = data
-            .lines()
-            .filter(|l| is_database_text(l))
-            .collect::<Vec<&str>>()
-            .join("\n");
+    nuke_references(service, feature)?;
 
-        fs::write(c.path(), new_data)?;
+    for env_file in ENV_FILES.iter().map(|f| root.join(f)) {
+        if !env_file.exists() || feature.env().is_empty() {
+            continue;
+        }
+
+        let data = fs::read_to_string(&env_file)?;
+        let kept = data
+            .split_inclusive('\n')
+            .filter(|l| !feature.owns_env(env_var(l)))
+            .collect::<String>();
+
+        fs::write(env_file, kept)?;
     }
 
-    let telemetry_path = root
-        .join("lib")
-        .join(format!("{}_web", service.name))
-        .join("telemetry.ex");
-    let telemetry = fs::read_to_string(telemetry_path.clone())?;
-    let tel_data: String = telemetry
-        .lines()
-        .filter(|l| is_database_text(l))
-        .collect::<Vec<&str>>()
-        .join("\n");
-    fs::write(telemetry_path, tel_data)?;
+    Ok(())
+}
+
+// the nix shell tools that only make sense with gRPC
+pub fn nuke_grpc(service: &Service) -> Result<()> {
+    let flake_path = service.path.as_path().join("flake.nix");
 
-    fs::remove_file(root.join("test").join("support").join("data_case.ex"))?;
+    if !flake_path.exists() {
+        return Ok(());
+    }
 
-    fs::remove_dir_all(root.join("priv").join("repo"))?;
+    let data = fs::read_to_string(&flake_path)?;
+    fs::write(flake_path, remove_words(&data, &["grpcurl", "protobuf"]))?;
 
     Ok(())
 }
 
-pub fn nuke_graphql(service: &Service) -> Result<()> {
-    let web_path = service
-        .path
-        .as_path()
-        .join("lib")
-        .join(format!("{}_web", service.name));
+// a gRPC-only service gets an HTTP health
+// endpoint only when asked for one
+pub fn nuke_rest(service: &Service) -> Result<()> {
+    if service.health {
+        add_health_endpoint(service)?;
+    }
 
-    let endpoint_path = web_path.join("endpoint.ex");
-    let endpoint = fs::read_to_string(endpoint_path.clone())?;
+    Ok(())
+}
+
+// a plug of its own, started along the other children
+fn add_health_endpoint(service: &Service) -> Result<()> {
+    let root = service.path.as_path();
+    let module = str_utils::to_pascal_case(&service.name);
 
     fs::write(
-        endpoint_path,
-        endpoint
-            .replace(", Absinthe.Plug.Parser", "")
-            .lines()
-            .filter(|l| !l.contains("Absinthe") || !l.contains("GraphQL"))
-            .collect::<Vec<&str>>()
-            .join("\n"),
+        root.join("lib")
+            .join(format!("{}_web", service.name))
+            .join("health.ex"),
+        HEALTH_MODULE.replace("{module}", &module),
     )?;
 
-    fs::remove_dir_all(web_path.join("graphql"))?;
+    let application_path = root.join("lib").join(&service.name).join("application.ex");
+    let mut tree = SyntaxTree::parse(&fs::read_to_string(&application_path)?)?;
+
+    let children = tree
+        .root()
+        .descendants()
+        .into_iter()
+        .find(|n| {
+            n.kind() == SyntaxKind::BinaryOp
+                && n.children().len() == 2
+                && tree.text(n.children()[0].range()) == "children"
+                && n.children()[1].kind() == SyntaxKind::List
+        })
+        .map(|n| n.children()[1].clone())
+        .ok_or_else(|| anyhow!("no `children` list in {}", application_path.display()))?;
+
+    let child = format!(
+        "{{Plug.Cowboy, scheme: :http, plug: {}Web.Health, options: [port: {}]}}",
+        module, HEALTH_PORT
+    );
+    tree.insert(&children, children.children().len(), &child)?;
+
+    fs::write(application_path, tree.to_string())?;
 
     Ok(())
 }
 
-pub fn nuke_grpc(service: &Service) -> Result<()> {
-    let root = service.path.as_path();
+// every statement, list element or keyword pair in the
+// service code that refers to the feature, a call taking
+// it as an argument goes as a whole, and so do the
+// variables only the removed code used
+fn nuke_references(service: &Service, feature: &Feature) -> Result<()> {
+    let is_reference = |lexeme: &str| feature.owns_reference(&service.name, lexeme);
+    // a reference spells one of these out, files
+    // without any are left alone, unparsed
+    let names = feature
+        .config(&service.name)
+        .iter()
+        .map(|c| c.trim_start_matches(':').trim_end_matches('*').to_string())
+        .collect::<Vec<String>>();
 
-    let web_path = root.join("lib").join(format!("{}_web", service.name));
+    for path in source_files(service.path.as_path()) {
+        let data = fs::read_to_string(&path)?;
 
-    let config_path = root.join("config");
+        if !names.iter().any(|name| data.contains(name.as_str())) {
+            continue;
+        }
 
-    // Clean config files
-    for c in fs::read_dir(config_path)?.filter_map(|f| f.ok()) {
-        let data = fs::read_to_string(c.path())?;
+        let mut tree = match SyntaxTree::parse(&data) {
+            Ok(tree) => tree,
+            Err(e) => {
+                eprintln!(
+                    "\u{001b}[33mwarning: {} left as is, it doesn't parse: {}\u{001b}[0m",
+                    path.display(),
+                    e
+                );
+                continue;
+            }
+        };
+        let mut removed = BTreeSet::<String>::new();
 
-        let new_data: String = data
-            .lines()
-            .filter(|l| !l.contains("gRPC") || !l.contains("grpc"))
-            .collect::<Vec<&str>>()
-            .join("\n");
+        // one at a time, the tree is rebuilt on every removal
+        while let Some(range) =
+            last_reference(&tree, is_reference).or_else(|| unused_binding(&tree, &removed))
+        {
+            removed.extend(identifiers(&tree, range.clone()));
+            tree.remove(range)?;
+        }
 
-        fs::write(c.path(), new_data)?;
+        let new_data = tree.to_string();
+        if new_data != data {
+            fs::write(path, new_data)?;
+        }
     }
 
-    let flake_path = root.join("flake.nix");
-    fs::write(
-        flake_path.clone(),
-        fs::read_to_string(flake_path)?
-            .lines()
-            .filter(|l| !l.contains("grpcurl") || !l.contains("protobuf"))
-            .collect::<Vec<&str>>()
-            .join("\n"),
-    )?;
-
-    let endpoint_path = web_path.join("endpoint.ex");
-    fs::write(
-        endpoint_path.clone(),
-        fs::read_to_string(endpoint_path)?
-            .lines()
-            .filter(|l| !l.contains("GRPC"))
-            .collect::<Vec<&str>>()
-            .join("\n"),
-    )?;
+    Ok(())
+}
 
-    fs::remove_dir_all(web_path.join("grpc"))?;
+// `config/*.exs` and `lib/**/*.ex`
+fn source_files(root: &Path) -> Vec<PathBuf> {
+    [("config", "exs"), ("lib", "ex")]
+        .iter()
+        .flat_map(|(dir, extension)| {
+            WalkDir::new(root.join(dir))
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .map(|e| e.into_path())
+                .filter(move |p| p.is_file() && p.extension().is_some_and(|e| e == *extension))
+        })
+        .collect()
+}
 
-    Ok(())
+// what to remove for the last token that is a reference
+fn last_reference<P>(tree: &SyntaxTree, is_reference: P) -> Option<Range<usize>>
+where
+    P: Fn(&str) -> bool,
+{
+    (0..tree.tokens().len())
+        .rev()
+        .filter(|index| reference(tree, *index).is_some_and(|r| is_reference(&r)))
+        .find_map(|index| removal_node(tree.root(), index).map(|n| n.range()))
 }
 
-pub fn nuke_http_client(service: &Service) -> Result<()> {
-    let config_path = service.path.as_path().join("config").join("config.exs");
+// a keyword key, `pool_size:`, is the atom `:pool_size`; a
+// bare atom is data, `[:phoenix, :endpoint, :stop]`, but as
+// the app of `config :phoenix, ...`, or a module, `:gun.open`
+fn reference(tree: &SyntaxTree, index: usize) -> Option<String> {
+    let tokens = tree.tokens();
+    let token = &tokens[index];
+    let lexeme = token.lexeme();
 
-    fs::write(
-        config_path.clone(),
-        fs::read_to_string(config_path)?
-            .lines()
-            .filter(|l| !l.contains(":tesla"))
-            .collect::<Vec<&str>>()
-            .join("\n"),
-    )?;
+    let is_key = token.kind().is_identifier()
+        && tokens
+            .get(index + 1)
+            .is_some_and(|t| t.kind().is_operator() && t.lexeme().eq(":"));
 
-    Ok(())
+    if is_key {
+        return Some(format!(":{}", lexeme));
+    }
+
+    let is_data = token.kind().is_atom() && !lexeme.contains('.') && !is_config_app(tree, index);
+
+    (!is_data).then_some(lexeme)
 }
 
-pub fn nuke_mailer(service: &Service) -> Result<()> {
-    let root = service.path.as_path();
+// `config :app` and `config(:app`
+fn is_config_app(tree: &SyntaxTree, index: usize) -> bool {
+    let mut before = tree.tokens()[..index]
+        .iter()
+        .rev()
+        .filter(|t| !t.kind().is_whitespace());
 
-    let web_path = root.join("lib").join(format!("{}_web", service.name));
+    match before.next().map(|t| t.lexeme()).as_deref() {
+        Some("config") => true,
+        Some("(") => before.next().is_some_and(|t| t.lexeme() == "config"),
+        _ => false,
+    }
+}
 
-    fs::remove_dir_all(web_path.join("mailer"))?;
+// the innermost keyword pair, list element or statement
+// holding the token, skipping the arguments of calls; a
+// call left without its only keyword pair goes too, an
+// element of a list of literals is left alone
+fn removal_node(root: &SyntaxNode, index: usize) -> Option<&SyntaxNode> {
+    let mut path = vec![root];
 
-    let runtime_path = root.join("config").join("runtime.exs");
-    fs::write(
-        runtime_path.clone(),
-        fs::read_to_string(runtime_path)?
-            .lines()
-            .filter(|l| !l.contains("MAILER") || !l.contains("Mailer") || !l.contains("mailer"))
-            .collect::<Vec<&str>>()
-            .join("\n"),
-    )?;
+    while let Some(child) = path
+        .last()
+        .and_then(|n| n.children().iter().find(|c| c.range().contains(&index)))
+    {
+        path.push(child);
+    }
 
-    fs::write(
-        root.join(".env-sample"),
-        fs::read_to_string(root.join(".env-sample"))?
-            .lines()
-            .filter(|l| !l.contains("MAILER"))
-            .collect::<Vec<&str>>()
-            .join("\n"),
-    )?;
+    for pair in path.windows(2).rev() {
+        let (parent, node) = (pair[0], pair[1]);
 
-    Ok(())
-}
+        match (parent.kind(), node.kind()) {
+            (SyntaxKind::Pair, _) => {}
+            (SyntaxKind::Call, SyntaxKind::Pair) if pairs(parent) == 1 => {}
+            (_, SyntaxKind::Pair) => return Some(node),
+            (SyntaxKind::List, _) if is_data_element(parent, node) => return None,
+            (SyntaxKind::List | SyntaxKind::Block | SyntaxKind::Root, _) => return Some(node),
+            _ => {}
+        }
+    }
 
-pub fn nuke_messaging(service: &Service) -> Result<()> {
-    let config_path = service.path.as_path().join("config");
+    None
+}
 
-    // Clean config files
-    for c in fs::read_dir(config_path)?.filter_map(|f| f.ok()) {
-        let data = fs::read_to_string(c.path())?;
+fn is_data_element(list: &SyntaxNode, node: &SyntaxNode) -> bool {
+    let mut siblings = list.children().iter().filter(|c| c.range() != node.range());
 
-        let new_data: String = data
-            .lines()
-            .filter(|l| !l.contains("messaging") || !l.contains("Messaging"))
-            .filter(|l| {
-                !l.contains("kafka_ex")
-                    || !l.contains("brokers")
-                    || !l.contains("disable_default_broker")
-            })
-            .collect::<Vec<&str>>()
-            .join("\n");
+    list.children().len() > 1 && siblings.all(is_literal)
+}
 
-        fs::write(c.path(), new_data)?;
+// atoms, numbers, strings and the tuples and lists of them
+fn is_literal(node: &SyntaxNode) -> bool {
+    match node.kind() {
+        SyntaxKind::Atom
+        | SyntaxKind::Boolean
+        | SyntaxKind::Char
+        | SyntaxKind::Nil
+        | SyntaxKind::Number
+        | SyntaxKind::String => true,
+        SyntaxKind::List | SyntaxKind::Tuple => node.children().iter().all(is_literal),
+        _ => false,
     }
+}
 
-    Ok(())
+fn pairs(node: &SyntaxNode) -> usize {
+    node.children()
+        .iter()
+        .filter(|c| c.kind() == SyntaxKind::Pair)
+        .count()
 }
 
-// TODO
-pub fn nuke_monitoring(_service: &Service) -> Result<()> {
-    Ok(())
+// a `name = ...` statement binding one of `names`
+// that nothing else in the file uses anymore
+fn unused_binding(tree: &SyntaxTree, names: &BTreeSet<String>) -> Option<Range<usize>> {
+    let statements = tree
+        .root()
+        .descendants()
+        .into_iter()
+        .filter(|n| matches!(n.kind(), SyntaxKind::Block | SyntaxKind::Root))
+        .flat_map(|n| n.children())
+        .collect::<Vec<&SyntaxNode>>();
+
+    statements.into_iter().rev().find_map(|statement| {
+        let name = binding(tree, statement)?;
+        let bound_at = statement.range().start;
+
+        let is_used = tree.tokens().iter().enumerate().any(|(index, t)| {
+            let lexeme = t.lexeme();
+
+            (index != bound_at && t.kind().is_identifier() && lexeme == name)
+                // interpolated, `"#{name}"`
+                || (t.kind().is_string() && lexeme.contains(&name))
+        });
+
+        (names.contains(&name) && !is_used).then(|| statement.range())
+    })
 }
 
-// TODO
-pub fn nuke_rest(_service: &Service) -> Result<()> {
-    Ok(())
+// the variable of `name = ...`
+fn binding(tree: &SyntaxTree, node: &SyntaxNode) -> Option<String> {
+    let lhs = match node.children() {
+        [lhs, _] if node.kind() == SyntaxKind::BinaryOp && lhs.kind() == SyntaxKind::Var => lhs,
+        _ => return None,
+    };
+
+    let operator = tree.tokens()[lhs.range().end..]
+        .iter()
+        .find(|t| !t.kind().is_whitespace())?;
+
+    operator.lexeme().eq("=").then(|| tree.text(lhs.range()))
+}
+
+fn identifiers(tree: &SyntaxTree, range: Range<usize>) -> Vec<String> {
+    tree.tokens()[range]
+        .iter()
+        .filter(|t| t.kind().is_identifier())
+        .map(|t| t.lexeme())
+        .collect()
+}
+
+// drops `words` where they stand alone, and
+// the lines they leave blank
+fn remove_words(data: &str, words: &[&str]) -> String {
+    data.split_inclusive('\n')
+        .filter_map(|line| {
+            if !line.split_whitespace().any(|w| words.contains(&w)) {
+                return Some(line.to_string());
+            }
+
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let kept = line
+                .split_whitespace()
+                .filter(|w| !words.contains(w))
+                .collect::<Vec<&str>>();
+
+            (!kept.is_empty()).then(|| {
+                let ending = if line.ends_with('\n') { "\n" } else { "" };
+
+                format!("{}{}{}", indent, kept.join(" "), ending)
+            })
+        })
+        .collect()
 }
 
-fn is_database_text(line: &str) -> bool {
-    line.contains("database")
-        || line.contains("DATABASE")
-        || line.contains("Repo")
-        || line.contains("repo")
-        || line.contains("POOL_SIZE")
-        || line.contains("ECTO_IPV6")
-        || line.contains("username")
-        || line.contains("password")
-        || line.contains("pool")
-        || line.contains("pool_size")
-        || line.contains("hostname")
-        || line.contains("show_sensitive_data_on_connection_error")
+// `export FOO=bar` and `FOO=bar` are both `FOO`
+fn env_var(line: &str) -> &str {
+    let line = line.trim_start();
+    let line = line.strip_prefix("export ").unwrap_or(line);
+
+    line.split('=').next().unwrap_or_default().trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photosphere::service::{dep::Env, version::VersionRequirement};
+
+    const MIX_EXS: &str = r#"defmodule Foo.MixProject do
+  use Mix.Project
+
+  defp deps do
+    [
+      # start deps
+      {:phoenix, "~> 1.6.6"},
+      {:guardian, "~> 2.0"},
+      {:jason, "~> 1.2"},
+      # code analysis
+      {:credo, "~> 1.6", only: [:dev, :test], runtime: false},
+      {:absinthe, "~> 1.6.0"}
+      # end deps
+    ]
+  end
+end
+"#;
+
+    #[test]
+    fn dump_filtered_deps() {
+        let root = std::env::temp_dir().join("photosphere_dump_deps");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("mix.exs"), MIX_EXS).unwrap();
+
+        let mut service = Service::default();
+        let mut deps = de::parse_deps(&root).unwrap();
+        deps.retain(|d| ["phoenix", "jason", "credo"].contains(&d.name.as_str()));
+        service.set_path(root.display().to_string()).set_deps(deps);
+
+        dump_deps(&service).unwrap();
+
+        let expected = MIX_EXS
+            .replace("      {:guardian, \"~> 2.0\"},\n", "")
+            .replace(",\n      {:absinthe, \"~> 1.6.0\"}", "");
+        assert_eq!(fs::read_to_string(root.join("mix.exs")).unwrap(), expected);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn dump_changed_deps() {
+        let root = std::env::temp_dir().join("photosphere_dump_changed_deps");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("mix.exs"), MIX_EXS).unwrap();
+
+        let mut service = Service::default();
+        service.set_path(root.display().to_string());
+
+        let mut deps = de::parse_deps(&root).unwrap();
+        deps[2].set_version(VersionRequirement::parse("~> 1.3").ok());
+        deps.push(
+            Dep::new()
+                .set_name("grpc".to_string())
+                .set_github(Some("elixir-grpc/grpc".to_string()))
+                .clone(),
+        );
+        service.set_deps(deps);
+
+        dump_deps(&service).unwrap();
+
+        let expected = MIX_EXS.replace("~> 1.2", "~> 1.3").replace(
+            "{:absinthe, \"~> 1.6.0\"}",
+            "{:absinthe, \"~> 1.6.0\"},\n      {:grpc, github: \"elixir-grpc/grpc\"}",
+        );
+        assert_eq!(fs::read_to_string(root.join("mix.exs")).unwrap(), expected);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn dump_template_deps() {
+        let template = Path::new(env!("CARGO_MANIFEST_DIR")).join("priv");
+        let root = std::env::temp_dir().join("photosphere_dump_template_deps");
+        fs::create_dir_all(&root).unwrap();
+        fs::copy(template.join("mix.exs"), root.join("mix.exs")).unwrap();
+
+        let mut service = Service::default();
+        service
+            .set_path(root.display().to_string())
+            .set_deps(de::parse_deps(&root).unwrap());
+
+        dump_deps(&service).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(root.join("mix.exs")).unwrap(),
+            fs::read_to_string(template.join("mix.exs")).unwrap()
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn add_dep_last() {
+        let root = std::env::temp_dir().join("photosphere_add_dep_last");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("mix.exs"), MIX_EXS).unwrap();
+
+        let dep = Dep::new()
+            .set_name("mox".to_string())
+            .set_version(VersionRequirement::parse("~> 1.0").ok())
+            .set_envs(Some(vec![Env::Test]))
+            .clone();
+
+        add_dep(&root, &dep).unwrap();
+
+        let expected = MIX_EXS.replace(
+            "{:absinthe, \"~> 1.6.0\"}",
+            "{:absinthe, \"~> 1.6.0\"},\n      {:mox, \"~> 1.0\", only: [:test]}",
+        );
+        assert_eq!(fs::read_to_string(root.join("mix.exs")).unwrap(), expected);
+        assert!(add_dep(&root, &dep).is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn add_sorted_dep() {
+        let mix_exs = r#"defmodule Foo.MixProject do
+  use Mix.Project
+
+  defp deps do
+    # keep them sorted
+    [
+      {:credo, "~> 1.6", only: [:dev, :test], runtime: false},
+      {:jason, "~> 1.2"},
+      {:phoenix, "~> 1.6.6"}
+    ]
+  end
+end
+"#;
+        let root = std::env::temp_dir().join("photosphere_add_sorted_dep");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("mix.exs"), mix_exs).unwrap();
+
+        let dep = Dep::new()
+            .set_name("database".to_string())
+            .set_git(Some("git@github.com:solfacil/database.git".to_string()))
+            .set_tag(Some("0.0.7".to_string()))
+            .clone();
+
+        add_dep(&root, &dep).unwrap();
+
+        let expected = mix_exs.replace(
+            "      {:jason",
+            "      {:database, git: \"git@github.com:solfacil/database.git\", tag: \"0.0.7\"},\n      {:jason",
+        );
+        assert_eq!(fs::read_to_string(root.join("mix.exs")).unwrap(), expected);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn add_invalid_dep() {
+        let root = std::env::temp_dir().join("photosphere_add_invalid_dep");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("mix.exs"), MIX_EXS).unwrap();
+
+        let dep = Dep::new()
+            .set_name("foo".to_string())
+            .set_tag(Some("0.1.0".to_string()))
+            .clone();
+
+        assert!(add_dep(&root, &dep).is_err());
+        assert_eq!(fs::read_to_string(root.join("mix.exs")).unwrap(), MIX_EXS);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn remove_single_dep() {
+        let root = std::env::temp_dir().join("photosphere_remove_single_dep");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("mix.exs"), MIX_EXS).unwrap();
+
+        let removed = remove_dep(&root, "credo").unwrap();
+
+        assert_eq!(removed.name, "credo");
+        assert_eq!(
+            fs::read_to_string(root.join("mix.exs")).unwrap(),
+            MIX_EXS.replace(
+                "      {:credo, \"~> 1.6\", only: [:dev, :test], runtime: false},\n",
+                ""
+            )
+        );
+        assert!(remove_dep(&root, "credo").is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn nuke_catalog_feature() {
+        let root = std::env::temp_dir().join("photosphere_nuke_catalog_feature");
+        let mailer_path = root.join("lib").join("foo_web").join("mailer");
+        fs::create_dir_all(&mailer_path).unwrap();
+        fs::write(mailer_path.join("mailer.ex"), "").unwrap();
+        fs::write(
+            root.join(".env-sample"),
+            "PORT=4000\nMAILER_API_KEY=\nexport MAILER_DOMAIN=foo.com\n",
+        )
+        .unwrap();
+
+        let mut service = Service::default();
+        service
+            .set_name("foo".to_string())
+            .set_path(root.display().to_string());
+        let mailer = service.catalog().get("mailer").unwrap();
+
+        nuke_feature(&service, mailer).unwrap();
+        // nothing left to take out
+        nuke_feature(&service, mailer).unwrap();
+
+        assert!(!mailer_path.exists());
+        assert!(root.join("lib").join("foo_web").exists());
+        assert_eq!(
+            fs::read_to_string(root.join(".env-sample")).unwrap(),
+            "PORT=4000\n"
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn nuke_monitoring_references() {
+        let config = r#"import Config
+
+config :foo, Foo.Repo, telemetry_prefix: [:foo, :repo], tracer: Foo.Tracer
+
+config :foo, Foo.Tracer,
+  service: :foo,
+  adapter: SpandexDatadog.Adapter
+
+config :spandex_phoenix, tracer: Foo.Tracer
+
+config :foo, Foo.PromEx,
+  disabled: false,
+  metrics_server: :disabled
+
+import_config "prod.exs"
+"#;
+        let application = r#"defmodule Foo.Application do
+  use Application
+
+  @impl true
+  def start(_type, _args) do
+    SpandexPhoenix.Telemetry.install()
+
+    :telemetry.attach(
+      "spandex-query-tracer",
+      [:foo, :repo, :query],
+      &SpandexEcto.TelemetryAdapter.handle_event/4,
+      nil
+    )
+
+    children = [
+      Foo.PromEx,
+      Foo.Repo,
+      {SpandexDatadog.ApiServer, [host: "localhost", batch_size: 10]},
+      FooWeb.Endpoint
+    ]
+
+    Supervisor.start_link(children, strategy: :one_for_one, name: Foo.Supervisor)
+  end
+end
+"#;
+        let endpoint = r#"defmodule FooWeb.Endpoint do
+  use Phoenix.Endpoint, otp_app: :foo
+  use SpandexPhoenix
+
+  plug PromEx.Plug, prom_ex_module: Foo.PromEx
+  plug Plug.RequestId
+  plug FooWeb.Router
+end
+"#;
+        let root = std::env::temp_dir().join("photosphere_nuke_monitoring_references");
+        fs::create_dir_all(root.join("config")).unwrap();
+        fs::create_dir_all(root.join("lib").join("foo")).unwrap();
+        fs::create_dir_all(root.join("lib").join("foo_web")).unwrap();
+        fs::write(root.join("config").join("config.exs"), config).unwrap();
+        fs::write(
+            root.join("lib").join("foo").join("application.ex"),
+            application,
+        )
+        .unwrap();
+        fs::write(
+            root.join("lib").join("foo_web").join("endpoint.ex"),
+            endpoint,
+        )
+        .unwrap();
+
+        let mut service = Service::default();
+        service
+            .set_name("foo".to_string())
+            .set_path(root.display().to_string());
+
+        let monitoring = service.catalog().get("monitoring").unwrap();
+
+        nuke_feature(&service, monitoring).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(root.join("config").join("config.exs")).unwrap(),
+            r#"import Config
+
+config :foo, Foo.Repo, telemetry_prefix: [:foo, :repo]
+
+import_config "prod.exs"
+"#
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("lib").join("foo").join("application.ex")).unwrap(),
+            r#"defmodule Foo.Application do
+  use Application
+
+  @impl true
+  def start(_type, _args) do
+    children = [
+      Foo.Repo,
+      FooWeb.Endpoint
+    ]
+
+    Supervisor.start_link(children, strategy: :one_for_one, name: Foo.Supervisor)
+  end
+end
+"#
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("lib").join("foo_web").join("endpoint.ex")).unwrap(),
+            r#"defmodule FooWeb.Endpoint do
+  use Phoenix.Endpoint, otp_app: :foo
+
+  plug Plug.RequestId
+  plug FooWeb.Router
+end
+"#
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn nuke_rest_with_health() {
+        let config = r#"import Config
+
+config :foo, FooWeb.Endpoint,
+  url: [host: "localhost"],
+  render_errors: [view: FooWeb.ErrorView, accepts: ~w(json), layout: false],
+  pubsub_server: Foo.PubSub
+
+config :phoenix, :json_library, Jason
+
+config :foo, Foo.Repo, pool_size: 10
+"#;
+        let application = r#"defmodule Foo.Application do
+  use Application
+
+  @impl true
+  def start(_type, _args) do
+    children = [
+      Foo.Repo,
+      FooWeb.Telemetry,
+      {Phoenix.PubSub, name: Foo.PubSub},
+      {GRPC.Server.Supervisor, {FooWeb.GRPC.Endpoint, 50051}},
+      FooWeb.Endpoint
+    ]
+
+    Supervisor.start_link(children, strategy: :one_for_one, name: Foo.Supervisor)
+  end
+end
+"#;
+        let root = std::env::temp_dir().join("photosphere_nuke_rest_with_health");
+        let web_path = root.join("lib").join("foo_web");
+        fs::create_dir_all(root.join("config")).unwrap();
+        fs::create_dir_all(root.join("lib").join("foo")).unwrap();
+        fs::create_dir_all(web_path.join("controllers")).unwrap();
+        fs::write(root.join("config").join("config.exs"), config).unwrap();
+        fs::write(
+            root.join("lib").join("foo").join("application.ex"),
+            application,
+        )
+        .unwrap();
+        fs::write(root.join("lib").join("foo_web.ex"), "").unwrap();
+        fs::write(web_path.join("router.ex"), "").unwrap();
+        fs::write(
+            web_path.join("controllers").join("health_controller.ex"),
+            "",
+        )
+        .unwrap();
+
+        let mut service = Service::default();
+        service
+            .set_name("foo".to_string())
+            .set_path(root.display().to_string())
+            .set_health(true);
+        let rest = service.catalog().get("rest").unwrap();
+
+        nuke_feature(&service, rest).unwrap();
+        nuke_rest(&service).unwrap();
+
+        assert!(!root.join("lib").join("foo_web.ex").exists());
+        assert!(!web_path.join("router.ex").exists());
+        assert!(!web_path.join("controllers").exists());
+        assert!(fs::read_to_string(web_path.join("health.ex"))
+            .unwrap()
+            .starts_with("defmodule FooWeb.Health do"));
+        assert_eq!(
+            fs::read_to_string(root.join("config").join("config.exs")).unwrap(),
+            "import Config\n\nconfig :foo, Foo.Repo, pool_size: 10\n"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("lib").join("foo").join("application.ex")).unwrap(),
+            application
+                .replace("      {Phoenix.PubSub, name: Foo.PubSub},\n", "")
+                .replace(
+                    "      FooWeb.Endpoint\n",
+                    &format!(
+                        "      {{Plug.Cowboy, scheme: :http, plug: FooWeb.Health, options: [port: {}]}}\n",
+                        HEALTH_PORT
+                    )
+                )
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn keep_telemetry_event_names() {
+        let config = r#"import Config
+
+config :phoenix, :json_library, Jason
+
+config :foo, FooWeb.Endpoint, url: [host: "localhost"]
+"#;
+        let telemetry = r#"defmodule FooWeb.Telemetry do
+  def metrics do
+    [
+      summary("phoenix.endpoint.stop.duration"),
+      counter([:phoenix, :router_dispatch, :stop]),
+      last_value("vm.memory.total")
+    ]
+  end
+
+  def events do
+    [[:phoenix, :endpoint, :stop], [:foo, :repo, :query]]
+  end
+end
+"#;
+        let root = std::env::temp_dir().join("photosphere_keep_telemetry_event_names");
+        let web_path = root.join("lib").join("foo_web");
+        fs::create_dir_all(root.join("config")).unwrap();
+        fs::create_dir_all(&web_path).unwrap();
+        fs::write(root.join("config").join("config.exs"), config).unwrap();
+        fs::write(web_path.join("telemetry.ex"), telemetry).unwrap();
+
+        let mut service = Service::default();
+        service
+            .set_name("foo".to_string())
+            .set_path(root.display().to_string());
+        let rest = service.catalog().get("rest").unwrap();
+
+        nuke_feature(&service, rest).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(root.join("config").join("config.exs")).unwrap(),
+            "import Config\n\n"
+        );
+        assert_eq!(
+            fs::read_to_string(web_path.join("telemetry.ex")).unwrap(),
+            telemetry
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn skip_sources_without_references() {
+        let header = r#"defmodule Foo.Header do
+  def size(<<size::16, _::binary>>) do
+    FooWeb.Endpoint.broadcast("frames", "size", size)
+    size
+  end
+end
+"#;
+        // no reference, so the parser never sees it
+        let frame = "defmodule Foo.Frame do\n  def decode(`frame`), do: frame\nend\n";
+        let broken = "defmodule Foo.Broken do\n  def call(`x`), do: FooWeb.Endpoint.url()\nend\n";
+        let root = std::env::temp_dir().join("photosphere_skip_sources_without_references");
+        let lib_path = root.join("lib").join("foo");
+        fs::create_dir_all(&lib_path).unwrap();
+        fs::write(lib_path.join("header.ex"), header).unwrap();
+        fs::write(lib_path.join("frame.ex"), frame).unwrap();
+        fs::write(lib_path.join("broken.ex"), broken).unwrap();
+
+        let mut service = Service::default();
+        service
+            .set_name("foo".to_string())
+            .set_path(root.display().to_string());
+        let rest = service.catalog().get("rest").unwrap();
+
+        nuke_references(&service, rest).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(lib_path.join("header.ex")).unwrap(),
+            header.replace(
+                "    FooWeb.Endpoint.broadcast(\"frames\", \"size\", size)\n",
+                ""
+            )
+        );
+        assert_eq!(
+            fs::read_to_string(lib_path.join("frame.ex")).unwrap(),
+            frame
+        );
+        assert_eq!(
+            fs::read_to_string(lib_path.join("broken.ex")).unwrap(),
+            broken
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn nuke_database_config() {
+        let config = r#"import Config
+
+config :foo,
+  ecto_repos: [Foo.Repo]
+
+config :foo, FooWeb.Endpoint, url: [host: "localhost"]
+"#;
+        let runtime = r#"import Config
+
+if config_env() == :prod do
+  database_url =
+    System.get_env("DATABASE_URL") ||
+      raise "environment variable DATABASE_URL is missing."
+
+  maybe_ipv6 = if System.get_env("ECTO_IPV6"), do: [:inet6], else: []
+
+  config :foo, Foo.Repo,
+    url: database_url,
+    pool_size: String.to_integer(System.get_env("POOL_SIZE") || "10"),
+    socket_options: maybe_ipv6
+
+  port = String.to_integer(System.get_env("PORT") || "4000")
+
+  config :foo, FooWeb.Endpoint, http: [port: port]
+end
+"#;
+        let root = std::env::temp_dir().join("photosphere_nuke_database_config");
+        fs::create_dir_all(root.join("config")).unwrap();
+        fs::write(root.join("config").join("config.exs"), config).unwrap();
+        fs::write(root.join("config").join("runtime.exs"), runtime).unwrap();
+
+        let mut service = Service::default();
+        service
+            .set_name("foo".to_string())
+            .set_path(root.display().to_string());
+        let database = service.catalog().get("database").unwrap();
+
+        nuke_feature(&service, database).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(root.join("config").join("config.exs")).unwrap(),
+            "import Config\n\nconfig :foo, FooWeb.Endpoint, url: [host: \"localhost\"]\n"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("config").join("runtime.exs")).unwrap(),
+            r#"import Config
+
+if config_env() == :prod do
+  port = String.to_integer(System.get_env("PORT") || "4000")
+
+  config :foo, FooWeb.Endpoint, http: [port: port]
+end
+"#
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn remove_standalone_words() {
+        let flake = "buildInputs = with pkgs; [\n  elixir\n  grpcurl\n  protobuf postgresql\n];\n";
+
+        assert_eq!(
+            remove_words(flake, &["grpcurl", "protobuf"]),
+            "buildInputs = with pkgs; [\n  elixir\n  postgresql\n];\n"
+        );
+    }
 }
@@ -0,0 +1,490 @@
+use anyhow::{anyhow, bail, Error, Result};
+use serde::Deserialize;
+use std::{cmp::Ordering, fmt};
+
+// a semver version, `1.6.6` or `2.0.0-rc.1+build`
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Vec<Identifier>,
+    build: Option<String>, // ignored when comparing
+}
+
+// numeric identifiers sort before alphanumeric ones
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Identifier {
+    Numeric(u64),
+    Alphanumeric(String),
+}
+
+// Elixir's requirement syntax, `~> 0.14 and >= 0.14.4`,
+// `and` binds tighter than `or`; written back as is
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct VersionRequirement {
+    raw: String,
+    alternatives: Vec<Vec<Condition>>, // `or` of `and`s
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Condition {
+    op: Operator,
+    version: Version,
+    parts: usize, // `~> 1.6` has two, `~> 1.6.0` three
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Compatible, // ~>
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Ne,
+}
+
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+            pre: vec![],
+            build: None,
+        }
+    }
+
+    pub fn parse(version: &str) -> Result<Self> {
+        match parse_version(version)? {
+            (version, 3) => Ok(version),
+            _ => bail!("`{}` is not a MAJOR.MINOR.PATCH version", version),
+        }
+    }
+
+    pub fn major(&self) -> u64 {
+        self.major
+    }
+
+    pub fn minor(&self) -> u64 {
+        self.minor
+    }
+
+    pub fn patch(&self) -> u64 {
+        self.patch
+    }
+
+    pub fn is_pre(&self) -> bool {
+        !self.pre.is_empty()
+    }
+
+    // lowest pre-release of the version, so
+    // `< 2.0.0-0` leaves `2.0.0-rc.0` out
+    fn lowest_pre(mut self) -> Self {
+        self.pre = vec![Identifier::Numeric(0)];
+
+        self
+    }
+}
+
+impl TryFrom<String> for Version {
+    type Error = Error;
+
+    fn try_from(version: String) -> Result<Self> {
+        Version::parse(&version)
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let core =
+            (self.major, self.minor, self.patch).cmp(&(other.major, other.minor, other.patch));
+
+        // a pre-release comes before its version
+        let pre = match (self.pre.is_empty(), other.pre.is_empty()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => self.pre.cmp(&other.pre),
+        };
+
+        core.then(pre)
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+        if !self.pre.is_empty() {
+            let pre = self
+                .pre
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<String>>();
+
+            write!(f, "-{}", pre.join("."))?;
+        }
+
+        if let Some(build) = &self.build {
+            write!(f, "+{}", build)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Identifier::Numeric(n) => write!(f, "{}", n),
+            Identifier::Alphanumeric(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl VersionRequirement {
+    pub fn parse(requirement: &str) -> Result<Self> {
+        let mut alternatives = vec![vec![]];
+        let mut words = requirement.split_whitespace().peekable();
+
+        while let Some(word) = words.next() {
+            let (op, version) = match parse_operator(word) {
+                // `>= 1.0` and `>=1.0` alike
+                (Some(op), "") => (op, words.next()),
+                (Some(op), rest) => (op, Some(rest)),
+                (None, version) => (Operator::Eq, Some(version)),
+            };
+
+            let version = version.ok_or_else(|| anyhow!("missing version in `{}`", requirement))?;
+            let (version, parts) = parse_version(version)?;
+
+            if parts < 3 && op != Operator::Compatible {
+                bail!("`{}` needs a MAJOR.MINOR.PATCH version", requirement);
+            }
+
+            alternatives
+                .last_mut()
+                .unwrap()
+                .push(Condition { op, version, parts });
+
+            match words.next() {
+                Some("and") => {}
+                Some("or") => alternatives.push(vec![]),
+                Some(word) => bail!(
+                    "expected `and` or `or` in `{}`, found `{}`",
+                    requirement,
+                    word
+                ),
+                None => break,
+            }
+
+            if words.peek().is_none() {
+                bail!("dangling operator at the end of `{}`", requirement);
+            }
+        }
+
+        if alternatives.iter().any(Vec::is_empty) {
+            bail!("empty version requirement");
+        }
+
+        Ok(VersionRequirement {
+            raw: requirement.to_string(),
+            alternatives,
+        })
+    }
+
+    pub fn matches(&self, version: &Version) -> bool {
+        self.alternatives
+            .iter()
+            .any(|conditions| conditions.iter().all(|c| c.matches(version)))
+    }
+
+    // pre-releases are only picked when
+    // the requirement names one itself
+    pub fn allows_pre(&self) -> bool {
+        self.alternatives
+            .iter()
+            .flatten()
+            .any(|c| c.version.is_pre())
+    }
+
+    // the same kind of requirement, moved to `latest`;
+    // `~> 1.6` gives `~> 1.7` and `~> 1.6.6` gives `~> 1.7.2`,
+    // ranges and compound requirements are left to be
+    // rewritten by hand
+    pub fn bump(&self, latest: &Version) -> Option<Self> {
+        let raw = match self.alternatives.as_slice() {
+            [conditions] => match conditions.as_slice() {
+                [c] if c.op == Operator::Compatible && c.parts == 3 => format!("~> {}", latest),
+                [c] if c.op == Operator::Compatible => {
+                    format!("~> {}.{}", latest.major, latest.minor)
+                }
+                [c] if c.op == Operator::Eq && self.raw.trim().starts_with("==") => {
+                    format!("== {}", latest)
+                }
+                [c] if c.op == Operator::Eq => latest.to_string(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        VersionRequirement::parse(&raw).ok()
+    }
+
+    // the lowest version any `and` group asks
+    // for, `~> 1.6` and `>= 1.6.0` give `1.6.0`
+    pub fn minimum(&self) -> Option<&Version> {
+        self.alternatives
+            .iter()
+            .flatten()
+            .filter(|c| matches!(c.op, Operator::Compatible | Operator::Eq | Operator::Gte))
+            .map(|c| &c.version)
+            .min()
+    }
+}
+
+impl TryFrom<String> for VersionRequirement {
+    type Error = Error;
+
+    fn try_from(requirement: String) -> Result<Self> {
+        VersionRequirement::parse(&requirement)
+    }
+}
+
+impl fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl Condition {
+    fn matches(&self, version: &Version) -> bool {
+        match self.op {
+            Operator::Eq => version.eq(&self.version),
+            Operator::Ne => version.ne(&self.version),
+            Operator::Gt => version.gt(&self.version),
+            Operator::Gte => version.ge(&self.version),
+            Operator::Lt => version.lt(&self.version),
+            Operator::Lte => version.le(&self.version),
+            Operator::Compatible => {
+                let base = &self.version;
+                let upper = match self.parts {
+                    2 => Version::new(base.major + 1, 0, 0),
+                    _ => Version::new(base.major, base.minor + 1, 0),
+                };
+
+                version.ge(base) && version.lt(&upper.lowest_pre())
+            }
+        }
+    }
+}
+
+fn parse_operator(word: &str) -> (Option<Operator>, &str) {
+    let operators = [
+        ("~>", Operator::Compatible),
+        ("==", Operator::Eq),
+        ("!=", Operator::Ne),
+        (">=", Operator::Gte),
+        ("<=", Operator::Lte),
+        (">", Operator::Gt),
+        ("<", Operator::Lt),
+    ];
+
+    for (symbol, op) in operators {
+        if let Some(rest) = word.strip_prefix(symbol) {
+            return (Some(op), rest);
+        }
+    }
+
+    (None, word)
+}
+
+// `1.6` is allowed here, the number
+// of parts is returned with the version
+fn parse_version(version: &str) -> Result<(Version, usize)> {
+    let invalid = || anyhow!("`{}` is not a valid version", version);
+
+    let (version_pre, build) = match version.split_once('+') {
+        Some((version_pre, build)) if !build.is_empty() => (version_pre, Some(build.to_string())),
+        Some(_) => return Err(invalid()),
+        None => (version, None),
+    };
+
+    let (core, pre) = match version_pre.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (version_pre, None),
+    };
+
+    let numbers = core
+        .split('.')
+        // no leading zeros, `01` is not a number here
+        .map(|n| n.parse::<u64>().ok().filter(|p| p.to_string() == n))
+        .collect::<Option<Vec<u64>>>()
+        .ok_or_else(invalid)?;
+
+    let pre = match pre {
+        Some(pre) => pre
+            .split('.')
+            .map(|id| match id.parse::<u64>() {
+                _ if id.is_empty() => None,
+                Ok(n) => Some(Identifier::Numeric(n)),
+                Err(_) if id.chars().all(|c| c.is_ascii_alphanumeric() || c.eq(&'-')) => {
+                    Some(Identifier::Alphanumeric(id.to_string()))
+                }
+                Err(_) => None,
+            })
+            .collect::<Option<Vec<Identifier>>>()
+            .ok_or_else(invalid)?,
+        None => vec![],
+    };
+
+    let (major, minor, patch) = match numbers.as_slice() {
+        [major, minor] => (*major, *minor, 0),
+        [major, minor, patch] => (*major, *minor, *patch),
+        _ => return Err(invalid()),
+    };
+
+    let version = Version {
+        major,
+        minor,
+        patch,
+        pre,
+        build,
+    };
+
+    Ok((version, numbers.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    fn matches(requirement: &str, v: &str) -> bool {
+        VersionRequirement::parse(requirement)
+            .unwrap()
+            .matches(&version(v))
+    }
+
+    #[test]
+    fn parse_version() {
+        let v = version("2.0.0-rc.1+build.5");
+
+        assert_eq!((v.major(), v.minor(), v.patch()), (2, 0, 0));
+        assert!(v.is_pre());
+        assert_eq!(v.to_string(), "2.0.0-rc.1+build.5");
+    }
+
+    #[test]
+    fn parse_invalid_version() {
+        assert!(Version::parse("1.6").is_err());
+        assert!(Version::parse("1.6.x").is_err());
+        assert!(Version::parse("1.6.0-").is_err());
+        assert!(Version::parse("1.6.0+").is_err());
+    }
+
+    #[test]
+    fn compare_versions() {
+        assert!(version("1.0.0") < version("1.0.1"));
+        assert!(version("1.0.0-rc.1") < version("1.0.0"));
+        assert!(version("1.0.0-alpha") < version("1.0.0-alpha.1"));
+        assert!(version("1.0.0-2") < version("1.0.0-10"));
+        assert!(version("1.0.0-10") < version("1.0.0-beta"));
+        assert_eq!(version("1.0.0+a").cmp(&version("1.0.0+b")), Ordering::Equal);
+    }
+
+    #[test]
+    fn round_trip_requirements() {
+        let requirements = [
+            "~> 1.6.6",
+            ">= 0.0.0",
+            "~> 0.14 and >= 0.14.4",
+            "== 2.0.0-rc.1",
+            "~> 1.0 or ~> 2.0",
+            ">=1.0.0",
+            "1.2.3",
+        ];
+
+        for requirement in requirements {
+            let parsed = VersionRequirement::parse(requirement).unwrap();
+            assert_eq!(parsed.to_string(), requirement);
+        }
+    }
+
+    #[test]
+    fn parse_invalid_requirements() {
+        assert!(VersionRequirement::parse("").is_err());
+        assert!(VersionRequirement::parse(">= 1.0").is_err());
+        assert!(VersionRequirement::parse("~> 1.0 and").is_err());
+        assert!(VersionRequirement::parse("~> 1.0 xor 2.0.0").is_err());
+        assert!(VersionRequirement::parse("~>").is_err());
+    }
+
+    #[test]
+    fn match_compatible() {
+        assert!(matches("~> 1.6.6", "1.6.9"));
+        assert!(!matches("~> 1.6.6", "1.7.0"));
+        assert!(!matches("~> 1.6.6", "1.6.5"));
+        assert!(matches("~> 1.6", "1.9.0"));
+        assert!(!matches("~> 1.6", "2.0.0"));
+        assert!(!matches("~> 1.6", "2.0.0-rc.0"));
+    }
+
+    #[test]
+    fn match_operators() {
+        assert!(matches(">= 0.0.0", "0.1.0"));
+        assert!(matches("== 1.0.0", "1.0.0"));
+        assert!(matches("1.0.0", "1.0.0"));
+        assert!(matches("!= 1.0.0", "1.0.1"));
+        assert!(matches("< 1.0.0", "1.0.0-rc.1"));
+        assert!(matches("<= 1.0.0", "1.0.0"));
+        assert!(!matches("> 1.0.0", "1.0.0"));
+    }
+
+    #[test]
+    fn match_and_or() {
+        assert!(matches("~> 0.14 and >= 0.14.4", "0.14.4"));
+        assert!(!matches("~> 0.14 and >= 0.14.4", "0.14.3"));
+        assert!(matches("~> 1.0 or ~> 2.0", "2.3.0"));
+        assert!(!matches("~> 1.0 or ~> 2.0", "3.0.0"));
+        assert!(matches("< 1.0.0 or >= 2.0.0 and < 3.0.0", "2.1.0"));
+    }
+
+    #[test]
+    fn bump_requirements() {
+        let bump = |requirement: &str, latest: &str| {
+            VersionRequirement::parse(requirement)
+                .unwrap()
+                .bump(&version(latest))
+                .map(|r| r.to_string())
+        };
+
+        assert_eq!(bump("~> 1.6", "1.7.2").unwrap(), "~> 1.7");
+        assert_eq!(bump("~> 1.6.6", "1.7.2").unwrap(), "~> 1.7.2");
+        assert_eq!(bump("== 1.0.0", "2.0.1").unwrap(), "== 2.0.1");
+        assert_eq!(bump("1.0.0", "2.0.1").unwrap(), "2.0.1");
+        assert_eq!(bump("~> 0.14 and >= 0.14.4", "0.16.1"), None);
+        assert_eq!(bump("< 1.0.0", "1.2.0"), None);
+        assert_eq!(bump(">= 1.0.0 and < 2.0.0", "2.1.0"), None);
+    }
+
+    #[test]
+    fn minimum_version() {
+        let requirement = VersionRequirement::parse("~> 0.14 and >= 0.14.4").unwrap();
+
+        assert_eq!(requirement.minimum(), Some(&version("0.14.0")));
+    }
+}
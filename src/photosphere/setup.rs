@@ -1,24 +1,24 @@
 use super::{
+    manifest::{Manifest, MANIFEST_FILE},
+    plan::Plan,
     service,
-    service::{dep::Dep, Service},
-    str_utils,
+    service::{
+        dep::Dep,
+        feature::{Catalog, CATALOG_DIR},
+        Service,
+    },
+    staging::{self, Staging},
+    template::{self, Source},
     validations::get_project_name,
 };
 use crate::ServiceArgs;
-use anyhow::Result;
-use std::{io::Error, path::Path, process::Command};
+use anyhow::{bail, Result};
 use walkdir::WalkDir;
 
-const WITH_SPACE_DEFAULT: &'static &str = &"Service Template";
-const PASCAL_CASE_DEFAULT: &'static &str = &"ServiceTemplate";
-const KEBAB_CASE_DEFAULT: &'static &str = REPO_NAME;
-const REPO_NAME: &'static &str = &"service-template";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const HTTPS_URL: &'static &str = &"https://github.com/solfacil/service-template";
 const SSH_URL: &'static &str = &"git@github.com:solfacil/service-template";
 
-pub const SNAKE_CASE_DEFAULT: &'static &str = &"service_template";
-
 pub fn build_partial_service(service_path: &str, is_ssh: bool) -> Service {
     let mut default_service = Service::default();
 
@@ -31,21 +31,49 @@ pub fn build_partial_service(service_path: &str, is_ssh: bool) -> Service {
         .clone()
 }
 
+// generated in a staging dir and moved to the service
+// path once done, nothing is left behind on failure
 pub fn create_service(service: &mut Service, args: &ServiceArgs) -> Result<()> {
-    let repo_url = get_repo_url(service.ssh);
-    clone_repository(&repo_url, &service.path)?;
+    let target = service.path.clone();
+    let staging = Staging::new(&target, args.keep_failed)?;
+    staging::catch_interrupt()?;
+
+    service.set_path(staging.path().display().to_string());
+    let mut plan = Plan::new(args.dry_run || args.diff);
+    let manifest = generate_service(service, args, &mut plan)?;
+
+    // the staging dir goes with the plan printed
+    if args.dry_run {
+        match args.diff {
+            true => print!("{}", plan.unified_diff()),
+            false => println!("{}", plan.render(args.format)?),
+        }
+        staging.discard();
+
+        return Ok(());
+    }
+
+    staging::check_interrupt()?;
+    staging.commit()?;
+    service.set_path(target.display().to_string());
+
+    if args.diff {
+        print!("{}", plan.unified_diff());
+    }
 
-    setup_service(service, args)?;
+    let instructions = manifest
+        .instructions(&service.name, &service.path)
+        .iter()
+        .map(|i| format!("$ {}", i))
+        .collect::<Vec<String>>();
 
     println!(
         "\u{001b}[32m \nGenerated {} with Photosphere {} \u{001b}[0m\n\n\
          Next:\n\
-         $ cd {}\n\
-         $ mix setup - to get dependencies\n\
-         $ iex -S mix phx.server - to set up the service server",
+         {}",
         service.name,
         VERSION,
-        service.path.as_path().display()
+        instructions.join("\n")
     );
 
     println!(
@@ -56,52 +84,146 @@ pub fn create_service(service: &mut Service, args: &ServiceArgs) -> Result<()> {
     Ok(())
 }
 
-fn clone_repository(url: &str, dest: &Path) -> Result<(), Error> {
-    let dest_os = dest.as_os_str();
+fn generate_service(
+    service: &mut Service,
+    args: &ServiceArgs,
+    plan: &mut Plan,
+) -> Result<Manifest> {
+    let source = match &args.template {
+        Some(template) => Source::parse(template)?,
+        None => Source::Git(get_repo_url(service.ssh)),
+    };
+
+    if args.git_ref.is_some() && !source.is_git() {
+        bail!("--ref only applies to a git template");
+    }
 
-    Command::new("git")
-        .arg("clone")
-        .arg(url)
-        .arg(dest_os)
-        .arg("-b")
-        .arg("photosphere-test")
-        .status()?;
+    source.fetch(&service.path)?;
+    staging::check_interrupt()?;
+
+    // `--ref` wins over what the default branch manifest names,
+    // the built-in one only knows about our own template
+    let has_manifest = service.path.join(MANIFEST_FILE).exists();
+    let mut manifest = Manifest::load(&service.path)?;
+    let git_ref = match (&args.git_ref, has_manifest || args.template.is_none()) {
+        (Some(git_ref), _) => Some(git_ref.to_string()),
+        (None, true) => manifest.git_ref().map(|r| r.to_string()),
+        (None, false) => None,
+    };
+
+    if let (true, Some(git_ref)) = (source.is_git(), git_ref) {
+        template::checkout(&service.path, &git_ref)?;
+        manifest = Manifest::load(&service.path)?;
+    }
 
-    Ok(())
+    if service.name == manifest.placeholders().snake_case() {
+        bail!(
+            "Hey...that's my name! Please name your project something other than {}.",
+            service.name
+        );
+    }
+
+    staging::check_interrupt()?;
+    setup_service(service, args, &manifest, plan)?;
+
+    Ok(manifest)
 }
 
-fn setup_service(service: &mut Service, args: &ServiceArgs) -> Result<()> {
+fn setup_service(
+    service: &mut Service,
+    args: &ServiceArgs,
+    manifest: &Manifest,
+    plan: &mut Plan,
+) -> Result<()> {
     let deps = service::de::parse_deps(&service.path)?;
+    let catalog = Catalog::load(&service.path)?;
+
+    let disabled = disabled_flags(args)
+        .iter()
+        .map(|flag| catalog.find_flag(flag).map(|f| f.name().to_string()))
+        .collect::<Result<Vec<String>>>()?;
+
+    if args.health && args.protocol.is_rest() {
+        eprintln!(
+            "\u{001b}[33mwarning: --health does nothing, REST already serves /health\u{001b}[0m"
+        );
+    }
 
     // set deps first to filter them after
     service
-        .set_deps(deps)
-        .set_no_auth(args.no_auth)
-        .set_no_database(args.no_database)
-        .set_no_graphql(args.no_graphql)
-        .set_no_http_client(args.no_http_client)
-        .set_no_mailer(args.no_mailer)
-        .set_no_messaging(args.no_messaging)
-        .set_no_monitoring(args.no_monitoring)
+        .set_catalog(catalog)
+        .set_deps(deps.clone())
+        .set_git_scheme(args.rewrite_git_deps)
+        .set_health(args.health)
         .set_protocol(args.protocol);
 
+    for name in disabled.iter() {
+        service.set_no_feature(name, true);
+    }
+
+    let outcome = service.apply_rules()?;
+
+    for warning in outcome.warnings() {
+        eprintln!("\u{001b}[33mwarning: {}\u{001b}[0m", warning);
+    }
+
+    if args.explain {
+        eprintln!("{}", outcome.explain());
+    }
+
+    plan.set_dropped_deps(dropped_deps(service, &deps));
+
     let root_path = service.path.as_path();
 
     // We don't need our `service_template` commit history anymore
     let git_path = root_path.join(".git");
-    std::fs::remove_dir_all(git_path)?;
+    if git_path.exists() {
+        std::fs::remove_dir_all(git_path)?;
+    }
+
+    // nor the catalog and the manifest
+    plan.record("template", root_path, || {
+        let catalog_path = root_path.join(CATALOG_DIR);
+        if catalog_path.exists() {
+            std::fs::remove_dir_all(catalog_path)?;
+        }
 
-    // Also we don't need old `mix.lock`
-    let lock_path = root_path.join("mix.lock");
-    std::fs::remove_file(lock_path)?;
+        let manifest_path = root_path.join(MANIFEST_FILE);
+        if manifest_path.exists() {
+            std::fs::remove_file(manifest_path)?;
+        }
 
-    rename_source(service)?;
+        Ok(())
+    })?;
 
-    apply_config(service)?;
+    plan.record_rename(manifest, &service.name, root_path, || {
+        rename_source(service, manifest)
+    })?;
+    staging::check_interrupt()?;
+
+    apply_config(service, plan)?;
 
     Ok(())
 }
 
+// the `--no-*` flags given and the `--without` ones
+fn disabled_flags(args: &ServiceArgs) -> Vec<String> {
+    [
+        ("no-auth", args.no_auth),
+        ("no-database", args.no_database),
+        ("no-graphql", args.no_graphql),
+        ("no-http-client", args.no_http_client),
+        ("no-mailer", args.no_mailer),
+        ("no-messaging", args.no_messaging),
+        ("no-monitoring", args.no_monitoring),
+    ]
+    .into_iter()
+    .filter(|(_, no)| *no)
+    .map(|(flag, _)| flag.to_string())
+    .chain(args.without.iter().cloned())
+    .collect()
+}
+
 fn get_repo_url(is_ssh: bool) -> String {
     if is_ssh {
         return SSH_URL.to_string();
@@ -110,111 +232,127 @@ fn get_repo_url(is_ssh: bool) -> String {
     HTTPS_URL.to_string()
 }
 
-fn apply_config(service: &Service) -> Result<()> {
-    if !service.auth {
-        service::ser::nuke_auth(service)?;
-    }
+fn apply_config(service: &Service, plan: &mut Plan) -> Result<()> {
+    let root = service.path.as_path();
 
-    if !service.database {
-        service::ser::nuke_database(service)?;
-    }
+    for feature in service.disabled_features() {
+        let step = format!("without {}", feature.name());
 
-    if !service.graphql || service.protocol.is_grpc() {
-        service::ser::nuke_graphql(service)?;
-    }
-
-    if service.protocol.is_rest() {
-        service::ser::nuke_grpc(service)?;
-    }
-
-    if !service.http_client {
-        service::ser::nuke_http_client(service)?;
-    }
-
-    if !service.mailer {
-        service::ser::nuke_mailer(service)?;
-    }
+        plan.record(&step, root, || {
+            service::ser::nuke_feature(service, feature)?;
 
-    if !service.messaging {
-        service::ser::nuke_messaging(service)?;
-    }
+            match feature.name() {
+                "grpc" => service::ser::nuke_grpc(service)?,
+                "rest" => service::ser::nuke_rest(service)?,
+                _ => {} // the catalog is all there is to it
+            }
 
-    if !service.monitoring {
-        service::ser::nuke_monitoring(service)?;
+            Ok(())
+        })?;
     }
 
-    if !service.protocol.is_rest() {
-        service::ser::nuke_rest(service)?;
-    }
-
-    service::ser::dump_deps(service)?;
+    plan.record("deps", root, || {
+        // before `mix.exs` loses the removed deps
+        service::lock::prune_lock(service)?;
+        service::ser::dump_deps(service)
+    })
+}
 
-    Ok(())
+// the template deps the service goes without, and
+// the disabled feature that took each of them out
+fn dropped_deps(service: &Service, template_deps: &[Dep]) -> Vec<(String, String)> {
+    template_deps
+        .iter()
+        .filter(|d| !service.deps.iter().any(|kept| kept.name() == d.name()))
+        .map(|d| {
+            let feature = service
+                .disabled_features()
+                .into_iter()
+                .find(|f| f.owns(d))
+                .map(|f| f.name().to_string())
+                .unwrap_or_default();
+
+            (d.name().to_string(), feature)
+        })
+        .collect()
 }
 
-fn rename_source(service: &Service) -> Result<()> {
-    let file_entries = WalkDir::new(&service.path)
+fn rename_source(service: &Service, manifest: &Manifest) -> Result<()> {
+    let root = service.path.as_path();
+
+    let file_entries = WalkDir::new(root)
         .into_iter()
         .filter_map(|e| e.ok())
-        .filter(|e| !e.path().is_dir());
+        .filter(|e| !e.path().is_dir())
+        .filter(|e| {
+            let relative = e.path().strip_prefix(root).unwrap_or(e.path());
+            !manifest.is_excluded(relative)
+        });
 
     for entry in file_entries {
         let data = std::fs::read_to_string(entry.path())?;
 
-        let kabeb_name = str_utils::to_kebab_case(&service.name);
-        let with_space = str_utils::to_title(&service.name);
-        let pascal_name = str_utils::to_pascal_case(&service.name);
-
-        let new_data = data
-            .replace(SNAKE_CASE_DEFAULT, &service.name) // new name is already snake_case
-            .replace(WITH_SPACE_DEFAULT, &with_space)
-            .replace(KEBAB_CASE_DEFAULT, &kabeb_name)
-            .replace(PASCAL_CASE_DEFAULT, &pascal_name);
+        // new name is already snake_case
+        let new_data = manifest.placeholders().replace(&data, &service.name);
 
         std::fs::write(entry.path(), new_data)?;
     }
 
-    std::fs::rename(
-        service.path.as_path().join("lib").join(SNAKE_CASE_DEFAULT),
-        service.path.as_path().join("lib").join(&service.name),
-    )?;
-    std::fs::rename(
-        service
-            .path
-            .as_path()
-            .join("lib")
-            .join(format!("{}_web", SNAKE_CASE_DEFAULT)),
-        service
-            .path
-            .as_path()
-            .join("lib")
-            .join(format!("{}_web", &service.name)),
-    )?;
+    for (from, to) in manifest.renames(&service.name) {
+        let from_path = root.join(&from);
 
-    std::fs::rename(
-        service
-            .path
-            .as_path()
-            .join("lib")
-            .join(format!("{}.ex", SNAKE_CASE_DEFAULT)),
-        service
-            .path
-            .as_path()
-            .join("lib")
-            .join(format!("{}.ex", &service.name)),
-    )?;
-    std::fs::rename(
-        service
-            .path
-            .as_path()
-            .join("lib")
-            .join(format!("{}_web.ex", SNAKE_CASE_DEFAULT)),
-        service
-            .path
-            .as_path()
-            .join("lib")
-            .join(format!("{}_web.ex", &service.name)),
-    )?;
+        if !from_path.exists() {
+            bail!("{} to rename is not in the template", from);
+        }
+
+        std::fs::rename(from_path, root.join(to))?;
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn rename_nested_source() {
+        let manifest = Manifest::parse(
+            r#"
+rename = ["lib/acme_template", "lib/acme_template/acme_template.ex"]
+
+[placeholders]
+snake_case = "acme_template"
+pascal_case = "AcmeTemplate"
+kebab_case = "acme-template"
+title = "Acme Template"
+"#,
+        )
+        .unwrap();
+        let root = std::env::temp_dir().join("photosphere_rename_nested_source");
+        fs::create_dir_all(root.join("lib").join("acme_template")).unwrap();
+        fs::write(
+            root.join("lib")
+                .join("acme_template")
+                .join("acme_template.ex"),
+            "defmodule AcmeTemplate.AcmeTemplate do\nend\n",
+        )
+        .unwrap();
+
+        let mut service = Service::default();
+        service
+            .set_name("foo".to_string())
+            .set_path(root.display().to_string());
+
+        rename_source(&service, &manifest).unwrap();
+
+        assert!(!root.join("lib").join("acme_template").exists());
+        assert_eq!(
+            fs::read_to_string(root.join("lib").join("foo").join("foo.ex")).unwrap(),
+            "defmodule Foo.Foo do\nend\n"
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+use anyhow::{anyhow, bail, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tempfile::TempDir;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Where a service is generated before it is moved to
+// its path, so a failed generation leaves nothing behind
+pub struct Staging {
+    dir: Option<TempDir>,
+    keep_failed: bool,
+    name: String,
+    target: PathBuf,
+}
+
+impl Staging {
+    // next to the target, so moving it is a rename
+    pub fn new(target: &Path, keep_failed: bool) -> Result<Self> {
+        if target.exists() {
+            bail!("{} already exists", target.display());
+        }
+
+        let name = target
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("{} is not a directory name", target.display()))?;
+        let parent = match target.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        let dir = tempfile::Builder::new()
+            .prefix(".photosphere-")
+            .tempdir_in(parent)?;
+
+        Ok(Staging {
+            dir: Some(dir),
+            keep_failed,
+            name,
+            target: target.to_path_buf(),
+        })
+    }
+
+    // doesn't exist until the template is cloned into it
+    pub fn path(&self) -> PathBuf {
+        let dir = self.dir.as_ref().expect("staging dir");
+
+        dir.path().join(&self.name)
+    }
+
+    // dropped without keeping it, it did not fail
+    pub fn discard(mut self) {
+        self.keep_failed = false;
+    }
+
+    pub fn commit(self) -> Result<()> {
+        if self.target.exists() {
+            bail!("{} already exists", self.target.display());
+        }
+
+        fs::rename(self.path(), &self.target)?;
+
+        Ok(())
+    }
+}
+
+impl Drop for Staging {
+    // removed unless it was asked to be kept; after a
+    // commit only the empty staging dir is left to remove
+    fn drop(&mut self) {
+        let dir = match self.dir.take() {
+            Some(dir) => dir,
+            None => return,
+        };
+
+        if self.keep_failed && dir.path().join(&self.name).exists() {
+            let path = dir.keep().join(&self.name);
+            eprintln!("The failed service was kept at {}", path.display());
+        }
+    }
+}
+
+// Ctrl-C fails the step being run instead of killing
+// the process, so the staging dir is cleaned up
+pub fn catch_interrupt() -> Result<()> {
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))
+        .map_err(|e| anyhow!("could not catch Ctrl-C: {}", e))
+}
+
+pub fn check_interrupt() -> Result<()> {
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        bail!("interrupted");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_staging() {
+        let root = std::env::temp_dir().join("photosphere_commit_staging");
+        fs::create_dir_all(&root).unwrap();
+        let target = root.join("foo");
+
+        let staging = Staging::new(&target, false).unwrap();
+        fs::create_dir_all(staging.path()).unwrap();
+        fs::write(staging.path().join("mix.exs"), "").unwrap();
+
+        assert!(!target.exists());
+
+        staging.commit().unwrap();
+
+        assert!(target.join("mix.exs").exists());
+        // nothing but the service left behind
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+        assert!(Staging::new(&target, false).is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn drop_failed_staging() {
+        let root = std::env::temp_dir().join("photosphere_drop_failed_staging");
+        fs::create_dir_all(&root).unwrap();
+        let target = root.join("foo");
+
+        let staging = Staging::new(&target, false).unwrap();
+        fs::create_dir_all(staging.path()).unwrap();
+        drop(staging);
+
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+
+        let staging = Staging::new(&target, true).unwrap();
+        let path = staging.path();
+        fs::create_dir_all(&path).unwrap();
+        drop(staging);
+
+        assert!(path.exists());
+        assert!(!target.exists());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,256 @@
+use anyhow::{anyhow, bail, Result};
+use flate2::read::GzDecoder;
+use ignore::WalkBuilder;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+// Where the template comes from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    Dir(PathBuf),     // copied, without what it ignores
+    Git(String),      // a URL or a bare mirror
+    Tarball(PathBuf), // `.tar.gz` or `.tgz`
+}
+
+impl Source {
+    // an existing path is a local template, anything else
+    // a git URL unless it was meant to be a path
+    pub fn parse(template: &str) -> Result<Self> {
+        let path = PathBuf::from(template);
+        let is_tarball = template.ends_with(".tar.gz") || template.ends_with(".tgz");
+
+        if path.is_dir() && !is_bare_repository(&path) {
+            return Ok(Source::Dir(path));
+        }
+
+        if path.is_file() && is_tarball {
+            return Ok(Source::Tarball(path));
+        }
+
+        if !path.exists() && (is_tarball || is_path(template)) {
+            bail!("no such template {}", template);
+        }
+
+        Ok(Source::Git(template.to_string()))
+    }
+
+    pub fn is_git(&self) -> bool {
+        matches!(self, Source::Git(_))
+    }
+
+    // puts the template at `dest`, which must not exist yet
+    pub fn fetch(&self, dest: &Path) -> Result<()> {
+        match self {
+            Source::Dir(path) => copy_dir(path, dest),
+            Source::Git(url) => clone_repository(url, dest),
+            Source::Tarball(path) => unpack(path, dest),
+        }
+    }
+}
+
+pub fn clone_repository(url: &str, dest: &Path) -> Result<()> {
+    let dest_os = dest.as_os_str();
+
+    let status = Command::new("git")
+        .arg("clone")
+        .arg(url)
+        .arg(dest_os)
+        .status()?;
+
+    if !status.success() {
+        bail!("could not clone {}", url);
+    }
+
+    Ok(())
+}
+
+pub fn checkout(repo: &Path, git_ref: &str) -> Result<()> {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(repo.as_os_str())
+        .arg("checkout")
+        .arg("--quiet")
+        .arg(git_ref)
+        .status()?;
+
+    if !status.success() {
+        bail!("could not check out `{}` of the template", git_ref);
+    }
+
+    Ok(())
+}
+
+// `./tpl`, `../tpl`, `/tpl` or `~/tpl`
+fn is_path(template: &str) -> bool {
+    ["./", "../", "/", "~/"]
+        .iter()
+        .any(|prefix| template.starts_with(prefix))
+}
+
+// `HEAD`, `objects` and `refs` with no working tree
+fn is_bare_repository(path: &Path) -> bool {
+    ["HEAD", "objects", "refs"]
+        .iter()
+        .all(|entry| path.join(entry).exists())
+}
+
+// only the template's own `.gitignore` files are followed, even
+// outside a git repository, not those around it or the user's
+fn copy_dir(from: &Path, dest: &Path) -> Result<()> {
+    let entries = WalkBuilder::new(from)
+        .hidden(false)
+        .git_global(false)
+        .git_exclude(false)
+        .parents(false)
+        .ignore(false)
+        .require_git(false)
+        .filter_entry(|e| e.file_name() != ".git")
+        .build();
+
+    for entry in entries {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(from)?;
+        let target = dest.join(relative);
+
+        match entry.file_type() {
+            Some(t) if t.is_dir() => fs::create_dir_all(target)?,
+            _ => {
+                fs::copy(entry.path(), target)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// a single top level directory, as GitHub
+// archives have, is the template itself
+fn unpack(tarball: &Path, dest: &Path) -> Result<()> {
+    let parent = dest
+        .parent()
+        .ok_or_else(|| anyhow!("{} has no parent directory", dest.display()))?;
+    let unpacked = tempfile::Builder::new()
+        .prefix(".photosphere-")
+        .tempdir_in(parent)?;
+
+    let file = fs::File::open(tarball)
+        .map_err(|e| anyhow!("could not open {}: {}", tarball.display(), e))?;
+    tar::Archive::new(GzDecoder::new(file)).unpack(unpacked.path())?;
+
+    let entries = fs::read_dir(unpacked.path())?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect::<Vec<PathBuf>>();
+
+    match entries.as_slice() {
+        [root] if root.is_dir() => fs::rename(root, dest)?,
+        _ => fs::rename(unpacked.keep(), dest)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+
+    #[test]
+    fn parse_source() {
+        let root = std::env::temp_dir().join("photosphere_parse_source");
+        let bare = root.join("template.git");
+        fs::create_dir_all(bare.join("objects")).unwrap();
+        fs::create_dir_all(bare.join("refs")).unwrap();
+        fs::write(bare.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(root.join("template.tar.gz"), "").unwrap();
+
+        let parse = |path: &Path| Source::parse(&path.display().to_string());
+
+        assert_eq!(parse(&root).unwrap(), Source::Dir(root.clone()));
+        assert!(parse(&bare).unwrap().is_git());
+        assert_eq!(
+            parse(&root.join("template.tar.gz")).unwrap(),
+            Source::Tarball(root.join("template.tar.gz"))
+        );
+        assert_eq!(
+            Source::parse("git@github.com:acme/template.git").unwrap(),
+            Source::Git("git@github.com:acme/template.git".to_string())
+        );
+        assert!(parse(&root.join("missing")).is_err());
+        assert!(Source::parse("missing.tar.gz").is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn copy_template_dir() {
+        let root = std::env::temp_dir().join("photosphere_copy_template_dir");
+        let template = root.join("template");
+        // around the template, not for it
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(".gitignore"), "*.ex\n").unwrap();
+        fs::create_dir_all(template.join("lib")).unwrap();
+        fs::create_dir_all(template.join("_build").join("dev")).unwrap();
+        fs::create_dir_all(template.join(".git")).unwrap();
+        fs::write(template.join(".gitignore"), "/_build\n*.beam\n").unwrap();
+        fs::write(template.join(".env-sample"), "PORT=4000\n").unwrap();
+        fs::write(template.join(".ignore"), "lib\n").unwrap();
+        fs::write(template.join("lib").join("foo.ex"), "").unwrap();
+        fs::write(template.join("lib").join("foo.beam"), "").unwrap();
+        fs::write(template.join("_build").join("dev").join("foo"), "").unwrap();
+        fs::write(template.join(".git").join("HEAD"), "").unwrap();
+
+        let dest = root.join("service");
+        Source::parse(&template.display().to_string())
+            .unwrap()
+            .fetch(&dest)
+            .unwrap();
+
+        assert!(dest.join(".gitignore").exists());
+        assert!(dest.join(".env-sample").exists());
+        assert!(dest.join("lib").join("foo.ex").exists());
+        assert!(!dest.join("lib").join("foo.beam").exists());
+        assert!(!dest.join("_build").exists());
+        assert!(!dest.join(".git").exists());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn unpack_tarball() {
+        let root = std::env::temp_dir().join("photosphere_unpack_tarball");
+        fs::create_dir_all(&root).unwrap();
+        let tarball = root.join("template.tar.gz");
+
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            fs::File::create(&tarball).unwrap(),
+            Compression::default(),
+        ));
+        let data = b"defmodule Foo do\nend\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "template-main/lib/foo.ex", &data[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let dest = root.join("service");
+        Source::parse(&tarball.display().to_string())
+            .unwrap()
+            .fetch(&dest)
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dest.join("lib").join("foo.ex")).unwrap(),
+            "defmodule Foo do\nend\n"
+        );
+        // the unpacking dir is gone
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 2);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,9 +1,12 @@
-use super::{setup::SNAKE_CASE_DEFAULT, str_utils};
+use super::{manifest::Manifest, str_utils};
 use anyhow::{bail, Result};
 
+// the template is not known yet, the
+// built-in manifest names the default one
 pub fn validate_project_name(path: &str) -> Result<String> {
     let name = get_project_name(path);
-    let is_same_default = name.to_lowercase().eq(SNAKE_CASE_DEFAULT);
+    let manifest = Manifest::default();
+    let is_same_default = name.to_lowercase().eq(manifest.placeholders().snake_case());
     let has_hifen = name.contains('-');
     let is_valid_name = name.chars().all(str_utils::is_lower_alphanumeric);
 
@@ -23,7 +26,5 @@ pub fn validate_project_name(path: &str) -> Result<String> {
 }
 
 pub fn get_project_name(path: &str) -> &str {
-    let path_entries = path.split('/').collect::<Vec<&str>>();
-
-    path_entries.last().unwrap_or(SNAKE_CASE_DEFAULT)
+    path.rsplit('/').next().unwrap_or(path)
 }